@@ -0,0 +1,69 @@
+//! Save and load times of a large world as JSON and as a bincode snapshot.
+//!
+//! Run with `cargo bench --bench save_formats`. Saves a 100-player world in
+//! both formats and reports file size and the mean save and load time of each.
+
+use flight_sim::{Character, GameState};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const PLAYERS: usize = 100;
+const ROUNDS: u32 = 50;
+
+// A world busy enough to look like a large deployment
+fn populated_state() -> GameState {
+    let mut state = GameState::new();
+    for i in 0..PLAYERS {
+        let id = Uuid::new_v4();
+        let mut character = Character::new(id, format!("Citizen {}", i), "Pilot".to_string());
+        for entry in 0..10 {
+            character.journal_entries.push(format!(
+                "Entry {}: the clocks were striking thirteen.",
+                entry
+            ));
+        }
+        character.inventory.push("Victory Gin".to_string());
+        state.players.insert(id, character);
+    }
+    state
+}
+
+fn time(mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        f();
+    }
+    start.elapsed() / ROUNDS
+}
+
+fn report(state: &GameState, path: &Path) {
+    let save = time(|| state.save_to_path(path).unwrap());
+    let load = time(|| {
+        GameState::load_from_path(path).unwrap();
+    });
+    let size = std::fs::metadata(path).unwrap().len();
+    std::fs::remove_file(path).unwrap();
+    println!(
+        "{:<6} size: {:>9} bytes  save: {:>10.1?}  load: {:>10.1?}",
+        path.extension().unwrap().to_string_lossy(),
+        size,
+        save,
+        load
+    );
+}
+
+fn temp_path(extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "flight_sim_bench_{}.{}",
+        std::process::id(),
+        extension
+    ))
+}
+
+fn main() {
+    let state = populated_state();
+    println!("{} players, mean of {} rounds", PLAYERS, ROUNDS);
+    report(&state, &temp_path("json"));
+    report(&state, &temp_path("bin"));
+}
@@ -0,0 +1,94 @@
+//! Address ranges the server accepts connections from.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A block of addresses in CIDR notation, such as `10.0.0.0/8` or `::1/128`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct CidrBlock {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl CidrBlock {
+    /// Whether `addr` lies in the block; IPv4 addresses mapped into IPv6 count as IPv4
+    pub fn contains(&self, addr: &IpAddr) -> bool {
+        match (self.network, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(addr) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(self.prefix_len))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(addr) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for CidrBlock {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (network, prefix_len) = match s.split_once('/') {
+            Some((network, prefix_len)) => (network, Some(prefix_len)),
+            None => (s, None),
+        };
+        let network: IpAddr = network
+            .trim()
+            .parse()
+            .map_err(|_| format!("{} is not an IP address", network))?;
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("{} is not a prefix length from 0 to {}", len, max_len))?,
+            // A bare address is a block of one
+            None => max_len,
+        };
+        Ok(CidrBlock {
+            network,
+            prefix_len,
+        })
+    }
+}
+
+impl TryFrom<String> for CidrBlock {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<CidrBlock> for String {
+    fn from(block: CidrBlock) -> Self {
+        block.to_string()
+    }
+}
+
+impl fmt::Display for CidrBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.network, self.prefix_len)
+    }
+}
+
+/// Whether a connection from `addr` is allowed by `allowlist`; an empty list allows everyone,
+/// and an unknown address is only allowed then
+pub fn is_allowed(allowlist: &[CidrBlock], addr: Option<&IpAddr>) -> bool {
+    if allowlist.is_empty() {
+        return true;
+    }
+    addr.is_some_and(|addr| allowlist.iter().any(|block| block.contains(addr)))
+}
@@ -0,0 +1,260 @@
+//! Server and world settings loaded from a TOML file.
+
+use crate::access::CidrBlock;
+use crate::newspeak::NewspeakConfig;
+use crate::physics::PhysicsConfig;
+use crate::rpg_structs::{Location, WorldState};
+use crate::DEFAULT_TICK_HZ;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Settings for a server run; every field falls back to its built-in default
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// IP address to bind to
+    pub ip: IpAddr,
+    /// Port to bind to
+    pub port: u16,
+    /// Address ranges allowed to connect; empty allows everyone
+    pub allow_cidr: Vec<CidrBlock>,
+    /// Game loop tick rate in Hz
+    pub tick_hz: f32,
+    /// Rate in Hz that moving aircraft are sent to clients, at most `tick_hz`
+    pub broadcast_hz: f32,
+    /// Most extra ticks run back to back when the loop falls behind real time; time beyond that
+    /// is dropped, and 0 never catches up so in-game time slows under load instead
+    pub max_catch_up_ticks: u32,
+    /// Maximum number of simultaneous connections
+    pub max_players: usize,
+    /// Messages queued for a client before it is disconnected as too slow to keep up
+    pub client_buffer: usize,
+    /// Longest text, in characters, accepted in any free-text field of a client message;
+    /// longer messages are rejected whole
+    pub max_text_len: usize,
+    /// Base wind strength in m/s (0 disables wind and turbulence)
+    pub wind: f32,
+    /// Gravity, drag, thrust and ground friction of the aircraft characters fly
+    pub physics: PhysicsConfig,
+    /// File to load the world from at startup and save it to on shutdown; JSON, or a bincode
+    /// snapshot when the extension is `.bin`
+    pub save_file: Option<PathBuf>,
+    /// Minutes between autosaves to `save_file` (0 disables autosave)
+    pub autosave_minutes: u64,
+    /// Deflate large messages for clients that ask for it; turn off to read frames while debugging
+    pub compress: bool,
+    /// Keep characters of disconnected players so they can be reclaimed by name
+    pub restore_characters: bool,
+    /// Seconds a disconnected player's character stays in the game, frozen, before it is
+    /// removed (0 removes it at once)
+    pub disconnect_grace_secs: u64,
+    /// Seconds without a message before a grounded, stationary player is disconnected (0
+    /// disables it)
+    pub idle_timeout_secs: u64,
+    /// Party tasks a character must complete before they can open the throttle; pilots and the
+    /// Inner Party can fly from the start, and 0 lets everyone fly
+    pub flight_min_tasks: u32,
+    /// When characters die or are arrested, and what happens to them then
+    pub end_conditions: EndConditions,
+    /// How NPCs' trust shifts with what players do and what happens in the world
+    pub trust_dynamics: TrustDynamics,
+    /// Let players change their occupation after creation; off, occupations are fixed for life
+    pub allow_occupation_change: bool,
+    /// Directory the browser client is served from
+    pub web_dir: PathBuf,
+    /// Token required in the `x-admin-token` header of admin endpoints; `None` leaves them open
+    /// but refuses admin commands sent over the WebSocket
+    pub admin_token: Option<String>,
+    /// Seed for random game events; `None` seeds from the OS for a different world every run
+    pub seed: Option<u64>,
+    /// File every handled client message is recorded to, for replaying the session later
+    pub record_file: Option<PathBuf>,
+    /// Overrides applied to the built-in starting world
+    pub world: WorldConfig,
+    /// Oldspeak filter for journal entries
+    pub newspeak: NewspeakConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 8080,
+            allow_cidr: Vec::new(),
+            tick_hz: DEFAULT_TICK_HZ,
+            broadcast_hz: 10.0,
+            max_catch_up_ticks: 5,
+            max_players: 64,
+            client_buffer: 256,
+            max_text_len: 2000,
+            wind: 2.0,
+            physics: PhysicsConfig::default(),
+            save_file: None,
+            autosave_minutes: 5,
+            compress: true,
+            restore_characters: true,
+            disconnect_grace_secs: 30,
+            idle_timeout_secs: 900,
+            flight_min_tasks: 5,
+            end_conditions: EndConditions::default(),
+            trust_dynamics: TrustDynamics::default(),
+            allow_occupation_change: false,
+            web_dir: PathBuf::from("web"),
+            admin_token: None,
+            seed: None,
+            record_file: None,
+            world: WorldConfig::default(),
+            newspeak: NewspeakConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Read a config from a TOML file
+    pub fn load_from_path(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Socket address built from `ip` and `port`
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port)
+    }
+
+    /// How long a disconnected player's character lingers before it is removed
+    pub fn disconnect_grace(&self) -> Duration {
+        Duration::from_secs(self.disconnect_grace_secs)
+    }
+
+    /// Autosave period, or `None` when autosave is disabled
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        (self.autosave_minutes > 0).then(|| Duration::from_secs(self.autosave_minutes * 60))
+    }
+}
+
+/// What the Thought Police do with a character they arrest
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrestConsequence {
+    /// Questioned by O'Brien, who may send them home reformed
+    #[default]
+    Interrogation,
+    /// Vaporized on the spot, becoming an unperson
+    Removal,
+}
+
+/// Thresholds at which a character's game ends, and how harshly
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndConditions {
+    /// Health at or below which a character dies
+    pub death_health: u8,
+    /// Whether death is the end; otherwise the character comes round at home, shaken
+    pub permadeath: bool,
+    /// Suspicion at or above which the Thought Police make an arrest
+    pub arrest_suspicion: u8,
+    /// What an arrest leads to
+    pub arrest: ArrestConsequence,
+}
+
+impl Default for EndConditions {
+    fn default() -> Self {
+        EndConditions {
+            death_health: 0,
+            permadeath: true,
+            arrest_suspicion: 100,
+            arrest: ArrestConsequence::Interrogation,
+        }
+    }
+}
+
+/// How NPCs' trust shifts with players' deeds and world events; each value is added to the
+/// trust of every NPC on that side, so negative values cost trust
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrustDynamics {
+    /// Party-aligned NPCs' trust in a player who reports someone
+    pub report_party: i8,
+    /// Rebel-aligned NPCs' trust in a player who reports someone
+    pub report_rebel: i8,
+    /// Rebel-aligned NPCs' trust in a player who shares forbidden knowledge
+    pub share_rebel: i8,
+    /// Party-aligned NPCs' trust in a player who shares forbidden knowledge
+    pub share_party: i8,
+    /// Party-aligned NPCs' trust in everyone when the enemy switches
+    pub enemy_switch_party: i8,
+    /// Rebel-aligned NPCs' trust in everyone when the enemy switches
+    pub enemy_switch_rebel: i8,
+}
+
+impl Default for TrustDynamics {
+    fn default() -> Self {
+        TrustDynamics {
+            report_party: 5,
+            report_rebel: -10,
+            share_rebel: 5,
+            share_party: -5,
+            enemy_switch_party: -2,
+            enemy_switch_rebel: 2,
+        }
+    }
+}
+
+/// Changes to the starting world; anything left unset keeps the built-in value
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    /// Scenario file the map, NPCs and forbidden texts are loaded from instead of the built-in
+    /// 1984 world; see [`WorldState::from_config`]
+    pub scenario_file: Option<PathBuf>,
+    /// In-game calendar date the world starts on
+    pub current_date: Option<String>,
+    /// Starting weekly chocolate ration in grams
+    pub chocolate_ration: Option<u8>,
+    /// Starting enemy, "Eurasia" or "Eastasia"
+    pub current_enemy: Option<String>,
+    /// Locations added to the map, replacing built-in ones with the same name
+    pub locations: HashMap<String, Location>,
+    /// NPC name -> base trust, replacing the built-in trust level
+    pub npc_trust: HashMap<String, i8>,
+}
+
+impl WorldConfig {
+    /// The configured scenario, or the built-in world without one, with the overrides applied
+    pub fn starting_world(&self) -> io::Result<WorldState> {
+        let mut world = match &self.scenario_file {
+            Some(path) => WorldState::from_config(path)?,
+            None => WorldState::initialize(),
+        };
+        self.apply(&mut world);
+        Ok(world)
+    }
+
+    /// Apply the overrides to a freshly initialized world
+    pub fn apply(&self, world: &mut WorldState) {
+        if let Some(date) = &self.current_date {
+            world.current_date = date.clone();
+        }
+        if let Some(ration) = self.chocolate_ration {
+            world.chocolate_ration = ration;
+        }
+        if let Some(enemy) = &self.current_enemy {
+            world.current_enemy = enemy.clone();
+        }
+        for (name, location) in &self.locations {
+            world.locations.insert(name.clone(), location.clone());
+        }
+        for (name, trust) in &self.npc_trust {
+            match world.npcs.get_mut(name) {
+                Some(npc) => npc.trust = *trust,
+                None => warn!("Config sets trust for unknown NPC {}", name),
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Data-driven NPC conversations.
+
+use crate::rpg_structs::{adjust_relationship, Character, TextLanguage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Node every conversation starts from
+pub const DIALOGUE_START: &str = "start";
+
+/// Node a conversation starts from instead when the NPC senses the character understands
+/// free exchange
+pub const DIALOGUE_UNDERSTOOD: &str = "understood";
+
+/// Lessons it takes before a character can read a language
+pub const LANGUAGE_LESSONS: u8 = 3;
+
+/// Something that happens when a dialogue option is chosen
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DialogueEffect {
+    /// Change how the NPC feels about the character
+    Relationship(i8),
+    /// Put an item in the character's inventory
+    GiveItem(String),
+    /// Raise suspicion
+    Suspicion(u8),
+    /// Raise thoughtcrime
+    Thoughtcrime(u8),
+    /// Lower thoughtcrime
+    Contrition(u8),
+    /// One lesson towards reading a language
+    LanguageLesson(TextLanguage),
+}
+
+impl DialogueEffect {
+    /// Apply the effect to `character`, who is talking to `npc_name`
+    pub fn apply(&self, character: &mut Character, npc_name: &str) {
+        match self {
+            DialogueEffect::Relationship(delta) => {
+                adjust_relationship(character, npc_name, *delta);
+            }
+            DialogueEffect::GiveItem(item) => character.inventory.push(item.clone()),
+            DialogueEffect::Suspicion(amount) => {
+                character.suspicion = character.suspicion.saturating_add(*amount).min(100);
+            }
+            DialogueEffect::Thoughtcrime(amount) => {
+                character.thoughtcrime = character.thoughtcrime.saturating_add(*amount).min(100);
+            }
+            DialogueEffect::Contrition(amount) => {
+                character.thoughtcrime = character.thoughtcrime.saturating_sub(*amount);
+            }
+            DialogueEffect::LanguageLesson(language) => {
+                let lessons = character.language_lessons.entry(*language).or_insert(0);
+                *lessons = lessons.saturating_add(1);
+                if *lessons >= LANGUAGE_LESSONS {
+                    character.languages.insert(*language);
+                }
+            }
+        }
+    }
+}
+
+/// An answer the player can give at a dialogue node
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DialogueOption {
+    /// What the player says
+    pub text: String,
+    /// Applied when the option is chosen
+    #[serde(default)]
+    pub effects: Vec<DialogueEffect>,
+    /// Node the conversation moves to; `None` ends it
+    #[serde(default)]
+    pub next: Option<String>,
+}
+
+/// One step of a conversation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DialogueNode {
+    /// What the NPC says
+    pub text: String,
+    /// Possible answers, chosen by index
+    pub options: Vec<DialogueOption>,
+}
+
+fn option(text: &str, effects: Vec<DialogueEffect>, next: Option<&str>) -> DialogueOption {
+    DialogueOption {
+        text: text.to_string(),
+        effects,
+        next: next.map(str::to_string),
+    }
+}
+
+/// O'Brien draws the player into the Brotherhood, then hands them over
+pub fn o_brien_dialogue() -> HashMap<String, DialogueNode> {
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        DIALOGUE_START.to_string(),
+        DialogueNode {
+            text: "O'Brien pauses by your desk. \"I have been reading your work. Perhaps you would care to call at my flat and see the tenth edition of the Newspeak dictionary?\"".to_string(),
+            options: vec![
+                option(
+                    "\"I should like that very much.\"",
+                    vec![DialogueEffect::Relationship(10)],
+                    Some("flat"),
+                ),
+                option("\"I'm afraid I'm very busy, comrade.\"", vec![], None),
+            ],
+        },
+    );
+    nodes.insert(
+        "flat".to_string(),
+        DialogueNode {
+            text: "In his flat O'Brien switches off the telescreen. \"The Brotherhood exists. What are you prepared to do?\"".to_string(),
+            options: vec![
+                option(
+                    "\"Anything. We are prepared to give our lives.\"",
+                    vec![
+                        DialogueEffect::Relationship(20),
+                        DialogueEffect::Thoughtcrime(20),
+                    ],
+                    Some("book"),
+                ),
+                option(
+                    "\"I only came about the dictionary.\"",
+                    vec![DialogueEffect::Suspicion(5)],
+                    None,
+                ),
+            ],
+        },
+    );
+    nodes.insert(
+        "book".to_string(),
+        DialogueNode {
+            text: "\"You will be sent a copy of the book. Goldstein's book. Read it, and you will understand the society we live in.\"".to_string(),
+            options: vec![option(
+                "Hide the book under your overalls.",
+                vec![
+                    DialogueEffect::GiveItem(crate::items::THE_BOOK.to_string()),
+                    DialogueEffect::Thoughtcrime(10),
+                ],
+                Some("betrayal"),
+            )],
+        },
+    );
+    nodes.insert(
+        "betrayal".to_string(),
+        DialogueNode {
+            text: "O'Brien's voice has changed. \"They got me a long time ago. You knew this, Winston. You have always known it.\"".to_string(),
+            options: vec![option(
+                "\"But you were one of us!\"",
+                vec![DialogueEffect::Suspicion(100)],
+                None,
+            )],
+        },
+    );
+    nodes
+}
+
+/// The Old Trader only talks freely to those who understand free exchange
+pub fn old_trader_dialogue() -> HashMap<String, DialogueNode> {
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        DIALOGUE_START.to_string(),
+        DialogueNode {
+            text: "The old man squints at you. \"Bootlaces, razor blades, bit of string. Nothing else here, comrade.\"".to_string(),
+            options: vec![option("Nod and move along.", vec![], None)],
+        },
+    );
+    nodes.insert(
+        DIALOGUE_UNDERSTOOD.to_string(),
+        DialogueNode {
+            text: "The old man lowers his voice. \"You're not like the others. Before the Party, a man could sell what he made to whoever wanted it. I've a few things put by for someone who understands.\"".to_string(),
+            options: vec![
+                option(
+                    "\"Tell me how it was, before.\"",
+                    vec![
+                        DialogueEffect::Relationship(10),
+                        DialogueEffect::Thoughtcrime(5),
+                    ],
+                    Some("before"),
+                ),
+                option(
+                    "\"Some of the old papers aren't in English. Could you teach me to read them?\"",
+                    vec![],
+                    Some("czech"),
+                ),
+                option("\"Another time.\"", vec![], None),
+            ],
+        },
+    );
+    nodes.insert(
+        "before".to_string(),
+        DialogueNode {
+            text: "\"Nobody told you what to charge. You made a thing, you named your price, and if nobody paid it you made it better.\" He presses a small packet into your hand. \"Real coffee. Between friends.\"".to_string(),
+            options: vec![option(
+                "Hide the packet in your overalls.",
+                vec![DialogueEffect::GiveItem(crate::items::REAL_COFFEE.to_string())],
+                None,
+            )],
+        },
+    );
+    nodes.insert(
+        "czech".to_string(),
+        DialogueNode {
+            text: "\"Czech. My mother's tongue, from before.\" He smooths a scrap of newspaper on the counter and takes you through it word by word. \"Come back tomorrow. A language isn't learned in an afternoon.\"".to_string(),
+            options: vec![option(
+                "Repeat the words after him under your breath.",
+                vec![
+                    DialogueEffect::LanguageLesson(TextLanguage::Czech),
+                    DialogueEffect::Thoughtcrime(3),
+                ],
+                None,
+            )],
+        },
+    );
+    nodes
+}
+
+/// Questions put to an arrested character in the Ministry of Love.
+///
+/// Submission lowers thoughtcrime and defiance raises it; the tree ends after the last question.
+pub fn interrogation_dialogue() -> HashMap<String, DialogueNode> {
+    let mut nodes = HashMap::new();
+    nodes.insert(
+        DIALOGUE_START.to_string(),
+        DialogueNode {
+            text: "O'Brien holds up his left hand, the thumb hidden. \"How many fingers am I holding up, Winston?\"".to_string(),
+            options: vec![
+                option("\"Four.\"", vec![DialogueEffect::Thoughtcrime(15)], Some("past")),
+                option(
+                    "\"Five. Whatever you want it to be.\"",
+                    vec![DialogueEffect::Contrition(30)],
+                    Some("past"),
+                ),
+            ],
+        },
+    );
+    nodes.insert(
+        "past".to_string(),
+        DialogueNode {
+            text: "\"Does the past exist concretely, in space?\"".to_string(),
+            options: vec![
+                option(
+                    "\"Yes. It happened, whatever the records say.\"",
+                    vec![DialogueEffect::Thoughtcrime(15)],
+                    Some("room_101"),
+                ),
+                option(
+                    "\"It exists only in records and memories. The Party controls both.\"",
+                    vec![DialogueEffect::Contrition(30)],
+                    Some("room_101"),
+                ),
+            ],
+        },
+    );
+    nodes.insert(
+        "room_101".to_string(),
+        DialogueNode {
+            text:
+                "\"You know what is in Room 101.\" The cage of rats is brought closer to your face."
+                    .to_string(),
+            options: vec![
+                option(
+                    "\"Do it to Julia! Not me!\"",
+                    vec![DialogueEffect::Contrition(50)],
+                    None,
+                ),
+                option(
+                    "\"I will not betray her.\"",
+                    vec![DialogueEffect::Thoughtcrime(30)],
+                    None,
+                ),
+            ],
+        },
+    );
+    nodes
+}
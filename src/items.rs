@@ -0,0 +1,165 @@
+//! Item definitions and their effects when used.
+
+use crate::rpg_structs::Character;
+
+/// What an item is and what using it does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ItemDef {
+    /// Name as it appears in inventories
+    pub name: &'static str,
+    /// Shown when inspecting the inventory
+    pub description: &'static str,
+    /// Narrative shown when the item is used
+    pub use_text: &'static str,
+    /// Whether using the item uses it up
+    pub consumed: bool,
+    /// Health change on use
+    pub health: i8,
+    /// Suspicion change on use
+    pub suspicion: i8,
+    /// Thoughtcrime change on use
+    pub thoughtcrime: i8,
+    /// Whether using the item dresses the character's worst injury
+    pub treats_injury: bool,
+}
+
+/// Razor blades are scarce enough to win favours from NPCs
+pub const RAZOR_BLADE: &str = "Razor Blade";
+
+/// Goldstein's book, handed over by the Brotherhood
+pub const THE_BOOK: &str = "The Book";
+
+/// Inner Party coffee smuggled onto the black market
+pub const REAL_COFFEE: &str = "Real Coffee";
+
+/// Dressings the proles trade for, the only treatment for an injury
+pub const BANDAGES: &str = "Bandages";
+
+/// Canteen leftovers a cat will happily eat
+pub const SCRAPS: &str = "Scraps";
+
+/// What the cat can be fed
+pub const CAT_FOOD: &[&str] = &[SCRAPS, "Chocolate"];
+
+/// Goods the black market only offers to those who understand its value
+pub const BLACK_MARKET_PREMIUM: &[&str] = &[REAL_COFFEE, "Coral Paperweight"];
+
+/// Every item the server knows how to use
+pub const ITEMS: &[ItemDef] = &[
+    ItemDef {
+        name: "Victory Gin",
+        description: "A bottle of oily, sickly-smelling gin.",
+        use_text: "The gin burns like nitric acid, but a warmth spreads through you.",
+        consumed: true,
+        health: 5,
+        suspicion: 3,
+        thoughtcrime: 0,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: "Chocolate",
+        description: "A dull-brown crumbly square of your ration.",
+        use_text: "You let the chocolate melt slowly on your tongue.",
+        consumed: true,
+        health: 3,
+        suspicion: 0,
+        thoughtcrime: 0,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: REAL_COFFEE,
+        description: "A packet of real coffee, not Victory Coffee. Inner Party stuff.",
+        use_text: "The smell fills the room, a smell from before the Revolution.",
+        consumed: true,
+        health: 8,
+        suspicion: 5,
+        thoughtcrime: 0,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: BANDAGES,
+        description: "A roll of grey, much-washed bandages from the Prole District.",
+        use_text: "You bind your wound tightly. It will heal properly now.",
+        consumed: true,
+        health: 5,
+        suspicion: 0,
+        thoughtcrime: 0,
+        treats_injury: true,
+    },
+    ItemDef {
+        name: SCRAPS,
+        description: "Greasy leftovers wrapped in a page of the Times.",
+        use_text: "You eat the scraps. Somewhere a cat goes hungry.",
+        consumed: true,
+        health: 1,
+        suspicion: 0,
+        thoughtcrime: 0,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: RAZOR_BLADE,
+        description: "A precious razor blade. Someone would be grateful for it.",
+        use_text: "You shave carefully and tuck the blade away again. It could buy a favour.",
+        consumed: false,
+        health: 0,
+        suspicion: 0,
+        thoughtcrime: 0,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: "Coral Paperweight",
+        description: "A heavy lump of glass with a piece of pink coral at its heart.",
+        use_text: "You gaze into the coral, a tiny world from a time before the Party.",
+        consumed: false,
+        health: 0,
+        suspicion: 0,
+        thoughtcrime: 5,
+        treats_injury: false,
+    },
+    ItemDef {
+        name: THE_BOOK,
+        description: "The Theory and Practice of Oligarchical Collectivism, by Emmanuel Goldstein.",
+        use_text: "You read a chapter. It tells you nothing you did not already know, and that is its fascination.",
+        consumed: false,
+        health: 0,
+        suspicion: 0,
+        thoughtcrime: 10,
+        treats_injury: false,
+    },
+];
+
+/// Look up an item by name
+pub fn find_item(name: &str) -> Option<&'static ItemDef> {
+    ITEMS.iter().find(|item| item.name == name)
+}
+
+// Apply a signed change to a 0-100 stat
+pub(crate) fn apply_delta(stat: u8, delta: i8) -> u8 {
+    stat.saturating_add_signed(delta).min(100)
+}
+
+impl ItemDef {
+    /// Apply the item's effects to `character`, removing it from the inventory if consumed
+    pub fn apply(&self, character: &mut Character) {
+        if self.treats_injury {
+            character.treat_worst_injury();
+        }
+        character.health = apply_delta(character.health, self.health).min(character.max_health());
+        character.suspicion = apply_delta(character.suspicion, self.suspicion);
+        character.thoughtcrime = apply_delta(character.thoughtcrime, self.thoughtcrime);
+        if self.consumed {
+            remove_item(character, self.name);
+        }
+    }
+}
+
+/// Remove one `item` from the inventory; returns whether it was there
+pub fn remove_item(character: &mut Character, item: &str) -> bool {
+    match character.inventory.iter().position(|owned| owned == item) {
+        Some(index) => {
+            character.inventory.remove(index);
+            true
+        }
+        None => false,
+    }
+}
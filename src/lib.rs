@@ -1,73 +1,309 @@
+//! 1984 RPG server built on the flight-rs WebSocket flight simulator.
 #![warn(missing_docs)]
 
+use flate2::{write::ZlibEncoder, Compression};
 use futures::{SinkExt, StreamExt};
-use log::{info, warn};
-use nalgebra::Vector3;
+use log::{debug, info, warn};
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::hash_map::DefaultHasher,
+    collections::{BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    io::{self, Write},
     net::SocketAddr,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
     time::{Duration, Instant},
 };
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio_tungstenite::tungstenite::protocol::{frame::coding::CloseCode, CloseFrame};
 use tokio_tungstenite::tungstenite::Message as TungsteniteMessage;
 use uuid::Uuid;
 use warp::{
     ws::{WebSocket, Ws},
-    Filter,
+    Filter, Reply,
 };
 
 // Import RPG structs FIRST to avoid naming conflicts during definition
 pub mod rpg_structs;
 pub use rpg_structs::*;
 
-// Import physics code (might be repurposed for map navigation later)
-// pub mod physics; // Assuming physics is defined elsewhere if needed, or remove if unused.
-// use physics::Aircraft; // Remove if Aircraft physics are fully replaced
+// Server and world settings
+pub mod config;
+pub use config::{ArrestConsequence, EndConditions, ServerConfig, TrustDynamics, WorldConfig};
+
+// Item table and effects
+pub mod items;
+
+// NPC conversation trees
+pub mod dialogue;
+use dialogue::{
+    interrogation_dialogue, DialogueEffect, DialogueNode, DIALOGUE_START, DIALOGUE_UNDERSTOOD,
+};
+
+// Oldspeak detection for player text
+pub mod newspeak;
+
+// Counters for the /metrics endpoint
+pub mod metrics;
+use metrics::Metrics;
+
+// Flight model shared with the 2D Aircraft
+pub mod physics;
+use physics::{AeroSample, FlightState, PhysicsConfig};
+
+// Starting conditions per occupation
+pub mod occupations;
+use occupations::default_occupations;
+
+// Proximity queries between aircraft
+pub mod spatial;
+use spatial::SpatialIndex;
+
+// Address ranges allowed to connect
+pub mod access;
+
+// Recording and replaying sessions
+pub mod replay;
+
+// Client-friendly serde representations of positions and orientations
+pub mod nalgebra_serde;
 
 // Constants
-const FRAME_TIME: f32 = 1.0 / 30.0; // RPG loop can be slower, 30 FPS equivalent tick rate
+const FRAME_TIME: f32 = 1.0 / 30.0; // Nominal frame time used to scale per-message control input
+/// Game loop tick rate used when none is configured
+pub const DEFAULT_TICK_HZ: f32 = 30.0;
+const MIN_TICK_HZ: f32 = 1.0; // Slowest tick rate the loop will run at
+const MAX_TICK_HZ: f32 = 240.0; // Fastest tick rate the loop will run at
+const MOTION_EPSILON: f32 = 0.01; // Movement in meters too small to be worth sending
+const ROTATION_EPSILON: f32 = 0.001; // Rotation in radians too small to be worth sending
+const COMPRESS_MIN_BYTES: usize = 1024; // Shorter messages aren't worth deflating
+
+// Environment constants for the 3D flight model (aerodynamics live in physics.rs)
+const WIND_VEER_RATE: f32 = 0.05; // Max change in wind heading (rad/s)
+const WIND_GUST_RATE: f32 = 0.2; // Max change in wind strength (m/s per second)
+const TURBULENCE_INTENSITY: f32 = 0.15; // Turbulence amplitude relative to base wind
+const SAFE_LANDING_SPEED: f32 = 3.0; // Touchdown sink rate that causes no damage (m/s)
+const MAX_PLAUSIBLE_SPEED: f32 = 120.0; // Fastest any aircraft can really move (m/s); faster movement is clamped
+const HARD_LANDING_DAMAGE_PER_MPS: f32 = 5.0; // Health lost per m/s of sink rate above safe
+
+// Flight assist constants
+const AUTO_LEVEL_RATE: f32 = 0.5; // Fraction of the remaining bank the assist removes per second
+const AUTO_LEVEL_MAX_CLIMB: f32 = 0.99; // Nose pointing more steeply than this has no horizon to level to
+
+// Aircraft-to-aircraft collision constants
+const COLLISION_RADIUS: f32 = 2.0; // Two aircraft closer than this are touching
+const MIN_COLLISION_SPEED: f32 = 1.0; // Closing speed below this is a harmless nudge
+const COLLISION_DAMAGE: u8 = 15; // Health lost by each aircraft in a collision
+const COLLISION_SEPARATION_SPEED: f32 = 3.0; // Speed at which colliding aircraft bounce apart
+
+// Thought Police home search constants
+const HOME_SEARCH_MIN_SUSPICION: u8 = 30; // Below this the Thought Police don't bother searching
+const HOME_SEARCH_RATE: f32 = 0.01; // Home searches per second at full suspicion
+const HIDDEN_DIARY_FACTOR: f32 = 0.25; // Chance that a search finds a hidden diary
+const DIARY_FOUND_SUSPICION: u8 = 40; // Suspicion added when the diary is found
+
+// Lying low constants
+const SUSPICION_DECAY_RATE: f32 = 0.005; // Suspicion points shed per second while lying low
+const LYING_LOW_MIN_SAFETY: u8 = 3; // Locations at least this safe let suspicion fade
+const LYING_LOW_DAYS: u32 = 1; // Days a loyal act keeps counting towards lying low
+/// `text_id` that refers to the player's diary in `HideForbiddenText`
+pub const JOURNAL_TEXT_ID: &str = "journal";
+
+// Rebellion progression constants
+const REBELLION_PER_CONVERT: u8 = 5; // Gained for sharing knowledge successfully
+const REBELLION_PER_EXCHANGE: u8 = 3; // Gained for each voluntary exchange
+const KNOWLEDGE_PER_REBELLION: u8 = 5; // Understanding points per rebellion point
+const READING_UNDERSTANDING_PER_EASE: u8 = 3; // Understanding per point a text is below the hardest difficulty
+const FOREIGN_TEXT_DIVISOR: u8 = 5; // How much less is understood of a text in an unknown language
+const UNDERSTANDING_FREEDOM_SCORE: u8 = 40; // Economic freedom at which sympathetic NPCs open up
+const BLACK_MARKETEER: &str = "Old Trader"; // Keeps premium goods for those who understand
+const RESISTANCE_VICTORY_SCORE: u8 = 80; // Rebellion score that sparks a resistance cell
+const RESISTANCE_MAX_SUSPICION: u8 = 30; // Suspicion must stay below this to get away with it
+
+// Time and Inner Party promotion constants
+const DAY_LENGTH_SECS: f32 = 600.0; // Real seconds per in-game day
+const PROMOTION_LOYALTY: u8 = 80; // Loyalty that must be held to be considered for promotion
+const PROMOTION_DAYS: u32 = 3; // In-game days loyalty must be held
+const PROMOTION_TASKS: u32 = 10; // Party tasks that must have been completed
+const INNER_PARTY_ENTRANCE: &str = "Ministry of Truth"; // Where the Inner Party reaches the Ministry of Love from
+const MINISTRY_OF_LOVE: &str = "Ministry of Love";
+
+// Day/night constants
+const DAY_START_HOUR: f32 = 6.0; // Clock hour each in-game day starts at
+const TELESCREEN_SUSPICION_RATE: f32 = 0.02; // Suspicion points per second at full thoughtcrime
+const NIGHT_SURVEILLANCE_FACTOR: f32 = 0.3; // Telescreen attention at night relative to day
+const NIGHT_PATROL_FACTOR: f32 = 1.5; // Travel risk multiplier in unsafe districts at night
+const NIGHT_PATROL_MAX_SAFETY: u8 = 2; // Districts this unsafe are patrolled more at night
+
+// Two Minutes Hate constants
+const HATE_DURATION_SECS: f32 = 120.0; // Real seconds the Hate lasts
+const HATE_LOCATIONS: [&str; 3] = ["Ministry of Truth", "Victory Square", "Canteen"]; // Where telescreens gather a crowd for the Hate
+const HATE_LOYALTY: u8 = 10; // Loyalty gained for joining in
+const HATE_SUSPICION_RELIEF: u8 = 10; // Suspicion shed for joining in
+const HATE_PERFORMANCE_DOUBT: u8 = 50; // Thoughtcrime at which the hatred wavers
+const HATE_ABSENCE_SUSPICION: u8 = 15; // Suspicion gained for missing the Hate
+
+// Idle player constants
+const IDLE_WARNING_SECS: u64 = 60; // How long before the idle timeout players are warned
+
+// Interrogation constants
+const INTERROGATOR: &str = "O'Brien"; // Who questions arrested characters
+const REFORM_MAX_THOUGHTCRIME: u8 = 30; // Thoughtcrime left after questioning at or below which a character is reformed
+const RELEASE_LOCATION: &str = "Victory Mansions"; // Where reformed characters are sent home to
+
+// Collapse constants, when death isn't permanent
+const COLLAPSE_LOCATION: &str = "Victory Mansions"; // Where collapsed characters come round
+const COLLAPSE_RECOVERY_HEALTH: u8 = 30; // Health a collapsed character comes round with
+
+// Travel risk constants
+const TRAVEL_RISK_PER_UNSAFE_LEVEL: f32 = 0.08; // Event chance per safety point below 5
+const TRAVEL_ITEM_LOSS_SHARE: f32 = 0.25; // Share of travel events that cost an item
+const TRAVEL_ENCOUNTER_SHARE: f32 = 0.25; // Share of travel events that are an NPC encounter
+const TRAVEL_PATROL_SUSPICION: u8 = 10; // Suspicion added when a patrol stops you
+const DANGEROUS_SAFETY: u8 = 1; // Moves to locations this unsafe must be committed to
+
+// Denunciation constants
+const REPORT_SUSPICION: u8 = 20; // Suspicion a report from a fully loyal character adds to its target
+const REPORT_SUSPICION_RELIEF: u8 = 10; // Suspicion a reporter sheds by proving their loyalty
+const REPORT_CREDIBLE_SUSPICION: u8 = 20; // Targets below this suspicion are presumed falsely accused
+const REPORT_BACKFIRE_CHANCE: f32 = 0.5; // Chance a false report turns on the reporter
+const REPORT_BACKFIRE_SUSPICION: u8 = 15; // Suspicion a reporter gains when a false report backfires
+const REPORT_FALSE_THOUGHTCRIME: u8 = 5; // Thoughtcrime of knowingly bearing false witness
+
+// Hidden text constants
+const RETRIEVAL_RISK_PER_UNSAFE_LEVEL: f32 = 0.05; // Chance of being seen per safety point below 5
+const RETRIEVAL_SUSPICION_PER_RISK: u8 = 3; // Suspicion per point of the text's risk when seen
+const HANDOFF_RISK_PER_UNSAFE_LEVEL: f32 = 0.08; // Chance a handoff is seen per safety point below 5
+const HANDOFF_SUSPICION_PER_RISK: u8 = 2; // Suspicion for each player per point of the text's risk
+
+// Airspace constants
+const RESTRICTED_AIRSPACE_RADIUS: f32 = 150.0; // Horizontal meters around the Ministry of Love no one may overfly
+const AIRSPACE_SUSPICION: u8 = 15; // Suspicion added for entering restricted airspace
+
+// Records Department constants
+const RECORDS_OCCUPATION: &str = "Records Department Worker"; // The only occupation allowed to alter records
+const RECORDS_LOCATION: &str = "Ministry of Truth"; // Where records are altered
+const ALTER_RECORD_LOYALTY: u8 = 2; // Loyalty gained for each record brought into line
+const ALTER_RECORD_THOUGHTCRIME: u8 = 1; // Thoughtcrime of knowing the record was true before
+
+// Lesson constants
+const TEACHERS: [(&str, &str, Option<TextLanguage>); 3] = [
+    ("Syme", "Newspeak", None),
+    ("Old Trader", "Czech", Some(TextLanguage::Czech)),
+    ("Julia", PILOTING_SKILL, None),
+]; // Who teaches what, and the language a subject lets a character read
+const LESSON_MIN_TRUST: i16 = 60; // NPC trust plus relationship needed to be taught
+const SKILL_PER_LESSON: u8 = 10; // Proficiency gained from each lesson
+const LESSON_REFUSED_SUSPICION: u8 = 5; // Suspicion for asking someone who doesn't trust you
+
+// O'Brien entrapment constants
+const ENTRAPPER: &str = "O'Brien"; // Draws rebels in, then hands them to the Thought Police
+const ENTRAPMENT_CONFIDENCES: u8 = 3; // Confidences shared with him before he acts
+const ENTRAPMENT_REBELLION_SCORE: u8 = 50; // Rebellion score that draws his attention anyway
+const BETRAYAL_DELAY_DAYS: u32 = 1; // In-game days between his decision and the betrayal
+const BETRAYAL_SUSPICION: u8 = 95; // Suspicion the betrayal leaves the character with
+
+// Leaderboard constants
+const LEADERBOARD_SIZE: usize = 10; // Characters listed on the leaderboard
+const LEADERBOARD_POINTS_PER_TASK: u32 = 2; // Score for each Party task completed
+const LEADERBOARD_POINTS_PER_DAY: u32 = 10; // Score for each in-game day survived
+
+// Injury and rest constants
+const INJURY_HEALING_PER_DAY: u8 = 5; // Severity each untreated injury loses at the start of a day
+const REST_HEALING: u8 = 10; // Health an uninjured character recovers by resting
+const REST_HEALING_LOST_PER_INJURY: u8 = 3; // Less recovered from rest for each open injury
+const MIN_REST_HEALING: u8 = 2; // Rest always recovers at least this much
+
+// Morale constants
+const MORALE_DRIFT_RATE: f32 = 0.01; // Chance per second morale moves a point towards where it settles
+const CAT_MORALE: u8 = 15; // How far a cat at one's side lifts settled morale, or a hurt or lost one sinks it
+const RATION_CUT_MORALE: u8 = 10; // Morale everyone loses when the chocolate ration is cut
+const REFLECT_MORALE: u8 = 15; // Morale recovered by reflecting
+const REFLECT_THOUGHTCRIME: u8 = 3; // Thoughtcrime of thinking for oneself
+const LOW_MORALE: u8 = 30; // Morale below which searches, trades and persuasion start going worse
+
+// Search constants
+const SEARCH_CHANCE_PER_SAFETY: i16 = 10; // Percent chance of finding a text per point of location safety
+
+// Cat constants
+const CAT_CARE_HEALTH: u8 = 30; // Health a cat regains from being fed and cared for
+const CAT_INJURY_DECAY_RATE: f32 = 0.2; // Chance per second an injured cat loses a point of health
 
 // --- Original Flight Sim Structs (Renamed) ---
+/// Digital control input of the original 2D flight sim
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
 pub struct FlightSimInput {
     // Renamed from ClientInput
+    /// Pitch the nose up
     pub pitch_up: bool,
+    /// Pitch the nose down
     pub pitch_down: bool,
+    /// Increase throttle
     pub throttle_up: bool,
+    /// Decrease throttle
     pub throttle_down: bool,
 }
 
 // This might become redundant or merged into the RPG GameState
+/// World state of the original 2D flight sim
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FlightSimState {
     // Renamed from GameState
+    /// All aircraft in the world
     pub aircraft: Vec<FlightSimAircraftState>,
 }
 
 // This might become redundant or represented differently
+/// A single aircraft of the original 2D flight sim
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct FlightSimAircraftState {
     // Renamed from AircraftState
+    /// Aircraft id
     pub id: String, // Keep using String for ID here for now
+    /// Horizontal position in meters
     pub x: f32,
+    /// Altitude in meters
     pub y: f32,
+    /// Horizontal velocity in m/s
     pub vx: f32,
+    /// Vertical velocity in m/s
     pub vy: f32,
+    /// Pitch angle in radians
     pub theta: f32,
+    /// Throttle level (0.0 to 1.0)
     pub throttle_level: f32,
 }
 // --- End Renamed Structs ---
 
 // --- RPG Shared State Types ---
-// Map Player UUID to their Character state
+/// Map Player UUID to their Character state
 pub type CharacterMap = Arc<Mutex<HashMap<Uuid, Character>>>;
-// Map Player UUID to their WebSocket sender channel
-pub type Clients = Arc<Mutex<HashMap<Uuid, mpsc::UnboundedSender<TungsteniteMessage>>>>;
-// Shared overall game state (including world state)
+/// Bounded channel feeding one client's WebSocket
+pub type ClientSender = mpsc::Sender<TungsteniteMessage>;
+/// Map Player UUID to their WebSocket sender channel
+pub type Clients = Arc<Mutex<HashMap<Uuid, ClientSender>>>;
+/// Shared overall game state (including world state)
 pub type SharedGameState = Arc<Mutex<GameState>>; // Using the RPG GameState
 
+/// Lock the game state, recovering it if a panicking handler poisoned the lock
+pub fn lock_state(game_state: &SharedGameState) -> MutexGuard<'_, GameState> {
+    game_state.lock().unwrap_or_else(|poisoned| {
+        warn!("Game state lock was poisoned by a panic; recovering");
+        poisoned.into_inner()
+    })
+}
+
+/// Lock the client map, recovering it if a panicking handler poisoned the lock
+pub fn lock_clients(clients: &Clients) -> MutexGuard<'_, HashMap<Uuid, ClientSender>> {
+    clients.lock().unwrap_or_else(|poisoned| {
+        warn!("Clients lock was poisoned by a panic; recovering");
+        poisoned.into_inner()
+    })
+}
+
 // Helper functions to inject shared state into route handlers
 fn with_clients(
     clients: Clients,
@@ -75,13 +311,6 @@ fn with_clients(
     warp::any().map(move || clients.clone())
 }
 
-// Inject CharacterMap instead of AircraftMap
-fn with_characters(
-    characters: CharacterMap,
-) -> impl Filter<Extract = (CharacterMap,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || characters.clone())
-}
-
 // Inject SharedGameState
 fn with_game_state(
     game_state: SharedGameState,
@@ -89,41 +318,97 @@ fn with_game_state(
     warp::any().map(move || game_state.clone())
 }
 
+// Whether an admin endpoint request carrying `token` may go ahead; everything is allowed
+// while no admin token is configured
+fn admin_token_matches(admin_token: &Option<String>, token: &Option<String>) -> bool {
+    admin_token.is_none() || token == admin_token
+}
+
+// Reply for admin endpoint requests with a missing or wrong token
+fn invalid_admin_token() -> warp::reply::Response {
+    warp::reply::with_status(
+        warp::reply::json(&ServerMessage::error(
+            ErrorCode::NotAuthenticated,
+            "Invalid admin token",
+        )),
+        warp::http::StatusCode::UNAUTHORIZED,
+    )
+    .into_response()
+}
+
+// Inject the shared metrics
+fn with_metrics(
+    metrics: Arc<Metrics>,
+) -> impl Filter<Extract = (Arc<Metrics>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || metrics.clone())
+}
+
 // Handle new WebSocket connections
 async fn handle_connection(
     ws: WebSocket,
+    peer: Option<SocketAddr>,
     clients: Clients,
     // characters: CharacterMap, // Characters are now part of SharedGameState
     game_state: SharedGameState,
+    config: Arc<ServerConfig>,
+    metrics: Arc<Metrics>,
+    compress: bool, // Client asked for deflated frames and the server allows it
 ) {
     let player_id = Uuid::new_v4(); // Use Uuid directly
-    info!("New connection attempt: {}", player_id);
+    let peer = peer.map_or_else(|| "unknown address".to_string(), |addr| addr.to_string());
+    info!("New connection attempt: {} from {}", player_id, peer);
+    let restore_characters = config.restore_characters;
+    let disconnect_grace = config.disconnect_grace();
 
     let (mut ws_sender, mut ws_receiver) = ws.split();
-    let (client_sender, mut client_receiver) = mpsc::unbounded_channel::<TungsteniteMessage>();
+
+    // Turn the connection away if the server is full
+    if lock_clients(&clients).len() >= config.max_players {
+        warn!(
+            "Rejecting connection {} from {}: server full ({} players)",
+            player_id, peer, config.max_players
+        );
+        let full_msg = ServerMessage::error(
+            ErrorCode::ServerFull,
+            "The server is full, try again later.",
+        );
+        if let Ok(json) = serde_json::to_string(&full_msg) {
+            let _ = ws_sender.send(warp::ws::Message::text(json)).await;
+        }
+        let _ = ws_sender.send(warp::ws::Message::close()).await;
+        return;
+    }
+
+    let (client_sender, mut client_receiver) =
+        mpsc::channel::<TungsteniteMessage>(config.client_buffer.max(1));
 
     // Add client sender to the map immediately
-    clients.lock().unwrap().insert(player_id, client_sender);
+    lock_clients(&clients).insert(player_id, client_sender);
 
     // Send initial Welcome message - Client needs to send CharacterCreation request
     // We no longer create a default character/aircraft here.
-    let initial_state = game_state.lock().unwrap().clone(); // Clone the current state
+    let initial_state = lock_state(&game_state).public_view();
     let welcome_msg = ServerMessage::Welcome {
         player_id,
+        protocol_version: PROTOCOL_VERSION,
         initial_game_state: initial_state,
     };
 
     if let Ok(serialized_welcome) = serde_json::to_string(&welcome_msg) {
-        if ws_sender
-            .send(warp::ws::Message::text(serialized_welcome))
-            .await
-            .is_err()
-        {
+        let json_len = serialized_welcome.len();
+        let welcome_frame = outgoing_frame(serialized_welcome, compress);
+        info!(
+            "Welcome for {}: {} bytes of JSON, {} bytes on the wire",
+            player_id,
+            json_len,
+            welcome_frame.as_bytes().len()
+        );
+        if ws_sender.send(welcome_frame).await.is_err() {
             warn!(
                 "Failed to send welcome message to potential client {}",
                 player_id
             );
-            clients.lock().unwrap().remove(&player_id); // Clean up sender if welcome fails
+            lock_clients(&clients).remove(&player_id); // Clean up sender if welcome fails
             return;
         }
         info!("Sent Welcome to potential client {}", player_id);
@@ -132,7 +417,7 @@ async fn handle_connection(
             "Failed to serialize welcome message for potential client {}",
             player_id
         );
-        clients.lock().unwrap().remove(&player_id); // Clean up
+        lock_clients(&clients).remove(&player_id); // Clean up
         return;
     }
 
@@ -141,15 +426,19 @@ async fn handle_connection(
     let forward_clients = clients.clone();
     // let forward_characters = characters.clone(); // Pass characters map if needed later
     let forward_game_state = game_state.clone();
+    let forward_metrics = metrics.clone();
     tokio::spawn(async move {
         while let Some(message_to_send) = client_receiver.recv().await {
             // message_to_send is TungsteniteMessage
             let warp_message = match message_to_send {
-                TungsteniteMessage::Text(t) => warp::ws::Message::text(t),
+                TungsteniteMessage::Text(t) => outgoing_frame(t, compress),
                 TungsteniteMessage::Binary(b) => warp::ws::Message::binary(b),
                 TungsteniteMessage::Ping(p) => warp::ws::Message::ping(p),
                 TungsteniteMessage::Pong(p) => warp::ws::Message::pong(p),
-                TungsteniteMessage::Close(_) => warp::ws::Message::close(),
+                TungsteniteMessage::Close(Some(frame)) => {
+                    warp::ws::Message::close_with(frame.code, frame.reason)
+                }
+                TungsteniteMessage::Close(None) => warp::ws::Message::close(),
                 TungsteniteMessage::Frame(_) => continue, // Skip raw frames
             };
 
@@ -159,13 +448,21 @@ async fn handle_connection(
                     forward_player_id
                 );
                 // Trigger disconnect logic from here if send fails
-                handle_disconnect(forward_player_id, &forward_clients, &forward_game_state);
+                handle_disconnect(
+                    forward_player_id,
+                    &forward_clients,
+                    &forward_game_state,
+                    &forward_metrics,
+                    disconnect_grace,
+                    restore_characters,
+                );
                 break;
             }
         }
-        // If the loop ends (e.g., channel closed), ensure disconnect logic is called
+        // The channel closes when the server drops a client that fell behind; closing the
+        // socket ends the receive loop, which then removes the character
         warn!("Forwarding task for {} ending.", forward_player_id);
-        // Maybe call handle_disconnect here too? Depends if recv() returning None means disconnect
+        let _ = ws_sender.send(warp::ws::Message::close()).await;
     });
 
     // Process incoming messages from this client
@@ -176,26 +473,46 @@ async fn handle_connection(
                 if message.is_text() {
                     let msg_str = message.to_str().unwrap_or_default();
                     match serde_json::from_str::<ClientMessage>(msg_str) {
+                        // Pings are answered straight away so game state contention
+                        // doesn't show up as latency
+                        Ok(ClientMessage::Ping { client_time }) => {
+                            send_message_to_client(
+                                &clients,
+                                player_id,
+                                &ServerMessage::pong(client_time),
+                            );
+                        }
                         Ok(client_msg) => {
                             // Handle the deserialized ClientMessage
                             // Acquire lock ONCE per message if possible
-                            let mut state_guard = game_state.lock().unwrap();
+                            let mut state_guard = lock_state(&game_state);
+                            metrics.record_message();
                             handle_client_message(
                                 player_id,
                                 client_msg,
                                 &mut state_guard,
                                 &clients,
+                                &config,
                             );
                         }
                         Err(e) => {
                             // Log unrecognized text messages that aren't valid ClientMessage JSON
                             warn!("Failed to deserialize text message from client {}: {}. Content: '{}'", player_id, e, msg_str);
                             // Optionally send an error back to the client
-                            let error_msg =
-                                ServerMessage::Error(format!("Invalid message format: {}", e));
+                            let error_msg = ServerMessage::error(
+                                ErrorCode::InvalidMessage,
+                                format!("Invalid message format: {}", e),
+                            );
                             if let Ok(json_err) = serde_json::to_string(&error_msg) {
-                                if let Some(sender) = clients.lock().unwrap().get(&player_id) {
-                                    let _ = sender.send(TungsteniteMessage::Text(json_err));
+                                let mut clients_map = lock_clients(&clients);
+                                if let Some(sender) = clients_map.get(&player_id) {
+                                    if !queue_message(
+                                        &player_id,
+                                        sender,
+                                        TungsteniteMessage::Text(json_err),
+                                    ) {
+                                        clients_map.remove(&player_id);
+                                    }
                                 }
                             }
                         }
@@ -224,38 +541,268 @@ async fn handle_connection(
         }
     }
 
-    // Client disconnected (loop exited)
-    handle_disconnect(player_id, &clients, &game_state);
+    // Client disconnected (loop exited); a no-op if the forwarding task got there first
+    handle_disconnect(
+        player_id,
+        &clients,
+        &game_state,
+        &metrics,
+        disconnect_grace,
+        restore_characters,
+    );
 }
 
-// Function to process validated client messages
-fn handle_client_message(
+/// Apply one client message to the game state and send the replies it causes.
+///
+/// Called with the game state already locked; replies go out through `clients`.
+pub fn handle_client_message(
     player_id: Uuid,
     msg: ClientMessage,
     game_state: &mut GameState, // Mutably borrow the GameState
     clients: &Clients,          // To broadcast updates
+    config: &ServerConfig,
 ) {
+    // Oversized text is refused before it is logged, recorded or stored anywhere
+    if let Some(len) = msg
+        .texts()
+        .iter()
+        .map(|text| text.chars().count())
+        .find(|&len| len > config.max_text_len)
+    {
+        warn!(
+            "Rejected a message from {} with {} characters of text",
+            player_id, len
+        );
+        let error_msg = ServerMessage::error(
+            ErrorCode::InvalidMessage,
+            format!(
+                "Text is limited to {} characters; yours had {}.",
+                config.max_text_len, len
+            ),
+        );
+        send_message_to_client(clients, player_id, &error_msg);
+        return;
+    }
+
     info!("Received message from {}: {:?}", player_id, msg);
+    if let Some(recorder) = &game_state.recorder {
+        recorder.record(game_state.tick, player_id, &msg);
+    }
+
+    // Logging out is allowed at any time, even mid-interrogation
+    if let ClientMessage::Disconnect { save } = msg {
+        log_out(player_id, save, game_state, clients, config);
+        return;
+    }
+
+    // Detained characters can do nothing but answer their interrogator, and players without a
+    // character can only create one
+    if let Some(character) = game_state.players.get_mut(&player_id) {
+        match (character.detention.clone(), msg) {
+            (None, msg) => handle_free_message(player_id, msg, game_state, clients, config),
+            // Asking what can be done is harmless, and tells them the answer
+            (Some(_), msg @ ClientMessage::QueryActions) => {
+                handle_free_message(player_id, msg, game_state, clients, config)
+            }
+            (
+                Some(ThoughtcrimeConsequence::Interrogation { interrogator, .. }),
+                ClientMessage::DialogueChoice {
+                    npc_name,
+                    option_id,
+                },
+            ) if npc_name == interrogator => {
+                let reply = answer_interrogation(character, &interrogator, option_id);
+                send_message_to_client(clients, player_id, &reply);
+                send_message_to_client(clients, player_id, &stat_update(character));
+                broadcast_state_update(clients, game_state.public_view());
+            }
+            (Some(_), _) => {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::Detained,
+                    "You are held in the Ministry of Love. There is nothing to do but answer.",
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+            }
+        }
+    } else if matches!(
+        msg,
+        ClientMessage::Hello { .. }
+            | ClientMessage::RequestCharacterCreation { .. }
+            | ClientMessage::AdminCommand { .. }
+            | ClientMessage::RequestLeaderboard
+            | ClientMessage::RequestEventLog { .. }
+            | ClientMessage::RequestMap
+    ) {
+        handle_free_message(player_id, msg, game_state, clients, config);
+    } else {
+        debug!("Message from {} before character creation", player_id);
+        let error_msg = ServerMessage::error(ErrorCode::NoCharacter, "Create a character first");
+        send_message_to_client(clients, player_id, &error_msg);
+    }
+
+    // Anything the player sends shows they are still at the keyboard
+    if let Some(character) = game_state.players.get_mut(&player_id) {
+        character.last_input_at = Instant::now();
+        character.idle_warned = false;
+    }
+}
+
+// The error explaining why a profile update can't be applied, or `None` if it can
+fn profile_update_problem(
+    game_state: &GameState,
+    player_id: Uuid,
+    name: Option<&str>,
+    occupation: Option<&str>,
+    config: &ServerConfig,
+) -> Option<ServerMessage> {
+    if let Some(name) = name {
+        if name.is_empty() {
+            return Some(ServerMessage::error(
+                ErrorCode::InvalidMessage,
+                "A citizen must have a name.",
+            ));
+        }
+        let taken = game_state
+            .players
+            .iter()
+            .any(|(id, other)| *id != player_id && other.name == name)
+            || game_state.saved_characters.contains_key(name);
+        if taken {
+            return Some(ServerMessage::error(
+                ErrorCode::CharacterExists,
+                format!("The name {} belongs to another citizen.", name),
+            ));
+        }
+    }
+    if let Some(occupation) = occupation {
+        if !config.allow_occupation_change {
+            return Some(ServerMessage::error(
+                ErrorCode::NotAllowed,
+                "The Party assigns occupations. Yours has not changed.",
+            ));
+        }
+        if !default_occupations().contains_key(occupation) {
+            return Some(ServerMessage::error(
+                ErrorCode::UnknownTarget,
+                format!("{} is not an occupation the Party recognizes.", occupation),
+            ));
+        }
+    }
+    None
+}
 
+// Handle a message from a player who is free to act
+fn handle_free_message(
+    player_id: Uuid,
+    msg: ClientMessage,
+    game_state: &mut GameState,
+    clients: &Clients,
+    config: &ServerConfig,
+) {
     match msg {
+        ClientMessage::Hello { protocol_version } => {
+            if protocol_version == PROTOCOL_VERSION {
+                debug!(
+                    "Player {} speaks protocol version {}",
+                    player_id, protocol_version
+                );
+                return;
+            }
+            warn!(
+                "Closing connection {}: client speaks protocol version {}, server {}",
+                player_id, protocol_version, PROTOCOL_VERSION
+            );
+            let reason = format!(
+                "This server speaks protocol version {} but your client speaks version {}. Update your client.",
+                PROTOCOL_VERSION, protocol_version
+            );
+            let error_msg = ServerMessage::error(ErrorCode::IncompatibleProtocol, reason.clone());
+            send_message_to_client(clients, player_id, &error_msg);
+            let close = TungsteniteMessage::Close(Some(CloseFrame {
+                code: CloseCode::from(PROTOCOL_MISMATCH_CLOSE_CODE),
+                reason: reason.into(),
+            }));
+            let mut clients_map = lock_clients(clients);
+            if let Some(sender) = clients_map.get(&player_id) {
+                if !queue_message(&player_id, sender, close) {
+                    clients_map.remove(&player_id);
+                }
+            }
+        }
         ClientMessage::RequestCharacterCreation { name, occupation } => {
-            if !game_state.players.contains_key(&player_id) {
-                let mut new_char = Character::new(player_id, name.clone(), occupation.clone());
+            if let std::collections::hash_map::Entry::Vacant(slot) =
+                game_state.players.entry(player_id)
+            {
+                // Returning players pick their archived character back up
+                if config.restore_characters {
+                    if let Some(saved) = game_state.saved_characters.get(&name) {
+                        if saved.occupation != occupation {
+                            warn!(
+                                "Player {} tried to restore '{}' with the wrong occupation",
+                                player_id, name
+                            );
+                            let error_msg = ServerMessage::error(
+                                ErrorCode::CharacterExists,
+                                format!(
+                                    "The name {} belongs to a citizen with a different occupation.",
+                                    name
+                                ),
+                            );
+                            send_message_to_client(clients, player_id, &error_msg);
+                            return;
+                        }
+                        if let Some(mut restored) = game_state.saved_characters.remove(&name) {
+                            info!(
+                                "Restoring saved character '{}' ({}) for player {}",
+                                name, occupation, player_id
+                            );
+                            restored.player_id = player_id;
+                            let char_clone = restored.clone();
+                            slot.insert(restored);
 
-                // Adjust stats based on occupation (example)
-                match occupation.as_str() {
-                    "Records Department Worker" => {
-                        new_char.loyalty = new_char.loyalty.saturating_sub(5);
-                        new_char.thoughtcrime = new_char.thoughtcrime.saturating_add(10);
-                    }
-                    "Junior Spy Instructor" => {
-                        new_char.loyalty = new_char.loyalty.saturating_add(15);
-                        new_char.suspicion = new_char.suspicion.saturating_sub(10);
-                    }
-                    "Fiction Department Writer" => {
-                        new_char.thoughtcrime = new_char.thoughtcrime.saturating_add(15);
+                            let created_msg = ServerMessage::CharacterCreated {
+                                character: char_clone.clone(),
+                            };
+                            send_message_to_client(clients, player_id, &created_msg);
+                            let join_msg = ServerMessage::PlayerJoined {
+                                player_id,
+                                profile: char_clone.public_view(&game_state.world_state),
+                            };
+                            broadcast_message(clients, Some(&player_id), &join_msg);
+
+                            let narrative = ServerMessage::NarrativeUpdate(format!(
+                                "Welcome back, {}. The Party has not forgotten you.",
+                                name
+                            ));
+                            send_message_to_client(clients, player_id, &narrative);
+                            let update_msg =
+                                ServerMessage::GameStateUpdate(game_state.public_view());
+                            send_message_to_client(clients, player_id, &update_msg);
+                            return;
+                        }
                     }
-                    _ => { // Default or unknown occupation
+                }
+
+                let mut new_char = Character::new(player_id, name.clone(), occupation.clone());
+                new_char.created_day = game_state.day;
+
+                // Unknown occupations keep the defaults from Character::new
+                if let Some(profile) = default_occupations().get(&occupation) {
+                    profile.apply(&mut new_char);
+                    if game_state
+                        .world_state
+                        .locations
+                        .contains_key(&profile.starting_location)
+                    {
+                        arrive_at(&mut new_char, profile.starting_location.clone());
+                        if let Some(cat) = new_char.cat_companion.as_mut() {
+                            cat.location = new_char.location.clone();
+                        }
+                    } else {
+                        warn!(
+                            "Occupation {} starts at unknown location {}",
+                            occupation, profile.starting_location
+                        );
                     }
                 }
 
@@ -264,78 +811,146 @@ fn handle_client_message(
                     name, occupation, player_id
                 );
                 let char_clone = new_char.clone(); // Clone for broadcast message
-                game_state.players.insert(player_id, new_char);
+                slot.insert(new_char);
+
+                // Confirm to the creator before anything else arrives
+                let created_msg = ServerMessage::CharacterCreated {
+                    character: char_clone.clone(),
+                };
+                send_message_to_client(clients, player_id, &created_msg);
 
                 // Notify all OTHER clients that a new player joined
                 let join_msg = ServerMessage::PlayerJoined {
                     player_id,
-                    character: char_clone,
+                    profile: char_clone.public_view(&game_state.world_state),
                 };
-                broadcast_message(&clients, Some(&player_id), &join_msg); // Send to everyone except the new player
+                broadcast_message(clients, Some(&player_id), &join_msg); // Send to everyone except the new player
 
-                // Send the full updated state back to the new player (confirmation)
-                let update_msg = ServerMessage::GameStateUpdate(game_state.clone());
-                send_message_to_client(&clients, player_id, &update_msg);
+                // Send the updated public state back to the new player
+                let update_msg = ServerMessage::GameStateUpdate(game_state.public_view());
+                send_message_to_client(clients, player_id, &update_msg);
             } else {
                 warn!(
                     "Player {} tried to create character but already exists.",
                     player_id
                 );
-                let error_msg = ServerMessage::Error("Character already created.".to_string());
-                send_message_to_client(&clients, player_id, &error_msg);
+                let error_msg =
+                    ServerMessage::error(ErrorCode::CharacterExists, "Character already created.");
+                send_message_to_client(clients, player_id, &error_msg);
             }
         }
-        ClientMessage::MoveRequest { target_location } => {
+        ClientMessage::MoveRequest {
+            target_location,
+            commit,
+        } => {
             if let Some(character) = game_state.players.get_mut(&player_id) {
                 let current_location_name = &character.location;
                 if let Some(current_loc_details) =
                     game_state.world_state.locations.get(current_location_name)
                 {
-                    if current_loc_details.connections.contains(&target_location) {
-                        if game_state
-                            .world_state
-                            .locations
-                            .contains_key(&target_location)
+                    if current_loc_details.connections.contains(&target_location)
+                        || inner_party_route(character, current_location_name, &target_location)
+                    {
+                        if let Some(target) = game_state.world_state.locations.get(&target_location)
                         {
+                            if !commit && target.safety <= DANGEROUS_SAFETY {
+                                // Give the player a chance to think better of it
+                                let warning = ServerMessage::notify(
+                                    NotificationLevel::Warning,
+                                    format!(
+                                        "{} is crawling with patrols. Commit to the move to go anyway.",
+                                        target_location
+                                    ),
+                                );
+                                send_message_to_client(clients, player_id, &warning);
+                                return;
+                            }
                             info!(
                                 "Player {} moving from {} to {}",
                                 player_id, character.location, target_location
                             );
-                            character.location = target_location;
-                            // TODO: Add travel risk check? Random events on move?
-                            // Broadcast the change
-                            broadcast_state_update(&clients, game_state);
+                            // A direct move abandons any journey in progress
+                            character.travel_route.clear();
+                            let origin = character.location.clone();
+                            let departure =
+                                format!("{} leaves for {}.", character.name, target_location);
+                            let arrival = format!("{} arrives from {}.", character.name, origin);
+                            arrive_at(character, target_location);
+                            let event = roll_travel_risk(
+                                character,
+                                &game_state.world_state,
+                                &mut game_state.rng,
+                            );
+                            let stats = event.is_some().then(|| stat_update(character));
+                            let destination = character.location.clone();
+                            let scene = arrival_narrative(game_state, player_id, &destination);
+                            for narrative in [scene, event].into_iter().flatten() {
+                                send_message_to_client(
+                                    clients,
+                                    player_id,
+                                    &ServerMessage::NarrativeUpdate(narrative),
+                                );
+                            }
+                            if let Some(stats) = stats {
+                                send_message_to_client(clients, player_id, &stats);
+                            }
+                            // Only the two rooms involved hear about it in words
+                            narrate_at_location(
+                                clients,
+                                game_state,
+                                &origin,
+                                Some(&player_id),
+                                departure,
+                            );
+                            narrate_at_location(
+                                clients,
+                                game_state,
+                                &destination,
+                                Some(&player_id),
+                                arrival,
+                            );
+                            // Everyone else just needs to know the player changed rooms
+                            let moved_msg = ServerMessage::PlayerMoved {
+                                player_id,
+                                from: origin,
+                                to: destination,
+                            };
+                            broadcast_message(clients, None, &moved_msg);
                         } else {
                             warn!(
                                 "Player {} tried to move to invalid location {}",
                                 player_id, target_location
                             );
-                            let error_msg = ServerMessage::Error(format!(
-                                "Invalid move target: {}",
-                                target_location
-                            ));
-                            send_message_to_client(&clients, player_id, &error_msg);
+                            let error_msg = ServerMessage::error(
+                                ErrorCode::InvalidMove,
+                                format!("Invalid move target: {}", target_location),
+                            );
+                            send_message_to_client(clients, player_id, &error_msg);
                         }
                     } else {
                         warn!(
                             "Player {} tried invalid move from {} to {}",
                             player_id, current_location_name, target_location
                         );
-                        let error_msg = ServerMessage::Error(format!(
-                            "Cannot move from {} to {}",
-                            current_location_name, target_location
-                        ));
-                        send_message_to_client(&clients, player_id, &error_msg);
+                        let error_msg = ServerMessage::error(
+                            ErrorCode::InvalidMove,
+                            format!(
+                                "Cannot move from {} to {}",
+                                current_location_name, target_location
+                            ),
+                        );
+                        send_message_to_client(clients, player_id, &error_msg);
                     }
                 } else {
                     warn!(
                         "Player {} is in an invalid current location: {}",
                         player_id, current_location_name
                     );
-                    let error_msg = ServerMessage::Error(
-                        "Internal server error: Current location invalid.".to_string(),
+                    let error_msg = ServerMessage::error(
+                        ErrorCode::Internal,
+                        "Internal server error: Current location invalid.",
                     );
-                    send_message_to_client(&clients, player_id, &error_msg);
+                    send_message_to_client(clients, player_id, &error_msg);
                 }
             } else {
                 warn!("MoveRequest from unknown player {}", player_id);
@@ -347,12 +962,50 @@ fn handle_client_message(
             roll,
             yaw,
             throttle_change,
+            seq,
         } => {
             if let Some(character) = game_state.players.get_mut(&player_id) {
+                if seq != 0 && seq <= character.last_input_seq {
+                    debug!("Dropping stale FlyInput {} from {}", seq, player_id);
+                    return;
+                }
                 info!(
                     "Received FlyInput from {}: P:{:.2} R:{:.2} Y:{:.2} T:{:.2}",
                     player_id, pitch, roll, yaw, throttle_change
                 );
+                if ![pitch, roll, yaw, throttle_change]
+                    .iter()
+                    .all(|axis| axis.is_finite())
+                {
+                    warn!("Rejected non-finite FlyInput from {}", player_id);
+                    let error_msg = ServerMessage::error(
+                        ErrorCode::InvalidMessage,
+                        "Flight controls must be finite numbers.",
+                    );
+                    send_message_to_client(clients, player_id, &error_msg);
+                    return;
+                }
+                // Controls are documented as -1.0 to 1.0; don't trust clients to stay inside
+                let [pitch, roll, yaw, mut throttle_change] =
+                    [pitch, roll, yaw, throttle_change].map(|axis| axis.clamp(-1.0, 1.0));
+
+                // Aircraft belong to the Party; the untrained can work the stick but not the throttle
+                if throttle_change > 0.0 && !character.can_fly(config.flight_min_tasks) {
+                    throttle_change = 0.0;
+                    if !character.flight_refused {
+                        character.flight_refused = true;
+                        let refusal = ServerMessage::notify(
+                            NotificationLevel::Warning,
+                            format!(
+                                "You push at the throttle, but the dials and levers mean nothing to you. Prove yourself to the Party first ({} of {} tasks completed).",
+                                character.tasks_completed, config.flight_min_tasks
+                            ),
+                        );
+                        send_message_to_client(clients, player_id, &refusal);
+                    }
+                } else if throttle_change <= 0.0 {
+                    character.flight_refused = false;
+                }
 
                 // Update Throttle
                 character.throttle =
@@ -379,12 +1032,29 @@ fn handle_client_message(
                 let roll_quat = nalgebra::UnitQuaternion::from_axis_angle(&roll_axis, roll_rad);
                 let yaw_quat = nalgebra::UnitQuaternion::from_axis_angle(&yaw_axis, yaw_rad);
 
-                // Combine rotations
+                // Combine rotations, keeping drift from composing every input out of the result
                 character.orientation = yaw_quat * pitch_quat * roll_quat * character.orientation;
+                character.orientation.renormalize();
+                character.last_input_seq = seq;
+                character.stick_held = pitch != 0.0 || roll != 0.0 || yaw != 0.0;
             } else {
                 warn!("FlyInput from unknown player {}", player_id);
             }
         }
+        ClientMessage::SetFlightAssist { enabled } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("SetFlightAssist from unknown player {}", player_id);
+                return;
+            };
+            character.flight_assist = enabled;
+            let text = if enabled {
+                "Flight assist on: the aircraft will level its wings when you let go of the stick."
+            } else {
+                "Flight assist off: the aircraft will hold whatever attitude you leave it in."
+            };
+            let reply = ServerMessage::notify(NotificationLevel::Info, text);
+            send_message_to_client(clients, player_id, &reply);
+        }
         ClientMessage::InteractRequest {
             npc_name,
             interaction_type,
@@ -393,47 +1063,251 @@ fn handle_client_message(
                 "Player {} trying to interact with {} (type {})",
                 player_id, npc_name, interaction_type
             );
-            // TODO: Implement NPC interaction logic based on rpg_structs.py
-            // This will involve checking character location, NPC location, trust levels,
-            // updating character stats, relationships, inventory, and sending NarrativeUpdates.
-            let narrative = format!(
-                "You interact with {}. (Interaction type {} - logic not implemented yet)",
-                npc_name, interaction_type
-            );
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("InteractRequest from unknown player {}", player_id);
+                return;
+            };
+            let npc = match npc_at_location(&game_state.world_state, &npc_name, &character.location)
+            {
+                Ok(npc) => npc,
+                Err(error) => {
+                    send_message_to_client(
+                        clients,
+                        player_id,
+                        &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                    );
+                    return;
+                }
+            };
+
+            let narrative = match interaction_type {
+                // Small talk slowly builds rapport
+                0 => {
+                    let trust = adjust_relationship(character, &npc_name, 5);
+                    format!(
+                        "You exchange a few careful words with {}. (Trust: {})",
+                        npc_name, trust
+                    )
+                }
+                // A razor blade is a gift few can refuse
+                2 => {
+                    if !items::remove_item(character, items::RAZOR_BLADE) {
+                        let error_msg = ServerMessage::error(
+                            ErrorCode::MissingItem,
+                            "You have no razor blade to offer.",
+                        );
+                        send_message_to_client(clients, player_id, &error_msg);
+                        return;
+                    }
+                    let trust = adjust_relationship(character, &npc_name, 15);
+                    format!(
+                        "{} pockets the razor blade with a grateful look. (Trust: {})",
+                        npc_name, trust
+                    )
+                }
+                // The Inner Party gives orders instead of asking
+                3 => {
+                    if !character.is_inner_party() {
+                        let error_msg = ServerMessage::error(
+                            ErrorCode::NotAllowed,
+                            "Only the Inner Party can give orders.",
+                        );
+                        send_message_to_client(clients, player_id, &error_msg);
+                        return;
+                    }
+                    character.loyalty = character.loyalty.saturating_add(2).min(100);
+                    format!(
+                        "{} snaps to attention and carries out your instructions without a word.",
+                        npc_name
+                    )
+                }
+                // Confiding doubts pays off only with someone who already trusts you
+                1 => {
+                    let current = character.relationships.get(&npc_name).copied().unwrap_or(0);
+                    if npc.trust as i16 + current as i16 > 50 {
+                        let trust = adjust_relationship(character, &npc_name, 10);
+                        character.thoughtcrime = character.thoughtcrime.saturating_add(5);
+                        format!(
+                            "{} listens to your doubts and nods slowly. (Trust: {})",
+                            npc_name, trust
+                        )
+                    } else {
+                        let trust = adjust_relationship(character, &npc_name, -10);
+                        let suspicion = if npc.trust < 0 { 20 } else { 10 };
+                        character.suspicion = character.suspicion.saturating_add(suspicion);
+                        format!(
+                            "{} stiffens at your words. You may have said too much. (Trust: {})",
+                            npc_name, trust
+                        )
+                    }
+                }
+                _ => {
+                    let error_msg = ServerMessage::error(
+                        ErrorCode::InvalidMessage,
+                        format!("Unknown interaction type: {}", interaction_type),
+                    );
+                    send_message_to_client(clients, player_id, &error_msg);
+                    return;
+                }
+            };
+            // Anyone else in the room sees the conversation, not what was said
+            let witnessed = format!("{} has a quiet word with {}.", character.name, npc_name);
+            let location = character.location.clone();
             let update_msg = ServerMessage::NarrativeUpdate(narrative);
-            send_message_to_client(&clients, player_id, &update_msg);
-            // Remember to broadcast state changes if interaction modifies public state
+            send_message_to_client(clients, player_id, &update_msg);
+            send_message_to_client(clients, player_id, &stat_update(character));
+            narrate_at_location(clients, game_state, &location, Some(&player_id), witnessed);
         }
         ClientMessage::JournalWriteRequest { entry } => {
             if let Some(character) = game_state.players.get_mut(&player_id) {
                 info!("Player {} writing to journal.", player_id);
+                let flagged = if config.newspeak.enabled {
+                    newspeak::flag_oldspeak(&entry, &config.newspeak.banned_words)
+                } else {
+                    Vec::new()
+                };
                 character.journal_entries.push(entry);
                 character.thoughtcrime = character.thoughtcrime.saturating_add(5); // Increase thoughtcrime
-                                                                                   // TODO: Add risk of being caught?
-                let narrative =
-                    "You write in your secret journal. Your thoughtcrime increases.".to_string();
+
+                // Writing means taking the diary out of its hiding place
+                let mut narrative = if character.journal_hiding_place.take().is_some() {
+                    "You retrieve your diary from its hiding place and write. Your thoughtcrime increases. It lies in the open now."
+                } else {
+                    "You write in your secret journal. Your thoughtcrime increases."
+                }
+                .to_string();
+                if !flagged.is_empty() {
+                    let penalty = (flagged.len() * config.newspeak.thoughtcrime_per_word as usize)
+                        .min(u8::MAX as usize) as u8;
+                    character.thoughtcrime = character.thoughtcrime.saturating_add(penalty);
+                    narrative.push_str(&format!(
+                        " Oldspeak detected ({}): {}.",
+                        flagged.len(),
+                        flagged.join(", ")
+                    ));
+                }
                 let narrative_msg = ServerMessage::NarrativeUpdate(narrative);
-                send_message_to_client(&clients, player_id, &narrative_msg);
-                // Send updated stats privately
-                broadcast_state_update(&clients, game_state); // Or send private update
+                send_message_to_client(clients, player_id, &narrative_msg);
+                send_message_to_client(clients, player_id, &stat_update(character));
             }
         }
         ClientMessage::SearchRequest => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("SearchRequest from unknown player {}", player_id);
+                return;
+            };
             info!("Player {} is searching.", player_id);
-            // TODO: Implement search logic based on location, chance, stats
-            let narrative =
-                "You search the area, but find nothing of interest (logic not implemented yet)."
-                    .to_string();
-            let update_msg = ServerMessage::NarrativeUpdate(narrative);
-            send_message_to_client(&clients, player_id, &update_msg);
+            // Texts turn up where the telescreens watch least, and only to the determined
+            let safety = game_state
+                .world_state
+                .locations
+                .get(&character.location)
+                .map_or(0, |location| location.safety);
+            let chance = SEARCH_CHANCE_PER_SAFETY * i16::from(safety) - morale_penalty(character);
+            let unfound: Vec<String> = game_state
+                .world_state
+                .text_locations
+                .get(&character.location)
+                .into_iter()
+                .flatten()
+                .filter(|id| !character.inventory.contains(id))
+                .cloned()
+                .collect();
+            if game_state.rng.gen_range(0..100) >= chance || unfound.is_empty() {
+                let narrative = ServerMessage::NarrativeUpdate(
+                    "You search the area, but find nothing of interest.".to_string(),
+                );
+                send_message_to_client(clients, player_id, &narrative);
+                return;
+            }
+            let text_id = unfound[game_state.rng.gen_range(0..unfound.len())].clone();
+            info!("Player {} found {}", player_id, text_id);
+            // Once found, a text is no longer hidden here for the next searcher
+            if let Some(texts) = game_state
+                .world_state
+                .text_locations
+                .get_mut(&character.location)
+            {
+                texts.retain(|id| *id != text_id);
+            }
+            character.inventory.push(text_id.clone());
+            let found = ServerMessage::ForbiddenTextFound {
+                texts: vec![text_id],
+            };
+            send_message_to_client(clients, player_id, &found);
+            send_message_to_client(clients, player_id, &stat_update(character));
         }
         ClientMessage::WorkRequest => {
             info!("Player {} is working.", player_id);
-            // TODO: Implement work logic based on occupation, location
-            let narrative =
-                "You perform your duties for the Party (logic not implemented yet).".to_string();
-            let update_msg = ServerMessage::NarrativeUpdate(narrative);
-            send_message_to_client(&clients, player_id, &update_msg);
+            // TODO: Vary work by occupation and location
+            if let Some(character) = game_state.players.get_mut(&player_id) {
+                character.tasks_completed += 1;
+                character.last_loyal_act_day = Some(game_state.day);
+                character.loyalty = character.loyalty.saturating_add(1).min(100);
+                let narrative = format!(
+                    "You perform your duties for the Party. (Tasks completed: {})",
+                    character.tasks_completed
+                );
+                let update_msg = ServerMessage::NarrativeUpdate(narrative);
+                send_message_to_client(clients, player_id, &update_msg);
+                send_message_to_client(clients, player_id, &stat_update(character));
+            }
+        }
+        ClientMessage::AlterRecord {
+            record_id,
+            new_content,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("AlterRecord from unknown player {}", player_id);
+                return;
+            };
+            let problem = if character.occupation != RECORDS_OCCUPATION {
+                Some(ServerMessage::error(
+                    ErrorCode::NotAllowed,
+                    "Only the Records Department may correct the record.",
+                ))
+            } else if character.location != RECORDS_LOCATION {
+                Some(ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("Records can only be altered at the {}.", RECORDS_LOCATION),
+                ))
+            } else if record_id.trim().is_empty() || new_content.trim().is_empty() {
+                Some(ServerMessage::error(
+                    ErrorCode::InvalidMessage,
+                    "Name the record and what it has always said.",
+                ))
+            } else {
+                None
+            };
+            if let Some(error_msg) = problem {
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            info!("Player {} altered record {}", player_id, record_id);
+            character.tasks_completed += 1;
+            character.last_loyal_act_day = Some(game_state.day);
+            character.loyalty = character
+                .loyalty
+                .saturating_add(ALTER_RECORD_LOYALTY)
+                .min(100);
+            character.thoughtcrime = character
+                .thoughtcrime
+                .saturating_add(ALTER_RECORD_THOUGHTCRIME)
+                .min(100);
+            let narrative = format!(
+                "You drop the old version of {} into the memory hole. It has always read as you wrote it. Somewhere inside you, something remembers.",
+                record_id
+            );
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+            send_message_to_client(clients, player_id, &stat_update(character));
+            game_state
+                .world_state
+                .records
+                .insert(record_id, new_content);
         }
         ClientMessage::RestRequest => {
             info!("Player {} rests.", player_id);
@@ -441,296 +1315,3859 @@ fn handle_client_message(
             // For now, just advance the day if ALL players rest? Complex coordination needed.
             // Simplification: Maybe resting just recovers a bit of health?
             if let Some(character) = game_state.players.get_mut(&player_id) {
-                character.health = character.health.saturating_add(5).min(100);
-                let narrative = "You rest for a while, recovering slightly.".to_string();
+                character.heal(rest_healing(character));
+                let narrative = if character.injuries.is_empty() {
+                    "You rest for a while and feel your strength return.".to_string()
+                } else {
+                    "You rest for a while, but your injuries keep you from recovering much."
+                        .to_string()
+                };
                 let narrative_msg = ServerMessage::NarrativeUpdate(narrative);
-                send_message_to_client(&clients, player_id, &narrative_msg);
-                broadcast_state_update(&clients, game_state); // Broadcast health change
+                send_message_to_client(clients, player_id, &narrative_msg);
+                send_message_to_client(clients, player_id, &stat_update(character));
             }
         }
-        // Add a wildcard match arm to handle all other cases for now
-        _ => {
-            warn!(
-                "Unhandled message type from player {}: {:?}",
-                player_id, msg
-            );
-            let error_msg = ServerMessage::Error("This feature is not yet implemented".to_string());
-            send_message_to_client(clients, player_id, &error_msg);
-        }
-    }
-    // Note: Broadcasting the entire state on every action can be inefficient.
-    // Consider sending targeted updates or deltas in a more complex implementation.
-    // For now, broadcasting the whole state is simpler.
-    // broadcast_state_update(&clients, game_state); // Moved inside handlers where state changes
-}
-
-// Helper to handle client disconnection logic
-fn handle_disconnect(
-    player_id: Uuid,
-    clients: &Clients,
-    game_state: &SharedGameState,
-    // characters: &CharacterMap // Now part of game_state
-) {
-    info!("Client {} disconnected", player_id);
-    clients.lock().unwrap().remove(&player_id);
-
-    let mut state_guard = game_state.lock().unwrap();
-    let removed_char = state_guard.players.remove(&player_id); // Remove player from game state
-
-    if removed_char.is_some() {
-        info!("Removed character data for player {}", player_id);
-        // Notify remaining clients that the player left
-        let leave_msg = ServerMessage::PlayerLeft { player_id };
-        broadcast_message(&clients, Some(&player_id), &leave_msg); // Send to everyone else
-    } else {
-        info!(
-            "Disconnect for player {} who hadn't created a character.",
-            player_id
-        );
-    }
-}
-
-// Helper to send a ServerMessage to a specific client
-fn send_message_to_client(clients: &Clients, player_id: Uuid, message: &ServerMessage) {
-    if let Ok(serialized_msg) = serde_json::to_string(message) {
-        let clients_map = clients.lock().unwrap();
-        if let Some(sender) = clients_map.get(&player_id) {
-            if sender
-                .send(TungsteniteMessage::Text(serialized_msg))
-                .is_err()
-            {
-                warn!(
-                    "Failed to send message to client {} (already disconnected?)",
-                    player_id
+        ClientMessage::Reflect => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("Reflect from unknown player {}", player_id);
+                return;
+            };
+            if character.morale >= 100 {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    "Your spirit is as steady as it will ever be. Thinking further would only be dangerous.",
                 );
-                // Sender might be removed soon by disconnect handler
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
             }
-        } else {
-            warn!(
-                "Attempted to send message to non-existent client {}",
-                player_id
+            character.morale = character.morale.saturating_add(REFLECT_MORALE).min(100);
+            character.thoughtcrime = character
+                .thoughtcrime
+                .saturating_add(REFLECT_THOUGHTCRIME)
+                .min(100);
+            info!(
+                "Player {} reflects (morale {})",
+                player_id, character.morale
+            );
+            let narrative = ServerMessage::NarrativeUpdate(
+                "You let your face go blank for the telescreen and think your own thoughts for a while. Something in you steadies, though the thoughts themselves are a crime.".to_string(),
             );
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
         }
-    } else {
-        warn!("Failed to serialize message for client {}", player_id);
-    }
-}
+        ClientMessage::ParticipateInHate => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("ParticipateInHate from unknown player {}", player_id);
+                return;
+            };
+            let problem = if !game_state.world_state.hate_in_progress() {
+                Some("There is no Hate being held. Save your fury for the telescreens.".to_string())
+            } else if !HATE_LOCATIONS.contains(&character.location.as_str()) {
+                Some(format!(
+                    "There is no crowd here. The Hate is watched at {}.",
+                    HATE_LOCATIONS.join(", ")
+                ))
+            } else if character.attended_hate {
+                Some("You are already on your feet, screaming with the rest.".to_string())
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                send_message_to_client(
+                    clients,
+                    player_id,
+                    &ServerMessage::error(ErrorCode::InvalidAction, problem),
+                );
+                return;
+            }
+            character.attended_hate = true;
+            character.last_loyal_act_day = Some(game_state.day);
+            character.loyalty = character.loyalty.saturating_add(HATE_LOYALTY).min(100);
+            character.suspicion = character.suspicion.saturating_sub(HATE_SUSPICION_RELIEF);
+            info!("Player {} joined the Two Minutes Hate", player_id);
+            let narrative = if character.thoughtcrime >= HATE_PERFORMANCE_DOUBT {
+                "You leap to your feet and scream with the others. For a moment your hatred turns from Goldstein to Big Brother, and you hurl the insults all the louder so nobody notices."
+            } else {
+                "You leap to your feet and scream with the others until your throat is raw. When Big Brother's face fills the screen you find there are tears in your eyes."
+            };
+            let narrative_msg = ServerMessage::NarrativeUpdate(narrative.to_string());
+            send_message_to_client(clients, player_id, &narrative_msg);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::ShareForbiddenKnowledge {
+            target_npc,
+            knowledge_topic,
+            approach,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("ShareForbiddenKnowledge from unknown player {}", player_id);
+                return;
+            };
+            let npc =
+                match npc_at_location(&game_state.world_state, &target_npc, &character.location) {
+                    Ok(npc) => npc,
+                    Err(error) => {
+                        send_message_to_client(
+                            clients,
+                            player_id,
+                            &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                        );
+                        return;
+                    }
+                };
+            let Some(&understanding) = character.anarcho_knowledge.get(&knowledge_topic) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("You know nothing about {}.", knowledge_topic),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            info!(
+                "Player {} sharing '{}' with {} ({:?})",
+                player_id, knowledge_topic, target_npc, approach
+            );
+
+            // (success bonus, suspicion risk) of each approach
+            let (approach_bonus, risk): (i16, u8) = match approach {
+                SharingApproach::Subtle => (-10, 5),
+                SharingApproach::Direct => (10, 20),
+                SharingApproach::Metaphoric => (0, 10),
+                SharingApproach::Questioning => (5, 15),
+            };
+            let relationship = character
+                .relationships
+                .get(&target_npc)
+                .copied()
+                .unwrap_or(0);
+            let receptiveness =
+                npc.trust as i16 + relationship as i16 + understanding as i16 / 2 + approach_bonus
+                    - morale_penalty(character);
+            let success = game_state.rng.gen_range(0..100) < receptiveness;
+            if target_npc == ENTRAPPER {
+                if let Entrapment::Watching { confidences } = &mut character.entrapment {
+                    *confidences = confidences.saturating_add(1);
+                }
+            }
+
+            let (target_reaction, consequence) = if success {
+                adjust_relationship(character, &target_npc, 10);
+                character.economic_freedom_score =
+                    character.economic_freedom_score.saturating_add(5).min(100);
+                character.voluntary_actions += 1;
+                raise_rebellion(character, REBELLION_PER_CONVERT);
+                (
+                    format!("{} considers your words in thoughtful silence.", target_npc),
+                    "A seed of doubt has been planted.".to_string(),
+                )
+            } else {
+                adjust_relationship(character, &target_npc, -10);
+                // Informants pass on what they hear
+                let suspicion = if npc.trust < 0 { risk * 2 } else { risk };
+                character.suspicion = character.suspicion.saturating_add(suspicion);
+                (
+                    format!("{} looks away uneasily.", target_npc),
+                    format!("Your suspicion rises by {}.", suspicion),
+                )
+            };
+            // Word gets around to both sides
+            let trust = &config.trust_dynamics;
+            sway_aligned_npcs(
+                character,
+                &game_state.world_state,
+                trust.share_party,
+                trust.share_rebel,
+            );
+            let shared_msg = ServerMessage::KnowledgeShared {
+                success,
+                target_reaction,
+                consequence,
+            };
+            send_message_to_client(clients, player_id, &shared_msg);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::MemorizeForbiddenKnowledge {
+            topic,
+            time_invested,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!(
+                    "MemorizeForbiddenKnowledge from unknown player {}",
+                    player_id
+                );
+                return;
+            };
+            let Some(understanding) = character.anarcho_knowledge.get_mut(&topic) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("Unknown topic: {}", topic),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let hours = time_invested.clamp(1, 10);
+            let before = *understanding;
+            *understanding = understanding.saturating_add(hours * 3).min(100);
+            let gained = *understanding - before;
+            let level = *understanding;
+            character.thoughtcrime = character.thoughtcrime.saturating_add(hours);
+            raise_rebellion(character, gained / KNOWLEDGE_PER_REBELLION);
+            info!(
+                "Player {} studied '{}' for {} hours ({} -> {})",
+                player_id, topic, hours, before, level
+            );
+            let narrative = ServerMessage::NarrativeUpdate(format!(
+                "You spend {} hours turning over the ideas of {} in your mind. (Understanding: {})",
+                hours, topic, level
+            ));
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::ReadForbiddenText { text_id } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("ReadForbiddenText from unknown player {}", player_id);
+                return;
+            };
+            let Some(text) = game_state.world_state.forbidden_texts.get(&text_id) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("Unknown text: {}", text_id),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            if !character.inventory.contains(&text_id) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You are not carrying \"{}\".", text.title),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let understanding_increase = reading_understanding(character, text);
+            let suspicion_increase = text.suspicion_risk / 2;
+            let understanding = character
+                .anarcho_knowledge
+                .entry(text.topic.clone())
+                .or_insert(0);
+            *understanding = understanding
+                .saturating_add(understanding_increase)
+                .min(100);
+            character.suspicion = character
+                .suspicion
+                .saturating_add(suspicion_increase)
+                .min(100);
+            character.thoughtcrime = character
+                .thoughtcrime
+                .saturating_add(understanding_increase / 2)
+                .min(100);
+            raise_rebellion(character, understanding_increase / KNOWLEDGE_PER_REBELLION);
+            info!(
+                "Player {} read '{}' (+{} understanding)",
+                player_id, text_id, understanding_increase
+            );
+            let content_msg = ServerMessage::ForbiddenTextContent {
+                text: text.clone(),
+                understanding_increase,
+                suspicion_increase,
+            };
+            send_message_to_client(clients, player_id, &content_msg);
+            if !character.languages.contains(&text.language) {
+                let narrative = ServerMessage::NarrativeUpdate(format!(
+                    "You stare at \"{}\" until the foreign words swim. A phrase here and there looks almost familiar, but the meaning slips away. Someone who remembers the old languages could teach you.",
+                    text.title
+                ));
+                send_message_to_client(clients, player_id, &narrative);
+            }
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::VoluntaryExchange {
+            target_npc,
+            offer,
+            request,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("VoluntaryExchange from unknown player {}", player_id);
+                return;
+            };
+            let npc =
+                match npc_at_location(&game_state.world_state, &target_npc, &character.location) {
+                    Ok(npc) => npc,
+                    Err(error) => {
+                        send_message_to_client(
+                            clients,
+                            player_id,
+                            &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                        );
+                        return;
+                    }
+                };
+            if !character.inventory.contains(&offer) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You don't have {}.", offer),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            // The best goods are only brought out for those who understand
+            if target_npc == BLACK_MARKETEER
+                && items::BLACK_MARKET_PREMIUM.contains(&request.as_str())
+                && character.economic_freedom_score < UNDERSTANDING_FREEDOM_SCORE
+            {
+                let result = ServerMessage::VoluntaryExchangeResult {
+                    success: false,
+                    result_message: format!(
+                        "{} looks blank. \"{}? Never heard of it, comrade.\"",
+                        target_npc, request
+                    ),
+                    gained_item: None,
+                    lost_item: None,
+                };
+                send_message_to_client(clients, player_id, &result);
+                return;
+            }
+
+            let relationship = character
+                .relationships
+                .get(&target_npc)
+                .copied()
+                .unwrap_or(0);
+            let acceptance =
+                50 + npc.trust as i16 / 2 + relationship as i16 / 2 - morale_penalty(character);
+            let success = game_state.rng.gen_range(0..100) < acceptance;
+            let result = if success {
+                items::remove_item(character, &offer);
+                character.inventory.push(request.clone());
+                character.voluntary_actions += 1;
+                adjust_relationship(character, &target_npc, 5);
+                raise_rebellion(character, REBELLION_PER_EXCHANGE);
+                // Informants trade happily and then talk
+                if npc.trust < 0 {
+                    character.suspicion = character.suspicion.saturating_add(10);
+                }
+                ServerMessage::VoluntaryExchangeResult {
+                    success,
+                    result_message: format!(
+                        "{} agrees. You trade your {} for {}.",
+                        target_npc, offer, request
+                    ),
+                    gained_item: Some(request),
+                    lost_item: Some(offer),
+                }
+            } else {
+                adjust_relationship(character, &target_npc, -5);
+                ServerMessage::VoluntaryExchangeResult {
+                    success,
+                    result_message: format!("{} shakes their head and turns away.", target_npc),
+                    gained_item: None,
+                    lost_item: None,
+                }
+            };
+            info!(
+                "Player {} traded with {}: success={}",
+                player_id, target_npc, success
+            );
+            send_message_to_client(clients, player_id, &result);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::TravelTo { destination } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("TravelTo from unknown player {}", player_id);
+                return;
+            };
+            if character.location == destination {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidMove,
+                    format!("You are already at {}.", destination),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let Some(route) = game_state
+                .world_state
+                .find_route(&character.location, &destination)
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidMove,
+                    format!(
+                        "There is no way to get from {} to {}.",
+                        character.location, destination
+                    ),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            info!(
+                "Player {} travelling from {} to {} via {:?}",
+                player_id, character.location, destination, route
+            );
+            let narrative = ServerMessage::NarrativeUpdate(format!(
+                "You set out for {} by way of {}.",
+                destination,
+                route.join(", ")
+            ));
+            character.travel_route = route;
+            send_message_to_client(clients, player_id, &narrative);
+        }
+        ClientMessage::LookAround => {
+            let Some(character) = game_state.players.get(&player_id) else {
+                warn!("LookAround from unknown player {}", player_id);
+                return;
+            };
+            let world = &game_state.world_state;
+            let Some(location) = world.locations.get(&character.location) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::Internal,
+                    "Internal server error: Current location invalid.",
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let (npcs_present, other_players_present) =
+                who_is_here(game_state, player_id, &location.name);
+            // Only searching reveals what the texts are
+            let texts_hint = world
+                .text_locations
+                .get(&location.name)
+                .is_some_and(|texts| !texts.is_empty())
+                .then(|| "Something here feels out of place, as if it were hidden.".to_string());
+            let report = ServerMessage::LocationReport {
+                description: location.description.clone(),
+                connections: location.connections.clone(),
+                npcs_present,
+                other_players_present,
+                texts_hint,
+            };
+            send_message_to_client(clients, player_id, &report);
+        }
+        ClientMessage::CareForCat => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("CareForCat from unknown player {}", player_id);
+                return;
+            };
+            let problem = match &character.cat_companion {
+                None => Some("You have no cat to care for.".to_string()),
+                Some(cat) if cat.status == CatStatus::Lost => {
+                    Some(format!("{} is lost. Call for it first.", cat.name))
+                }
+                Some(cat) if cat.location != character.location => {
+                    Some(format!("{} is not here.", cat.name))
+                }
+                Some(_) => None,
+            };
+            if let Some(problem) = problem {
+                send_message_to_client(
+                    clients,
+                    player_id,
+                    &ServerMessage::error(ErrorCode::InvalidAction, problem),
+                );
+                return;
+            }
+            let Some(food) = items::CAT_FOOD
+                .iter()
+                .find(|food| character.inventory.iter().any(|item| item == *food))
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    "You have nothing to feed the cat.",
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            items::remove_item(character, food);
+            let Some(cat) = character.cat_companion.as_mut() else {
+                return;
+            };
+            cat.health = cat.health.saturating_add(CAT_CARE_HEALTH).min(100);
+            let narrative = if cat.status == CatStatus::Injured {
+                cat.status = CatStatus::Following;
+                format!(
+                    "You feed {} the {} and bind its wounds. It limps after you again.",
+                    cat.name, food
+                )
+            } else {
+                format!("{} wolfs down the {} and purrs.", cat.name, food)
+            };
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::CallCat => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("CallCat from unknown player {}", player_id);
+                return;
+            };
+            let Some(cat) = character.cat_companion.as_mut() else {
+                let error_msg =
+                    ServerMessage::error(ErrorCode::InvalidAction, "You have no cat to call.");
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            if cat.status != CatStatus::Lost {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("{} is not lost.", cat.name),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let within_earshot = cat.location == character.location
+                || game_state
+                    .world_state
+                    .locations
+                    .get(&character.location)
+                    .is_some_and(|location| location.connections.contains(&cat.location));
+            let narrative = if within_earshot {
+                cat.status = CatStatus::Following;
+                cat.location = character.location.clone();
+                format!("{} hears you and comes running.", cat.name)
+            } else {
+                format!("You call for {}, but nothing answers.", cat.name)
+            };
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+        }
+        ClientMessage::ReportPlayer {
+            target_name,
+            accusation,
+        } => {
+            let Some(reporter) = game_state.players.get(&player_id) else {
+                warn!("ReportPlayer from unknown player {}", player_id);
+                return;
+            };
+            let loyalty = reporter.loyalty;
+            let Some((&target_id, target)) = game_state
+                .players
+                .iter()
+                .find(|(id, other)| **id != player_id && other.name == target_name)
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("There is no comrade called {} to report.", target_name),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let credible = target.suspicion >= REPORT_CREDIBLE_SUSPICION;
+            let backfired = !credible && game_state.rng.gen::<f32>() < REPORT_BACKFIRE_CHANCE;
+            info!(
+                "Player {} reported {} for '{}' (credible: {}, backfired: {})",
+                player_id, target_id, accusation, credible, backfired
+            );
+
+            let narrative = if backfired {
+                format!(
+                    "The Thought Police find nothing against {}. They begin to wonder why you would lie.",
+                    target_name
+                )
+            } else {
+                let Some(target) = game_state.players.get_mut(&target_id) else {
+                    return;
+                };
+                let added = (u16::from(REPORT_SUSPICION) * u16::from(loyalty) / 100) as u8;
+                target.suspicion = target.suspicion.saturating_add(added).min(100);
+                let warning = ServerMessage::TeleScreenWarning {
+                    message: format!(
+                        "A comrade has reported you for {}. The Thought Police are taking an interest.",
+                        accusation
+                    ),
+                    severity: (1 + target.suspicion / 25).min(5),
+                };
+                send_message_to_client(clients, target_id, &warning);
+                send_message_to_client(clients, target_id, &stat_update(target));
+                format!(
+                    "Your report on {} is noted. Big Brother thanks you.",
+                    target_name
+                )
+            };
+
+            let Some(reporter) = game_state.players.get_mut(&player_id) else {
+                return;
+            };
+            let trust = &config.trust_dynamics;
+            sway_aligned_npcs(
+                reporter,
+                &game_state.world_state,
+                trust.report_party,
+                trust.report_rebel,
+            );
+            if backfired {
+                reporter.suspicion = reporter
+                    .suspicion
+                    .saturating_add(REPORT_BACKFIRE_SUSPICION)
+                    .min(100);
+            } else {
+                reporter.suspicion = reporter.suspicion.saturating_sub(REPORT_SUSPICION_RELIEF);
+            }
+            if !credible {
+                reporter.thoughtcrime = reporter
+                    .thoughtcrime
+                    .saturating_add(REPORT_FALSE_THOUGHTCRIME)
+                    .min(100);
+            }
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+            send_message_to_client(clients, player_id, &stat_update(reporter));
+        }
+        ClientMessage::ExaminePlayer { target_name } => {
+            let Some(examiner) = game_state.players.get(&player_id) else {
+                warn!("ExaminePlayer from unknown player {}", player_id);
+                return;
+            };
+            let Some(target) = game_state.players.iter().find_map(|(id, other)| {
+                (*id != player_id
+                    && other.name == target_name
+                    && other.location == examiner.location
+                    && other.disconnected_at.is_none())
+                .then_some(other)
+            }) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("{} is not here to examine.", target_name),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let profile_msg = ServerMessage::PlayerProfile {
+                profile: target.public_view(&game_state.world_state),
+            };
+            send_message_to_client(clients, player_id, &profile_msg);
+        }
+        ClientMessage::GiveText {
+            target_name,
+            text_id,
+        } => {
+            let Some(giver) = game_state.players.get(&player_id) else {
+                warn!("GiveText from unknown player {}", player_id);
+                return;
+            };
+            let Some(text) = game_state.world_state.forbidden_texts.get(&text_id) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("Unknown text: {}", text_id),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            if !giver.inventory.contains(&text_id) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You are not carrying \"{}\".", text.title),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let Some(target_id) = game_state
+                .players
+                .iter()
+                .find(|(id, other)| {
+                    **id != player_id
+                        && other.name == target_name
+                        && other.location == giver.location
+                        && other.disconnected_at.is_none()
+                })
+                .map(|(id, _)| *id)
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("{} is not here.", target_name),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let title = text.title.clone();
+            let safety = game_state
+                .world_state
+                .locations
+                .get(&giver.location)
+                .map_or(5, |location| location.safety);
+            let seen = game_state.rng.gen::<f32>() < handoff_risk_chance(safety);
+            let suspicion = text
+                .suspicion_risk
+                .saturating_mul(HANDOFF_SUSPICION_PER_RISK);
+            let giver_name = giver.name.clone();
+            info!(
+                "Player {} gave '{}' to {} (seen: {})",
+                player_id, text_id, target_id, seen
+            );
+
+            for (id, mut narrative) in [
+                (
+                    player_id,
+                    format!("You slip \"{}\" to {}.", title, target_name),
+                ),
+                (
+                    target_id,
+                    format!("{} slips you a copy of \"{}\".", giver_name, title),
+                ),
+            ] {
+                let Some(character) = game_state.players.get_mut(&id) else {
+                    continue;
+                };
+                if id == player_id {
+                    if let Some(index) = character.inventory.iter().position(|i| *i == text_id) {
+                        character.inventory.remove(index);
+                    }
+                } else {
+                    character.inventory.push(text_id.clone());
+                }
+                if seen {
+                    character.suspicion = character.suspicion.saturating_add(suspicion).min(100);
+                    narrative.push_str(" Someone saw the exchange. Your suspicion rises.");
+                }
+                send_message_to_client(clients, id, &ServerMessage::NarrativeUpdate(narrative));
+                send_message_to_client(clients, id, &stat_update(character));
+            }
+        }
+        ClientMessage::AdminCommand { token, command } => {
+            if config.admin_token.as_deref() != Some(token.as_str()) {
+                warn!("Rejected admin command from {}: {:?}", player_id, command);
+                let error_msg =
+                    ServerMessage::error(ErrorCode::NotAuthenticated, "Invalid admin token");
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            info!("Admin command from {}: {:?}", player_id, command);
+            match run_admin_command(command, game_state, &config.trust_dynamics) {
+                Ok(announcement) => {
+                    if let Some(announcement) = announcement {
+                        game_state.record_event(None, announcement.as_str());
+                        broadcast_message(
+                            clients,
+                            None,
+                            &ServerMessage::notify(NotificationLevel::Info, announcement),
+                        );
+                    }
+                    broadcast_state_update(clients, game_state.public_view());
+                }
+                Err(error) => {
+                    send_message_to_client(
+                        clients,
+                        player_id,
+                        &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                    );
+                }
+            }
+        }
+        ClientMessage::QueryRelationships => {
+            if let Some(character) = game_state.players.get(&player_id) {
+                let report = ServerMessage::RelationshipReport {
+                    relationships: character.relationships.clone(),
+                };
+                send_message_to_client(clients, player_id, &report);
+            } else {
+                warn!("QueryRelationships from unknown player {}", player_id);
+            }
+        }
+        ClientMessage::UpdateProfile { name, occupation } => {
+            let name = name.map(|name| name.trim().to_string());
+            if let Some(problem) = profile_update_problem(
+                game_state,
+                player_id,
+                name.as_deref(),
+                occupation.as_deref(),
+                config,
+            ) {
+                send_message_to_client(clients, player_id, &problem);
+                return;
+            }
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("UpdateProfile from unknown player {}", player_id);
+                return;
+            };
+            if let Some(name) = name {
+                info!(
+                    "Player {} renamed '{}' to '{}'",
+                    player_id, character.name, name
+                );
+                character.name = name;
+            }
+            if let Some(occupation) = occupation {
+                info!(
+                    "Player {} changed occupation from {} to {}",
+                    player_id, character.occupation, occupation
+                );
+                character.occupation = occupation;
+            }
+            let updated_msg = ServerMessage::ProfileUpdated {
+                player_id,
+                profile: character.public_view(&game_state.world_state),
+            };
+            // Only the owner gets the whole character back
+            let self_state = ServerMessage::SelfState(character.clone());
+            broadcast_message(clients, None, &updated_msg);
+            send_message_to_client(clients, player_id, &self_state);
+        }
+        ClientMessage::RequestSelf => {
+            let Some(character) = game_state.players.get(&player_id) else {
+                warn!("RequestSelf from unknown player {}", player_id);
+                return;
+            };
+            let self_state = ServerMessage::SelfState(character.clone());
+            send_message_to_client(clients, player_id, &self_state);
+        }
+        ClientMessage::StartDialogue { npc_name } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("StartDialogue from unknown player {}", player_id);
+                return;
+            };
+            let npc = match npc_at_location(&game_state.world_state, &npc_name, &character.location)
+            {
+                Ok(npc) => npc,
+                Err(error) => {
+                    send_message_to_client(
+                        clients,
+                        player_id,
+                        &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                    );
+                    return;
+                }
+            };
+            // Pick up where the last conversation left off
+            let mut node_id = character
+                .dialogue_nodes
+                .get(&npc_name)
+                .map_or(DIALOGUE_START, String::as_str);
+            if node_id == DIALOGUE_START
+                && character.economic_freedom_score >= UNDERSTANDING_FREEDOM_SCORE
+                && npc.dialogue.contains_key(DIALOGUE_UNDERSTOOD)
+            {
+                node_id = DIALOGUE_UNDERSTOOD;
+                character
+                    .dialogue_nodes
+                    .insert(npc_name.clone(), node_id.to_string());
+                let narrative = ServerMessage::NarrativeUpdate(format!(
+                    "{} studies you for a moment and seems to sense that you understand. Their manner changes.",
+                    npc_name
+                ));
+                send_message_to_client(clients, player_id, &narrative);
+            }
+            let reply = dialogue_prompt(&npc.name, &npc.dialogue, node_id).unwrap_or_else(|| {
+                ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("{} has nothing to say to you.", npc_name),
+                )
+            });
+            send_message_to_client(clients, player_id, &reply);
+        }
+        ClientMessage::DialogueChoice {
+            npc_name,
+            option_id,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("DialogueChoice from unknown player {}", player_id);
+                return;
+            };
+            let npc = match npc_at_location(&game_state.world_state, &npc_name, &character.location)
+            {
+                Ok(npc) => npc,
+                Err(error) => {
+                    send_message_to_client(
+                        clients,
+                        player_id,
+                        &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                    );
+                    return;
+                }
+            };
+            let node_id = character
+                .dialogue_nodes
+                .get(&npc_name)
+                .map_or(DIALOGUE_START, String::as_str);
+            let Some(option) = npc
+                .dialogue
+                .get(node_id)
+                .and_then(|node| node.options.get(option_id))
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidMessage,
+                    format!("That is not something you can say to {}.", npc_name),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            info!(
+                "Player {} chose option {} at {}'s node {}",
+                player_id, option_id, npc_name, node_id
+            );
+            for effect in &option.effects {
+                effect.apply(character, &npc_name);
+            }
+            let reply = match &option.next {
+                Some(next) => {
+                    character
+                        .dialogue_nodes
+                        .insert(npc_name.clone(), next.clone());
+                    dialogue_prompt(&npc.name, &npc.dialogue, next)
+                }
+                None => {
+                    character.dialogue_nodes.remove(&npc_name);
+                    None
+                }
+            };
+            let reply = reply.unwrap_or_else(|| {
+                ServerMessage::NarrativeUpdate(format!("{} turns away.", npc_name))
+            });
+            send_message_to_client(clients, player_id, &reply);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::LearnFromNpc { npc_name, subject } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("LearnFromNpc from unknown player {}", player_id);
+                return;
+            };
+            let npc = match npc_at_location(&game_state.world_state, &npc_name, &character.location)
+            {
+                Ok(npc) => npc,
+                Err(error) => {
+                    send_message_to_client(
+                        clients,
+                        player_id,
+                        &ServerMessage::error(ErrorCode::UnknownTarget, error),
+                    );
+                    return;
+                }
+            };
+            let Some(&(_, _, language)) = TEACHERS
+                .iter()
+                .find(|(teacher, taught, _)| *teacher == npc_name && *taught == subject)
+            else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("{} has nothing to teach you about {}.", npc_name, subject),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            let skill = character.skills.get(&subject).copied().unwrap_or(0);
+            let problem = if character.last_lesson_day == Some(game_state.day) {
+                Some(
+                    "You have already spent today's free hours on a lesson. Come back tomorrow."
+                        .to_string(),
+                )
+            } else if skill >= 100 {
+                Some(format!(
+                    "{} has taught you all they know of {}.",
+                    npc_name, subject
+                ))
+            } else {
+                None
+            };
+            if let Some(problem) = problem {
+                send_message_to_client(
+                    clients,
+                    player_id,
+                    &ServerMessage::error(ErrorCode::InvalidAction, problem),
+                );
+                return;
+            }
+
+            // Asking takes the day's free hours whether or not the answer is yes
+            character.last_lesson_day = Some(game_state.day);
+            let relationship = character.relationships.get(&npc_name).copied().unwrap_or(0);
+            let narrative = if (npc.trust as i16 + relationship as i16) < LESSON_MIN_TRUST {
+                // Informants pass on what they hear
+                let suspicion = if npc.trust < 0 {
+                    LESSON_REFUSED_SUSPICION * 2
+                } else {
+                    LESSON_REFUSED_SUSPICION
+                };
+                character.suspicion = character.suspicion.saturating_add(suspicion).min(100);
+                info!(
+                    "Player {} was refused a lesson in {} by {}",
+                    player_id, subject, npc_name
+                );
+                format!(
+                    "{} gives you a long, careful look and talks about the weather instead. Asking was a mistake.",
+                    npc_name
+                )
+            } else {
+                let level = skill.saturating_add(SKILL_PER_LESSON).min(100);
+                character.skills.insert(subject.clone(), level);
+                if let Some(language) = language {
+                    DialogueEffect::LanguageLesson(language).apply(character, &npc_name);
+                }
+                info!(
+                    "Player {} learned {} from {} ({} -> {})",
+                    player_id, subject, npc_name, skill, level
+                );
+                format!(
+                    "You spend the afternoon learning {} from {}. (Skill: {})",
+                    subject, npc_name, level
+                )
+            };
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::DropItem { item } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("DropItem from unknown player {}", player_id);
+                return;
+            };
+            if !items::remove_item(character, &item) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You don't have {}.", item),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            info!("Player {} dropped {}", player_id, item);
+            let narrative =
+                ServerMessage::NarrativeUpdate(format!("You discreetly get rid of the {}.", item));
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::UseItem { item } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("UseItem from unknown player {}", player_id);
+                return;
+            };
+            if !character.inventory.contains(&item) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You don't have {}.", item),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let Some(def) = items::find_item(&item) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("You can't think of a use for {}.", item),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            info!("Player {} used {}", player_id, item);
+            def.apply(character);
+            let narrative = ServerMessage::NarrativeUpdate(def.use_text.to_string());
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::InspectInventory => {
+            if let Some(character) = game_state.players.get(&player_id) {
+                let narrative = if character.inventory.is_empty() {
+                    "Your pockets are empty.".to_string()
+                } else {
+                    let lines: Vec<String> = character
+                        .inventory
+                        .iter()
+                        .map(|item| match items::find_item(item) {
+                            Some(def) => format!("{}: {}", item, def.description),
+                            None => item.clone(),
+                        })
+                        .collect();
+                    format!("You are carrying:\n{}", lines.join("\n"))
+                };
+                let narrative_msg = ServerMessage::NarrativeUpdate(narrative);
+                send_message_to_client(clients, player_id, &narrative_msg);
+            } else {
+                warn!("InspectInventory from unknown player {}", player_id);
+            }
+        }
+        ClientMessage::HideForbiddenText {
+            text_id,
+            hiding_place,
+        } if text_id == JOURNAL_TEXT_ID => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("HideForbiddenText from unknown player {}", player_id);
+                return;
+            };
+            if character.journal_entries.is_empty() {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    "You have not written anything to hide.",
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            info!("Player {} hid their diary {}", player_id, hiding_place);
+            let narrative = ServerMessage::NarrativeUpdate(format!(
+                "You hide your diary {}. A search would have to be thorough to find it.",
+                hiding_place
+            ));
+            character.journal_hiding_place = Some(hiding_place);
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::HideForbiddenText {
+            text_id,
+            hiding_place,
+        } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("HideForbiddenText from unknown player {}", player_id);
+                return;
+            };
+            let Some(text) = game_state.world_state.forbidden_texts.get(&text_id) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("Unknown text: {}", text_id),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            if !items::remove_item(character, &text_id) {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::MissingItem,
+                    format!("You are not carrying \"{}\".", text.title),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            info!(
+                "Player {} hid '{}' {} at {}",
+                player_id, text_id, hiding_place, character.location
+            );
+            let narrative = ServerMessage::NarrativeUpdate(format!(
+                "You hide \"{}\" {}. It will be here when you come back for it.",
+                text.title, hiding_place
+            ));
+            character.hidden_texts.insert(
+                text_id,
+                HiddenText {
+                    location: character.location.clone(),
+                    hiding_place,
+                },
+            );
+            send_message_to_client(clients, player_id, &narrative);
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::RetrieveHiddenText { text_id } => {
+            let Some(character) = game_state.players.get_mut(&player_id) else {
+                warn!("RetrieveHiddenText from unknown player {}", player_id);
+                return;
+            };
+            let Some(hidden) = character.hidden_texts.get(&text_id) else {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::UnknownTarget,
+                    format!("You have not hidden anything called {}.", text_id),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            };
+            if hidden.location != character.location {
+                let error_msg = ServerMessage::error(
+                    ErrorCode::InvalidAction,
+                    format!("You hid that at {}, not here.", hidden.location),
+                );
+                send_message_to_client(clients, player_id, &error_msg);
+                return;
+            }
+            let world = &game_state.world_state;
+            let safety = world
+                .locations
+                .get(&character.location)
+                .map_or(5, |location| location.safety);
+            let (title, risk) = world
+                .forbidden_texts
+                .get(&text_id)
+                .map_or((text_id.as_str(), 1), |text| {
+                    (text.title.as_str(), text.suspicion_risk)
+                });
+            let mut narrative = format!("You take \"{}\" from {}.", title, hidden.hiding_place);
+            if game_state.rng.gen::<f32>() < retrieval_risk_chance(safety) {
+                let suspicion = risk.saturating_mul(RETRIEVAL_SUSPICION_PER_RISK);
+                character.suspicion = character.suspicion.saturating_add(suspicion).min(100);
+                narrative.push_str(" Someone was watching. Your suspicion rises.");
+            }
+            info!(
+                "Player {} retrieved '{}' at {}",
+                player_id, text_id, character.location
+            );
+            character.hidden_texts.remove(&text_id);
+            character.inventory.push(text_id);
+            send_message_to_client(
+                clients,
+                player_id,
+                &ServerMessage::NarrativeUpdate(narrative),
+            );
+            send_message_to_client(clients, player_id, &stat_update(character));
+        }
+        ClientMessage::RequestLeaderboard => {
+            let leaderboard_msg = ServerMessage::Leaderboard {
+                entries: leaderboard(game_state),
+            };
+            send_message_to_client(clients, player_id, &leaderboard_msg);
+        }
+        ClientMessage::QueryActions => {
+            let actions_msg = ServerMessage::AvailableActions {
+                actions: available_actions(game_state, player_id, config),
+            };
+            send_message_to_client(clients, player_id, &actions_msg);
+        }
+        ClientMessage::RequestMap => {
+            let map_json = match &game_state.world_map_json {
+                Some(json) => json.clone(),
+                None => {
+                    let map_msg = ServerMessage::WorldMap {
+                        locations: game_state.world_state.map(),
+                    };
+                    let Ok(json) = serde_json::to_string(&map_msg) else {
+                        warn!("Failed to serialize the world map");
+                        return;
+                    };
+                    game_state.world_map_json = Some(json.clone());
+                    json
+                }
+            };
+            send_text_to_client(clients, player_id, map_json);
+        }
+        ClientMessage::RequestEventLog { limit } => {
+            // Players without a character yet only get the news told everywhere
+            let location = game_state
+                .players
+                .get(&player_id)
+                .map(|character| character.location.as_str());
+            let log_msg = ServerMessage::EventLog {
+                events: game_state.recent_events(location, limit.min(EVENT_LOG_CAPACITY)),
+            };
+            send_message_to_client(clients, player_id, &log_msg);
+        }
+        ClientMessage::ListHiddenTexts => {
+            let Some(character) = game_state.players.get(&player_id) else {
+                warn!("ListHiddenTexts from unknown player {}", player_id);
+                return;
+            };
+            let list_msg = ServerMessage::HiddenTextList {
+                texts: character.hidden_texts.clone(),
+            };
+            send_message_to_client(clients, player_id, &list_msg);
+        }
+        // Add a wildcard match arm to handle all other cases for now
+        _ => {
+            warn!(
+                "Unhandled message type from player {}: {:?}",
+                player_id, msg
+            );
+            let error_msg = ServerMessage::error(
+                ErrorCode::NotImplemented,
+                "This feature is not yet implemented",
+            );
+            send_message_to_client(clients, player_id, &error_msg);
+        }
+    }
+    // Note: Broadcasting the entire state on every action can be inefficient.
+    // Consider sending targeted updates or deltas in a more complex implementation.
+    // For now, broadcasting the whole state is simpler.
+    // broadcast_state_update(clients, game_state); // Moved inside handlers where state changes
+}
+
+// Frame for an outgoing text message, deflated with zlib into a binary frame when the client
+// asked for compression and the message is large enough to benefit
+fn outgoing_frame(text: String, compress: bool) -> warp::ws::Message {
+    if !compress || text.len() < COMPRESS_MIN_BYTES {
+        return warp::ws::Message::text(text);
+    }
+    match deflate(text.as_bytes()) {
+        Ok(bytes) => warp::ws::Message::binary(bytes),
+        Err(e) => {
+            warn!("Failed to compress message, sending it as text: {}", e);
+            warp::ws::Message::text(text)
+        }
+    }
+}
+
+fn deflate(bytes: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+/// Remove a disconnected client, telling everyone else it left once its character is gone.
+///
+/// With a non-zero `grace` the character stays in the game, frozen, until
+/// [`purge_disconnected`] removes it, so a momentary drop doesn't cost the player anything.
+///
+/// Both the forwarding task and the receive loop call this when a connection dies. Whichever
+/// call gets there first does the cleanup; later calls return `false` and do nothing.
+pub fn handle_disconnect(
+    player_id: Uuid,
+    clients: &Clients,
+    game_state: &SharedGameState,
+    metrics: &Metrics,
+    grace: Duration,
+    archive_character: bool, // Keep the character for a later reconnect by name
+) -> bool {
+    // Holding the state lock serializes racing calls
+    let mut state_guard = lock_state(game_state);
+    let removed_client = lock_clients(clients).remove(&player_id).is_some();
+    let character = state_guard.players.get_mut(&player_id);
+    let lingering = character
+        .as_ref()
+        .is_some_and(|character| character.disconnected_at.is_some());
+    if !removed_client && (character.is_none() || lingering) {
+        debug!("Client {} already disconnected", player_id);
+        return false;
+    }
+    info!("Client {} disconnected", player_id);
+    metrics.record_disconnect();
+
+    match character {
+        Some(character) if !grace.is_zero() => {
+            info!(
+                "Keeping character '{}' of player {} for {:?} in case they return",
+                character.name, player_id, grace
+            );
+            character.disconnected_at = Some(Instant::now());
+        }
+        Some(_) => {
+            if let Some(character) = state_guard.players.remove(&player_id) {
+                retire_character(
+                    &mut state_guard,
+                    clients,
+                    player_id,
+                    character,
+                    archive_character,
+                );
+            }
+        }
+        None => info!(
+            "Disconnect for player {} who hadn't created a character.",
+            player_id
+        ),
+    }
+    true
+}
+
+/// Remove characters whose players have been gone for longer than `grace`, telling everyone
+/// they left; returns the ids removed
+pub fn purge_disconnected(
+    game_state: &mut GameState,
+    clients: &Clients,
+    grace: Duration,
+    archive_characters: bool,
+) -> Vec<Uuid> {
+    let expired: Vec<Uuid> = game_state
+        .players
+        .iter()
+        .filter(|(_, character)| {
+            character
+                .disconnected_at
+                .is_some_and(|at| at.elapsed() >= grace)
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    for id in &expired {
+        if let Some(character) = game_state.players.remove(id) {
+            retire_character(game_state, clients, *id, character, archive_characters);
+        }
+    }
+    expired
+}
+
+// Archive or drop a character that has left the game and tell the remaining clients
+fn retire_character(
+    game_state: &mut GameState,
+    clients: &Clients,
+    player_id: Uuid,
+    mut character: Character,
+    archive: bool,
+) {
+    if archive {
+        info!(
+            "Archived character '{}' of player {}",
+            character.name, player_id
+        );
+        character.disconnected_at = None;
+        game_state
+            .saved_characters
+            .insert(character.name.clone(), character);
+    } else {
+        info!("Removed character data for player {}", player_id);
+    }
+    // Notify remaining clients that the player left
+    let leave_msg = ServerMessage::PlayerLeft { player_id };
+    broadcast_message(clients, Some(&player_id), &leave_msg); // Send to everyone else
+}
+
+// Retire a logging-out player's character at once, skipping the grace period, and close
+// their connection; the connection's own cleanup then finds nothing left to do but count it
+fn log_out(
+    player_id: Uuid,
+    save: bool,
+    game_state: &mut GameState,
+    clients: &Clients,
+    config: &ServerConfig,
+) {
+    let archive = save && config.restore_characters;
+    if let Some(character) = game_state.players.remove(&player_id) {
+        info!(
+            "Player {} logged out of '{}' (save: {})",
+            player_id, character.name, archive
+        );
+        retire_character(game_state, clients, player_id, character, archive);
+    }
+    let reason = if archive {
+        "Logged out; your character is kept for your return"
+    } else {
+        "Logged out"
+    };
+    let close = TungsteniteMessage::Close(Some(CloseFrame {
+        code: CloseCode::Normal,
+        reason: reason.into(),
+    }));
+    if let Some(sender) = lock_clients(clients).get(&player_id) {
+        queue_message(&player_id, sender, close);
+    }
+}
+
+/// What to do about a player who has stopped sending messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdleAction {
+    /// Tell them they will be disconnected soon
+    Warn,
+    /// Disconnect them to free their slot
+    Disconnect,
+}
+
+/// Whether `character`'s player has been idle long enough to warn or disconnect, given the
+/// idle `timeout` (zero disables it).
+///
+/// Players flying or on a journey are busy even without sending anything.
+pub fn idle_action(character: &Character, timeout: Duration) -> Option<IdleAction> {
+    let airborne = character.position.y > terrain_height(&character.location);
+    if timeout.is_zero() || airborne || !character.travel_route.is_empty() {
+        return None;
+    }
+    let idle = character.last_input_at.elapsed();
+    if idle >= timeout {
+        Some(IdleAction::Disconnect)
+    } else if !character.idle_warned
+        && idle >= timeout.saturating_sub(Duration::from_secs(IDLE_WARNING_SECS))
+    {
+        Some(IdleAction::Warn)
+    } else {
+        None
+    }
+}
+
+/// Characters whose players are connected; those lingering after a disconnect are frozen and
+/// skipped by the game loop.
+///
+/// They come in player id order, so the random draws made for each are the same every run.
+pub fn active_players(
+    players: &mut HashMap<Uuid, Character>,
+) -> impl Iterator<Item = (&Uuid, &mut Character)> {
+    let mut active: Vec<_> = players
+        .iter_mut()
+        .filter(|(_, character)| character.disconnected_at.is_none())
+        .collect();
+    active.sort_unstable_by_key(|(id, _)| **id);
+    active.into_iter()
+}
+
+// Queue a message for a client; returns false if the client has fallen so far behind that
+// its buffer is full, in which case the caller drops its sender to disconnect it
+fn queue_message(player_id: &Uuid, sender: &ClientSender, message: TungsteniteMessage) -> bool {
+    match sender.try_send(message) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            warn!(
+                "Disconnecting client {}: too slow, {} messages already queued",
+                player_id,
+                sender.max_capacity()
+            );
+            false
+        }
+        Err(TrySendError::Closed(_)) => {
+            warn!(
+                "Failed to send message to client {} (already disconnected?)",
+                player_id
+            );
+            // Sender will be removed soon by the disconnect handler
+            true
+        }
+    }
+}
+
+// Helper to send a ServerMessage to a specific client
+fn send_message_to_client(clients: &Clients, player_id: Uuid, message: &ServerMessage) {
+    if let Ok(serialized_msg) = serde_json::to_string(message) {
+        send_text_to_client(clients, player_id, serialized_msg);
+    } else {
+        warn!("Failed to serialize message for client {}", player_id);
+    }
+}
+
+// Send a message that has already been serialized, e.g. one cached for reuse
+fn send_text_to_client(clients: &Clients, player_id: Uuid, serialized_msg: String) {
+    let mut clients_map = lock_clients(clients);
+    if let Some(sender) = clients_map.get(&player_id) {
+        if !queue_message(&player_id, sender, TungsteniteMessage::Text(serialized_msg)) {
+            clients_map.remove(&player_id);
+        }
+    } else {
+        warn!(
+            "Attempted to send message to non-existent client {}",
+            player_id
+        );
+    }
+}
+
+// Helper: Broadcast Message to All Clients (Optionally Exclude One)
+// Ensure the signature correctly uses Option<&Uuid>
+fn broadcast_message(clients: &Clients, exclude_player_id: Option<&Uuid>, message: &ServerMessage) {
+    match serde_json::to_string(message) {
+        Ok(serialized_msg) => {
+            let mut clients_map = lock_clients(clients);
+            clients_map.retain(|id, sender| {
+                // Send if not excluded
+                exclude_player_id == Some(id)
+                    || queue_message(id, sender, TungsteniteMessage::Text(serialized_msg.clone()))
+            });
+        }
+        Err(e) => {
+            warn!("Failed to serialize broadcast message {:?}: {}", message, e);
+        }
+    }
+}
+
+// A tick's messages for each client, held back so each client gets them in a single frame
+#[derive(Default)]
+struct TickOutbox {
+    messages: HashMap<Uuid, Vec<ServerMessage>>,
+}
+
+impl TickOutbox {
+    // Hold a message for one client
+    fn send(&mut self, player_id: Uuid, message: &ServerMessage) {
+        self.messages
+            .entry(player_id)
+            .or_default()
+            .push(message.clone());
+    }
+
+    // Hold a message for every connected client, optionally except one
+    fn broadcast(
+        &mut self,
+        clients: &Clients,
+        exclude_player_id: Option<&Uuid>,
+        message: &ServerMessage,
+    ) {
+        let ids: Vec<Uuid> = lock_clients(clients)
+            .keys()
+            .filter(|id| exclude_player_id != Some(id))
+            .copied()
+            .collect();
+        for id in ids {
+            self.send(id, message);
+        }
+    }
+
+    // Send what is held for one client: a lone message as it is, several as one BatchUpdate
+    fn flush_client(&mut self, clients: &Clients, player_id: Uuid) {
+        let Some(mut messages) = self.messages.remove(&player_id) else {
+            return;
+        };
+        let message = if messages.len() == 1 {
+            messages.remove(0)
+        } else {
+            ServerMessage::BatchUpdate { messages }
+        };
+        send_message_to_client(clients, player_id, &message);
+    }
+
+    // Send everything still held
+    fn flush(&mut self, clients: &Clients) {
+        let ids: Vec<Uuid> = self.messages.keys().copied().collect();
+        for id in ids {
+            self.flush_client(clients, id);
+        }
+    }
+}
+
+// Tell only the players whose character is at `location` what happened, and log it there
+fn narrate_at_location(
+    clients: &Clients,
+    game_state: &mut GameState,
+    location: &str,
+    exclude_player_id: Option<&Uuid>,
+    text: String,
+) {
+    let message = ServerMessage::NarrativeUpdate(text.clone());
+    for (id, character) in &game_state.players {
+        if character.location == location && exclude_player_id != Some(id) {
+            send_message_to_client(clients, *id, &message);
+        }
+    }
+    game_state.record_event(Some(location), text);
+}
+
+// Stats of `character` for its owner alone
+fn stat_update(character: &Character) -> ServerMessage {
+    ServerMessage::StatUpdate {
+        loyalty: character.loyalty,
+        suspicion: character.suspicion,
+        thoughtcrime: character.thoughtcrime,
+        health: character.health,
+        injuries: character.injuries.clone(),
+        inventory: character.inventory.clone(),
+        morale: character.morale,
+        rebellion_score: character.rebellion_score,
+        economic_freedom_score: character.economic_freedom_score,
+        skills: character.skills.clone(),
+    }
+}
+
+/// What `player_id`'s character can do right now, from their location, occupation, inventory
+/// and who is nearby; empty without a character.
+///
+/// Mirrors the checks the handlers make about place, occupation and company, so clients
+/// needn't repeat them; an action listed can still fail on its own details or on chance.
+pub fn available_actions(
+    game_state: &GameState,
+    player_id: Uuid,
+    config: &ServerConfig,
+) -> Vec<AvailableAction> {
+    let Some(character) = game_state.players.get(&player_id) else {
+        return Vec::new();
+    };
+    let mut actions = Vec::new();
+    let mut offer = |message: &str, targets: Vec<String>| {
+        actions.push(AvailableAction {
+            message: message.to_string(),
+            targets,
+        });
+    };
+
+    // Under interrogation there is only O'Brien to answer
+    match &character.detention {
+        Some(ThoughtcrimeConsequence::Interrogation { interrogator, .. }) => {
+            offer("DialogueChoice", vec![interrogator.clone()]);
+            return actions;
+        }
+        Some(_) => return actions,
+        None => {}
+    }
+
+    let world = &game_state.world_state;
+    let here = character.location.as_str();
+    let (npcs, _) = who_is_here(game_state, player_id, here);
+    let mut players: Vec<String> = game_state
+        .players
+        .iter()
+        .filter(|(id, other)| {
+            **id != player_id && other.location == here && other.disconnected_at.is_none()
+        })
+        .map(|(_, other)| other.name.clone())
+        .collect();
+    players.sort();
+    let mut carried = character.inventory.clone();
+    carried.sort();
+    carried.dedup();
+    let texts: Vec<String> = carried
+        .iter()
+        .filter(|item| world.forbidden_texts.contains_key(item.as_str()))
+        .cloned()
+        .collect();
+
+    // Getting about
+    if let Some(location) = world.locations.get(here) {
+        offer("MoveRequest", location.connections.clone());
+    }
+    let destinations: Vec<String> = world
+        .map()
+        .into_iter()
+        .map(|location| location.name)
+        .filter(|name| name != here)
+        .collect();
+    offer("TravelTo", destinations);
+    if character.can_fly(config.flight_min_tasks) {
+        offer("FlyInput", Vec::new());
+        offer("SetFlightAssist", Vec::new());
+    }
+
+    // Everyday life
+    offer("LookAround", Vec::new());
+    offer("SearchRequest", Vec::new());
+    offer("WorkRequest", Vec::new());
+    offer("RestRequest", Vec::new());
+    offer("Reflect", Vec::new());
+    offer("JournalWriteRequest", Vec::new());
+    offer("InspectInventory", Vec::new());
+    if character.occupation == RECORDS_OCCUPATION && here == RECORDS_LOCATION {
+        offer("AlterRecord", Vec::new());
+    }
+    if world.hate_in_progress() && HATE_LOCATIONS.contains(&here) && !character.attended_hate {
+        offer("ParticipateInHate", Vec::new());
+    }
+
+    // Items and texts
+    let usable: Vec<String> = carried
+        .iter()
+        .filter(|item| items::find_item(item).is_some())
+        .cloned()
+        .collect();
+    if !usable.is_empty() {
+        offer("UseItem", usable);
+    }
+    if !carried.is_empty() {
+        offer("DropItem", carried.clone());
+    }
+    if !texts.is_empty() {
+        offer("ReadForbiddenText", texts.clone());
+        offer("HideForbiddenText", texts.clone());
+    }
+    let mut hidden_here: Vec<String> = character
+        .hidden_texts
+        .iter()
+        .filter(|(_, hidden)| hidden.location == here)
+        .map(|(text_id, _)| text_id.clone())
+        .collect();
+    hidden_here.sort();
+    if !hidden_here.is_empty() {
+        offer("RetrieveHiddenText", hidden_here);
+    }
+
+    // NPCs
+    if !npcs.is_empty() {
+        offer("InteractRequest", npcs.clone());
+        offer("ShareForbiddenKnowledge", npcs.clone());
+        if !carried.is_empty() {
+            offer("VoluntaryExchange", npcs.clone());
+        }
+        let talkative: Vec<String> = npcs
+            .iter()
+            .filter(|name| {
+                world.npcs[name.as_str()]
+                    .dialogue
+                    .contains_key(DIALOGUE_START)
+            })
+            .cloned()
+            .collect();
+        if !talkative.is_empty() {
+            offer("StartDialogue", talkative);
+        }
+        let teachers: Vec<String> = npcs
+            .iter()
+            .filter(|name| TEACHERS.iter().any(|(teacher, _, _)| teacher == name))
+            .cloned()
+            .collect();
+        if !teachers.is_empty() && character.last_lesson_day != Some(game_state.day) {
+            offer("LearnFromNpc", teachers);
+        }
+    }
+
+    // Other players
+    if !players.is_empty() {
+        offer("ExaminePlayer", players.clone());
+        offer("ReportPlayer", players.clone());
+        if !texts.is_empty() {
+            offer("GiveText", players);
+        }
+    }
+
+    // The cat
+    match &character.cat_companion {
+        Some(cat) if cat.status == CatStatus::Lost => offer("CallCat", Vec::new()),
+        Some(cat) if cat.location == here => offer("CareForCat", Vec::new()),
+        _ => {}
+    }
+
+    actions
+}
+
+/// The best characters in play, highest score first, at most `LEADERBOARD_SIZE` of them.
+///
+/// The score adds rebellion, loyalty, tasks completed and days survived; ties go by name.
+pub fn leaderboard(game_state: &GameState) -> Vec<LeaderboardEntry> {
+    let mut entries: Vec<LeaderboardEntry> = game_state
+        .players
+        .values()
+        .map(|character| {
+            let days_survived = game_state.day.saturating_sub(character.created_day);
+            LeaderboardEntry {
+                name: character.name.clone(),
+                rebellion_score: character.rebellion_score,
+                loyalty: character.loyalty,
+                tasks_completed: character.tasks_completed,
+                days_survived,
+                score: (u32::from(character.rebellion_score) + u32::from(character.loyalty))
+                    .saturating_add(
+                        character
+                            .tasks_completed
+                            .saturating_mul(LEADERBOARD_POINTS_PER_TASK),
+                    )
+                    .saturating_add(days_survived.saturating_mul(LEADERBOARD_POINTS_PER_DAY)),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+    entries.truncate(LEADERBOARD_SIZE);
+    entries
+}
+
+/// Number of characters and their mean suspicion and thoughtcrime at every location.
+///
+/// For operators only; it would show players where the rebels gather.
+pub fn surveillance_heat_map(game_state: &GameState) -> BTreeMap<String, LocationHeat> {
+    let mut totals: BTreeMap<String, (usize, u32, u32)> = game_state
+        .world_state
+        .locations
+        .keys()
+        .map(|name| (name.clone(), (0, 0, 0)))
+        .collect();
+    for character in game_state.players.values() {
+        let (players, suspicion, thoughtcrime) =
+            totals.entry(character.location.clone()).or_default();
+        *players += 1;
+        *suspicion += u32::from(character.suspicion);
+        *thoughtcrime += u32::from(character.thoughtcrime);
+    }
+    totals
+        .into_iter()
+        .map(|(location, (players, suspicion, thoughtcrime))| {
+            let average = |total: u32| {
+                if players == 0 {
+                    0.0
+                } else {
+                    total as f32 / players as f32
+                }
+            };
+            let heat = LocationHeat {
+                players,
+                average_suspicion: average(suspicion),
+                average_thoughtcrime: average(thoughtcrime),
+            };
+            (location, heat)
+        })
+        .collect()
+}
+
+// Helper to broadcast the public game state
+fn broadcast_state_update(clients: &Clients, public_state: PublicGameState) {
+    let update_msg = ServerMessage::GameStateUpdate(public_state);
+    if let Ok(serialized_msg) = serde_json::to_string(&update_msg) {
+        lock_clients(clients).retain(|id, sender| {
+            queue_message(id, sender, TungsteniteMessage::Text(serialized_msg.clone()))
+        });
+    } else {
+        warn!("Failed to serialize game state for broadcast update");
+    }
+}
+
+/// Integrate one flight step for `character` through air moving at `wind`.
+///
+/// Delegates to [`physics::integrate`] with the airframe and gravity from
+/// `physics`; ground contact is left to the caller.
+pub fn integrate_flight(
+    character: &mut Character,
+    wind: Vector3<f32>,
+    dt: f32,
+    physics: &PhysicsConfig,
+) -> AeroSample {
+    let mut state = FlightState {
+        position: character.position,
+        velocity: character.velocity,
+        orientation: character.orientation,
+        throttle: character.throttle,
+    };
+    let sample = physics::integrate(&physics.airframe(), &mut state, wind, physics.gravity, dt);
+    character.position = state.position;
+    character.velocity = state.velocity;
+    sample
+}
+
+/// A touchdown fast enough to damage the aircraft
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HardLanding {
+    /// Sink rate at touchdown in m/s
+    pub impact_speed: f32,
+    /// Health lost
+    pub damage: u8,
+}
+
+/// Outcome of one [`step_character_physics`] call
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicsStep {
+    /// Airflow measured during the step
+    pub aero: AeroSample,
+    /// Whether the character ended the step resting on the terrain
+    pub on_ground: bool,
+    /// Set when the character hit the ground too fast
+    pub hard_landing: Option<HardLanding>,
+    /// Whether the character stalled this step after flying unstalled
+    pub stall_began: bool,
+    /// Whether the character came down onto the terrain this step after flying
+    pub touchdown: bool,
+    /// Whether the step would have moved the character further than any aircraft can fly, and
+    /// was cut short
+    pub implausible_jump: bool,
+}
+
+/// Advance `character` by one tick of flight physics through air moving at `wind`.
+///
+/// Integrates the flight, clamps the aircraft to the terrain under its
+/// location (applying hard-landing damage and ground friction) and updates
+/// its stall flag. Deterministic for a given input; notifying the player is
+/// left to the caller.
+pub fn step_character_physics(
+    character: &mut Character,
+    wind: Vector3<f32>,
+    dt: f32,
+    physics: &PhysicsConfig,
+) -> PhysicsStep {
+    // Stop rounding error from building up in the orientation over many ticks
+    character.orientation.renormalize();
+    if !character.orientation.coords.iter().all(|v| v.is_finite()) {
+        // Nothing to recover the heading from, so level the aircraft out
+        warn!(
+            "Resetting corrupt orientation of {} to level flight",
+            character.name
+        );
+        character.orientation = UnitQuaternion::identity();
+    }
+    if !flight_state_is_finite(character) {
+        // Integrating NaN or infinity would only spread it further
+        warn!(
+            "Skipping physics for {} with a non-finite flight state",
+            character.name
+        );
+        return PhysicsStep {
+            aero: AeroSample {
+                airspeed: 0.0,
+                angle_of_attack: 0.0,
+            },
+            on_ground: false,
+            hard_landing: None,
+            stall_began: false,
+            touchdown: false,
+            implausible_jump: false,
+        };
+    }
+    let ground_height = terrain_height(&character.location);
+    let was_airborne = character.position.y > ground_height;
+    // Input only counts as held until the tick that follows it
+    let stick_held = std::mem::take(&mut character.stick_held);
+    if was_airborne && character.flight_assist && !stick_held {
+        assist_flight(character, dt);
+    }
+    let start = character.position;
+    let aero = integrate_flight(character, wind, dt, physics);
+    let implausible_jump = clamp_implausible_motion(character, start, dt);
+
+    // Prevent falling through the terrain under the player's location
+    let on_ground = character.position.y <= ground_height;
+    let mut hard_landing = None;
+    if character.position.y < ground_height {
+        character.position.y = ground_height;
+        // Zero out vertical velocity on collision
+        if character.velocity.y < 0.0 {
+            let impact_speed = -character.velocity.y;
+            character.velocity.y = 0.0;
+            if impact_speed > SAFE_LANDING_SPEED {
+                let damage = ((impact_speed - SAFE_LANDING_SPEED) * HARD_LANDING_DAMAGE_PER_MPS)
+                    .round()
+                    .min(u8::MAX as f32) as u8;
+                character.injure("Hard landing", damage);
+                hard_landing = Some(HardLanding {
+                    impact_speed,
+                    damage,
+                });
+            }
+        }
+        // Ground friction
+        character.velocity.x *= physics.ground_friction;
+        character.velocity.z *= physics.ground_friction;
+    }
+
+    let stalled = !on_ground && aero.is_stalled();
+    let stall_began = stalled && !character.stalled;
+    character.stalled = stalled;
+
+    PhysicsStep {
+        aero,
+        on_ground,
+        hard_landing,
+        stall_began,
+        touchdown: was_airborne && on_ground,
+        implausible_jump,
+    }
+}
+
+/// The location on the flight map nearest to `position`, ignoring height
+pub fn nearest_location(world: &WorldState, position: &Point3<f32>) -> Option<String> {
+    world
+        .locations
+        .keys()
+        .filter_map(|name| {
+            let (x, z) = location_coordinates(name)?;
+            Some((name, (x - position.x).hypot(z - position.z)))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(name, _)| name.clone())
+}
+
+/// Whether `position` is in the airspace over the Ministry of Love
+pub fn in_restricted_airspace(position: &Point3<f32>) -> bool {
+    location_coordinates(MINISTRY_OF_LOVE)
+        .is_some_and(|(x, z)| (x - position.x).hypot(z - position.z) < RESTRICTED_AIRSPACE_RADIUS)
+}
+
+/// Carry the outcome of a physics `step` over to the RPG map.
+///
+/// Flying into the Ministry of Love's airspace raises suspicion, and touching down moves the
+/// character to the nearest location, which at the Ministry of Love is an arrest. `previous` is
+/// where the character was before the step. Detained characters and the Inner Party are left
+/// alone. Returns what to tell the player.
+pub fn apply_flight_to_map(
+    character: &mut Character,
+    world: &WorldState,
+    previous: &Point3<f32>,
+    step: &PhysicsStep,
+) -> Vec<ServerMessage> {
+    let mut messages = Vec::new();
+    if character.detention.is_some() {
+        return messages;
+    }
+    let inner_party = character.is_inner_party();
+    if !step.on_ground
+        && !inner_party
+        && in_restricted_airspace(&character.position)
+        && !in_restricted_airspace(previous)
+    {
+        character.suspicion = character
+            .suspicion
+            .saturating_add(AIRSPACE_SUSPICION)
+            .min(100);
+        messages.push(ServerMessage::TeleScreenWarning {
+            message: format!(
+                "You have entered the airspace of the {}. Turn back at once.",
+                MINISTRY_OF_LOVE
+            ),
+            severity: 4,
+        });
+    }
+    if !step.touchdown {
+        return messages;
+    }
+    let Some(landed) =
+        nearest_location(world, &character.position).filter(|name| *name != character.location)
+    else {
+        return messages;
+    };
+    if landed == MINISTRY_OF_LOVE && !inner_party {
+        messages.push(ServerMessage::NarrativeUpdate(format!(
+            "You touch down inside the walls of the {}. The guards are waiting.",
+            MINISTRY_OF_LOVE
+        )));
+        messages.push(begin_interrogation(character));
+    } else {
+        // Stay where the wheels came down rather than jumping to the location's centre
+        character.travel_route.clear();
+        character.position.y = terrain_height(&landed);
+        character.location = landed.clone();
+        messages.push(ServerMessage::NarrativeUpdate(format!(
+            "You touch down at {}.",
+            landed
+        )));
+    }
+    messages
+}
+
+// Roll the wings a little back towards the horizon, keeping the nose where it points. The stick
+// turns the aircraft directly rather than setting it spinning, so with the stick let go the only
+// rotation left to damp is the bank it was left in.
+fn assist_flight(character: &mut Character, dt: f32) {
+    let forward = character.orientation * Vector3::z();
+    if forward.y.abs() > AUTO_LEVEL_MAX_CLIMB {
+        return;
+    }
+    let level = UnitQuaternion::face_towards(&forward, &Vector3::y());
+    if let Some(assisted) =
+        character
+            .orientation
+            .try_slerp(&level, (AUTO_LEVEL_RATE * dt).min(1.0), 1.0e-6)
+    {
+        character.orientation = assisted;
+    }
+}
+
+// The server is the only authority on where aircraft are: however the state got that way, a
+// step covering more ground than `MAX_PLAUSIBLE_SPEED` allows is cut back to that speed.
+// Returns whether anything was clamped.
+fn clamp_implausible_motion(character: &mut Character, start: Point3<f32>, dt: f32) -> bool {
+    let max_step = MAX_PLAUSIBLE_SPEED * dt;
+    let moved = character.position - start;
+    let jumped = moved.norm() > max_step;
+    let too_fast = character.velocity.norm() > MAX_PLAUSIBLE_SPEED;
+    if jumped {
+        warn!(
+            "Clamping implausible {:.1} m move of player {} ({}) in one tick",
+            moved.norm(),
+            character.player_id,
+            character.name
+        );
+        character.position = start + moved.normalize() * max_step;
+    }
+    if too_fast {
+        warn!(
+            "Clamping implausible speed {:.1} m/s of player {} ({})",
+            character.velocity.norm(),
+            character.player_id,
+            character.name
+        );
+        character.velocity = character.velocity.normalize() * MAX_PLAUSIBLE_SPEED;
+    }
+    jumped || too_fast
+}
+
+// Whether every number the flight model works with is finite
+fn flight_state_is_finite(character: &Character) -> bool {
+    character.position.iter().all(|v| v.is_finite())
+        && character.velocity.iter().all(|v| v.is_finite())
+        && character.orientation.coords.iter().all(|v| v.is_finite())
+        && character.throttle.is_finite()
+}
+
+/// Whether the named NPC exists and is at `location`, so a player there can deal with them
+pub fn npc_present_at(world: &WorldState, npc_name: &str, location: &str) -> bool {
+    world
+        .npcs
+        .get(npc_name)
+        .is_some_and(|npc| npc.location == location)
+}
+
+/// Shift how NPCs feel about `character` by side: Party-aligned NPCs by `party`, rebel-aligned
+/// ones by `rebel`
+pub fn sway_aligned_npcs(character: &mut Character, world: &WorldState, party: i8, rebel: i8) {
+    for npc in world.npcs.values() {
+        let delta = match npc.alignment {
+            NpcAlignment::Party => party,
+            NpcAlignment::Rebel => rebel,
+            NpcAlignment::Neutral => continue,
+        };
+        if delta != 0 {
+            adjust_relationship(character, &npc.name, delta);
+        }
+    }
+}
+
+/// Shift how far NPCs trust anyone by side, as world events change the mood; trust stays
+/// within -100..=100
+pub fn drift_npc_trust(world: &mut WorldState, party: i8, rebel: i8) {
+    for npc in world.npcs.values_mut() {
+        let delta = match npc.alignment {
+            NpcAlignment::Party => party,
+            NpcAlignment::Rebel => rebel,
+            NpcAlignment::Neutral => continue,
+        };
+        npc.trust = npc.trust.saturating_add(delta).clamp(-100, 100);
+    }
+}
+
+// The named NPC if it is at `location`, otherwise the error text to send back
+fn npc_at_location<'a>(
+    world: &'a WorldState,
+    npc_name: &str,
+    location: &str,
+) -> Result<&'a Npc, String> {
+    let Some(npc) = world.npcs.get(npc_name) else {
+        return Err(format!("Unknown NPC: {}", npc_name));
+    };
+    if !npc_present_at(world, npc_name, location) {
+        return Err(format!("{} is not here.", npc_name));
+    }
+    Ok(npc)
+}
+
+// Apply an admin command; returns an announcement for everyone, if the event is public
+fn run_admin_command(
+    command: AdminCommand,
+    game_state: &mut GameState,
+    trust: &TrustDynamics,
+) -> Result<Option<String>, String> {
+    let world = &mut game_state.world_state;
+    match command {
+        AdminCommand::ForceEnemySwitch => {
+            world.current_enemy = if world.current_enemy == "Eurasia" {
+                "Eastasia".to_string()
+            } else {
+                "Eurasia".to_string()
+            };
+            drift_npc_trust(world, trust.enemy_switch_party, trust.enemy_switch_rebel);
+            Ok(Some(format!(
+                "Oceania is at war with {0}. Oceania has always been at war with {0}.",
+                world.current_enemy
+            )))
+        }
+        AdminCommand::TwoMinutesHate => {
+            world.two_minutes_hate_today = true;
+            world.hate_seconds_left = HATE_DURATION_SECS;
+            for character in game_state.players.values_mut() {
+                character.attended_hate = false;
+            }
+            Ok(Some(format!(
+                "The telescreens scream. Goldstein's face fills every screen as the Two Minutes Hate begins against {}.",
+                world.current_enemy
+            )))
+        }
+        AdminCommand::SetRation { amount } => {
+            // The Ministry of Plenty reports every cut as an increase
+            let announcement = if amount < world.chocolate_ration {
+                for character in game_state.players.values_mut() {
+                    character.morale = character.morale.saturating_sub(RATION_CUT_MORALE);
+                }
+                format!(
+                    "The Ministry of Plenty announces that the chocolate ration has been increased to {} grams.",
+                    amount
+                )
+            } else {
+                format!("The chocolate ration is now {} grams.", amount)
+            };
+            world.chocolate_ration = amount;
+            Ok(Some(announcement))
+        }
+        AdminCommand::Teleport { player, location } => {
+            if !world.locations.contains_key(&location) {
+                return Err(format!("Unknown location: {}", location));
+            }
+            let Some(character) = game_state
+                .players
+                .values_mut()
+                .find(|character| character.name == player)
+            else {
+                return Err(format!("No player named {}", player));
+            };
+            character.travel_route.clear();
+            arrive_at(character, location);
+            Ok(None)
+        }
+        AdminCommand::SpawnText { location, text_id } => {
+            if !world.locations.contains_key(&location) {
+                return Err(format!("Unknown location: {}", location));
+            }
+            if !world.forbidden_texts.contains_key(&text_id) {
+                return Err(format!("Unknown text: {}", text_id));
+            }
+            let texts = world.text_locations.entry(location).or_default();
+            if !texts.contains(&text_id) {
+                texts.push(text_id);
+            }
+            Ok(None)
+        }
+    }
+}
+
+// What `npc_name` says at node `node_id` of `dialogue`, if the node exists
+fn dialogue_prompt(
+    npc_name: &str,
+    dialogue: &HashMap<String, DialogueNode>,
+    node_id: &str,
+) -> Option<ServerMessage> {
+    let node = dialogue.get(node_id)?;
+    Some(ServerMessage::DialoguePrompt {
+        npc_name: npc_name.to_string(),
+        text: node.text.clone(),
+        options: node
+            .options
+            .iter()
+            .map(|option| option.text.clone())
+            .collect(),
+    })
+}
+
+/// Understanding `character` gains from reading `text`: easier texts teach more, and little
+/// gets through in a language the character can't read
+pub fn reading_understanding(character: &Character, text: &ForbiddenText) -> u8 {
+    let ease = 11 - text.difficulty.clamp(1, 10);
+    let understanding = ease * READING_UNDERSTANDING_PER_EASE;
+    if character.languages.contains(&text.language) {
+        understanding
+    } else {
+        understanding / FOREIGN_TEXT_DIVISOR
+    }
+}
+
+// Raise the rebellion score, capped at 100; the Inner Party is watched too closely to make fast progress
+fn raise_rebellion(character: &mut Character, amount: u8) {
+    let amount = if character.is_inner_party() {
+        amount / 2
+    } else {
+        amount
+    };
+    character.rebellion_score = character.rebellion_score.saturating_add(amount).min(100);
+    character.morale = character.morale.saturating_add(amount).min(100);
+}
+
+/// Where `character`'s morale settles when left alone: higher with a cat at their side, lower
+/// while it is hurt or lost
+pub fn settled_morale(character: &Character) -> u8 {
+    match character.cat_companion.as_ref().map(|cat| &cat.status) {
+        Some(CatStatus::Following | CatStatus::Waiting) => STARTING_MORALE + CAT_MORALE,
+        Some(CatStatus::Injured | CatStatus::Lost) => STARTING_MORALE - CAT_MORALE,
+        None => STARTING_MORALE,
+    }
+}
+
+/// Points low morale takes off the percentile rolls of searches, trades and persuasion
+pub fn morale_penalty(character: &Character) -> i16 {
+    i16::from(LOW_MORALE.saturating_sub(character.morale))
+}
+
+// Put `character` at `destination`, keeping grounded characters on the ground there
+fn arrive_at(character: &mut Character, destination: String) {
+    if character.position.y <= terrain_height(&character.location) {
+        character.position.y = terrain_height(&destination);
+        if let Some((x, z)) = location_coordinates(&destination) {
+            character.position.x = x;
+            character.position.z = z;
+        }
+    }
+    character.location = destination;
+}
+
+// Names of the NPCs and of the connected players other than `player_id` at `location`, each
+// sorted
+fn who_is_here(
+    game_state: &GameState,
+    player_id: Uuid,
+    location: &str,
+) -> (Vec<String>, Vec<String>) {
+    let mut npcs: Vec<String> = game_state
+        .world_state
+        .npcs
+        .values()
+        .filter(|npc| npc.location == location)
+        .map(|npc| npc.name.clone())
+        .collect();
+    npcs.sort();
+    let mut players: Vec<String> = game_state
+        .players
+        .iter()
+        .filter(|(id, other)| {
+            **id != player_id && other.location == location && other.disconnected_at.is_none()
+        })
+        .map(|(_, other)| other.name.clone())
+        .collect();
+    players.sort();
+    (npcs, players)
+}
+
+// What `player_id` sees on arriving at `location`: its description and who is there
+fn arrival_narrative(game_state: &GameState, player_id: Uuid, location: &str) -> Option<String> {
+    let description = &game_state.world_state.locations.get(location)?.description;
+    let (npcs, players) = who_is_here(game_state, player_id, location);
+    let mut narrative = description.clone();
+    if !npcs.is_empty() {
+        narrative.push_str(&format!(" You notice {} here.", npcs.join(", ")));
+    }
+    if !players.is_empty() {
+        narrative.push_str(&format!(" Also here: {}.", players.join(", ")));
+    }
+    Some(narrative)
+}
+
+/// Chance that arriving somewhere with the given `safety` (1-5) leads to trouble
+pub fn travel_risk_chance(safety: u8, night: bool) -> f32 {
+    let chance = f32::from(5u8.saturating_sub(safety)) * TRAVEL_RISK_PER_UNSAFE_LEVEL;
+    if night && safety <= NIGHT_PATROL_MAX_SAFETY {
+        (chance * NIGHT_PATROL_FACTOR).min(1.0)
+    } else {
+        chance
+    }
+}
+
+/// Chance of being seen taking a hidden text back at a location with the given `safety` (1-5)
+pub fn retrieval_risk_chance(safety: u8) -> f32 {
+    f32::from(5u8.saturating_sub(safety)) * RETRIEVAL_RISK_PER_UNSAFE_LEVEL
+}
+
+/// Chance of being seen passing a text to another player at a location with the given
+/// `safety` (1-5)
+pub fn handoff_risk_chance(safety: u8) -> f32 {
+    f32::from(5u8.saturating_sub(safety)) * HANDOFF_RISK_PER_UNSAFE_LEVEL
+}
+
+// Roll for trouble on arriving at the character's current location.
+// Returns a narrative of what happened, if anything did.
+fn roll_travel_risk(
+    character: &mut Character,
+    world: &WorldState,
+    rng: &mut impl Rng,
+) -> Option<String> {
+    let destination = character.location.clone();
+    let safety = world.locations.get(&destination)?.safety;
+    if rng.gen::<f32>() >= travel_risk_chance(safety, world.is_night()) {
+        return None;
+    }
+    let roll = rng.gen::<f32>();
+    if roll < TRAVEL_ITEM_LOSS_SHARE && !character.inventory.is_empty() {
+        let item = character
+            .inventory
+            .remove(rng.gen_range(0..character.inventory.len()));
+        return Some(format!(
+            "In the crush on the way into {} someone relieves you of your {}.",
+            destination, item
+        ));
+    }
+    if roll < TRAVEL_ITEM_LOSS_SHARE + TRAVEL_ENCOUNTER_SHARE {
+        if let Some(npc) = world.npcs.values().find(|npc| npc.location == destination) {
+            return Some(format!(
+                "{} spots you arriving at {} and beckons you over.",
+                npc.name, destination
+            ));
+        }
+    }
+    // Anything else, or nothing to lose and nobody about, is a patrol
+    character.suspicion = character
+        .suspicion
+        .saturating_add(TRAVEL_PATROL_SUSPICION)
+        .min(100);
+    let mut narrative = format!(
+        "A patrol stops you at {} and checks your papers.",
+        destination
+    );
+    if let Some(cat) = character
+        .cat_companion
+        .as_mut()
+        .filter(|cat| cat.status == CatStatus::Following)
+    {
+        cat.status = CatStatus::Injured;
+        cat.location = destination;
+        narrative.push_str(&format!(
+            " A boot catches {} in the ribs, and it crawls away to lick its wounds.",
+            cat.name
+        ));
+    }
+    Some(narrative)
+}
+
+/// Move `character`'s cat along for `dt` seconds.
+///
+/// A following cat trails its owner but won't go anywhere dangerous, and is lost there; an
+/// injured cat stays put and weakens until cared for. A cat that dies fails the Kocourka quest.
+/// Returns a narrative when something happened to the cat.
+pub fn tick_cat(
+    character: &mut Character,
+    world: &WorldState,
+    dt: f32,
+    rng: &mut impl Rng,
+) -> Option<String> {
+    let cat = character.cat_companion.as_mut()?;
+    match cat.status {
+        CatStatus::Following if cat.location != character.location => {
+            let dangerous = world
+                .locations
+                .get(&character.location)
+                .is_some_and(|location| location.safety <= DANGEROUS_SAFETY);
+            if dangerous {
+                cat.status = CatStatus::Lost;
+                return Some(format!(
+                    "{} refuses to follow you into {} and slinks off.",
+                    cat.name, character.location
+                ));
+            }
+            cat.location = character.location.clone();
+            None
+        }
+        CatStatus::Injured => {
+            if rng.gen::<f32>() >= CAT_INJURY_DECAY_RATE * dt {
+                return None;
+            }
+            cat.health = cat.health.saturating_sub(1);
+            if cat.health > 0 {
+                return None;
+            }
+            let narrative = format!(
+                "{} has died of its wounds in {}. You failed it.",
+                cat.name, cat.location
+            );
+            character.cat_companion = None;
+            character.kocourka_quest_active = false;
+            character.kocourka_quest_failed = true;
+            Some(narrative)
+        }
+        _ => None,
+    }
+}
+
+// Routes only the Inner Party may take, on top of the map's connections
+fn inner_party_route(character: &Character, from: &str, to: &str) -> bool {
+    character.is_inner_party()
+        && ((from == INNER_PARTY_ENTRANCE && to == MINISTRY_OF_LOVE)
+            || (from == MINISTRY_OF_LOVE && to == INNER_PARTY_ENTRANCE))
+}
+
+/// Clock hour (0-23) after `day_elapsed` seconds of the in-game day
+pub fn hour_of_day(day_elapsed: f32) -> u8 {
+    ((DAY_START_HOUR + day_elapsed / DAY_LENGTH_SECS * 24.0) % 24.0) as u8
+}
+
+// Bring a character who would have died round at home, grounded, with a little health
+fn collapse(character: &mut Character, death_health: u8) {
+    character.travel_route.clear();
+    character.velocity = Vector3::zeros();
+    character.throttle = 0.0;
+    character.position.y = terrain_height(&character.location);
+    arrive_at(character, COLLAPSE_LOCATION.to_string());
+    character.health = COLLAPSE_RECOVERY_HEALTH.max(death_health.saturating_add(1));
+}
+
+/// Health recovered by resting, less for every injury still open
+pub fn rest_healing(character: &Character) -> u8 {
+    let hindered = REST_HEALING_LOST_PER_INJURY.saturating_mul(character.injuries.len() as u8);
+    REST_HEALING.saturating_sub(hindered).max(MIN_REST_HEALING)
+}
+
+/// Let a day pass for `character`'s injuries, each one healing a little; returns a narrative
+/// naming those that have healed completely
+pub fn heal_injuries(character: &mut Character) -> Option<String> {
+    let mut healed = Vec::new();
+    character.injuries.retain_mut(|injury| {
+        injury.severity = injury.severity.saturating_sub(INJURY_HEALING_PER_DAY);
+        if injury.severity == 0 {
+            healed.push(injury.cause.to_lowercase());
+        }
+        injury.severity > 0
+    });
+    (!healed.is_empty()).then(|| {
+        format!(
+            "The injuries from your {} have finally healed.",
+            healed.join(", ")
+        )
+    })
+}
+
+/// Advance the in-game clock by `dt` seconds; returns a `TimePulse` when a new hour begins
+pub fn advance_clock(state: &mut GameState, dt: f32) -> Option<ServerMessage> {
+    state.day_elapsed += dt;
+    if state.day_elapsed >= DAY_LENGTH_SECS {
+        state.day_elapsed -= DAY_LENGTH_SECS;
+        state.day += 1;
+        info!("Day {} begins", state.day);
+    }
+    let hour = hour_of_day(state.day_elapsed);
+    if hour == state.world_state.hour {
+        return None;
+    }
+    state.world_state.hour = hour;
+    Some(ServerMessage::TimePulse {
+        day: state.day,
+        current_date: state.world_state.current_date.clone(),
+        hour,
+    })
+}
+
+/// Run the Two Minutes Hate in progress for `dt` seconds; when it ends, everyone free who
+/// stayed away gains suspicion.
+///
+/// Returns the ids of the conspicuously absent.
+pub fn advance_hate(state: &mut GameState, dt: f32) -> Vec<Uuid> {
+    let world = &mut state.world_state;
+    if !world.hate_in_progress() {
+        return Vec::new();
+    }
+    world.hate_seconds_left -= dt;
+    if world.hate_seconds_left > 0.0 {
+        return Vec::new();
+    }
+    world.hate_seconds_left = 0.0;
+    world.two_minutes_hate_today = false;
+    let mut absent = Vec::new();
+    for (id, character) in active_players(&mut state.players) {
+        if character.attended_hate || character.detention.is_some() {
+            continue;
+        }
+        character.suspicion = character
+            .suspicion
+            .saturating_add(HATE_ABSENCE_SUSPICION)
+            .min(100);
+        absent.push(*id);
+    }
+    absent
+}
+
+/// Chance that the telescreens notice something about `character` within `dt` seconds.
+///
+/// Scales with thoughtcrime; telescreens pay less attention at night.
+pub fn telescreen_suspicion_chance(character: &Character, night: bool, dt: f32) -> f32 {
+    let surveillance = if night {
+        NIGHT_SURVEILLANCE_FACTOR
+    } else {
+        1.0
+    };
+    TELESCREEN_SUSPICION_RATE * dt * character.thoughtcrime.min(100) as f32 / 100.0 * surveillance
+}
+
+/// Move O'Brien's entrapment of `character` along on `day`.
+///
+/// Enough confidences or a high enough rebellion score make him schedule a betrayal; once
+/// the day comes, suspicion jumps to the brink of arrest. Returns the betrayal narrative.
+pub fn advance_entrapment(character: &mut Character, day: u32) -> Option<String> {
+    match character.entrapment {
+        Entrapment::Watching { confidences } => {
+            if confidences >= ENTRAPMENT_CONFIDENCES
+                || character.rebellion_score >= ENTRAPMENT_REBELLION_SCORE
+            {
+                character.entrapment = Entrapment::Scheduled {
+                    day: day + BETRAYAL_DELAY_DAYS,
+                };
+            }
+            None
+        }
+        Entrapment::Scheduled { day: betrayal_day } if day >= betrayal_day => {
+            character.entrapment = Entrapment::Betrayed;
+            character.suspicion = character.suspicion.max(BETRAYAL_SUSPICION);
+            Some(format!(
+                "{} smiles at you across the corridor. \"We shall meet in the place where there is no darkness,\" he says. Only now do you understand. Everything you told him is in a file at the Ministry of Love.",
+                ENTRAPPER
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// Take `character` to the Ministry of Love for questioning; returns the first question
+pub fn begin_interrogation(character: &mut Character) -> ServerMessage {
+    character.detention = Some(ThoughtcrimeConsequence::Interrogation {
+        location: MINISTRY_OF_LOVE.to_string(),
+        interrogator: INTERROGATOR.to_string(),
+    });
+    character.travel_route.clear();
+    arrive_at(character, MINISTRY_OF_LOVE.to_string());
+    character
+        .dialogue_nodes
+        .insert(INTERROGATOR.to_string(), DIALOGUE_START.to_string());
+    dialogue_prompt(INTERROGATOR, &interrogation_dialogue(), DIALOGUE_START).unwrap_or_else(|| {
+        ServerMessage::error(ErrorCode::Internal, "The interrogation has no questions.")
+    })
+}
+
+// Apply an answer to the current interrogation question and return what happens next.
+// After the last question the character is either reformed and released or broken.
+fn answer_interrogation(
+    character: &mut Character,
+    interrogator: &str,
+    option_id: usize,
+) -> ServerMessage {
+    let questions = interrogation_dialogue();
+    let node_id = character
+        .dialogue_nodes
+        .get(interrogator)
+        .map_or(DIALOGUE_START, String::as_str);
+    let Some(option) = questions
+        .get(node_id)
+        .and_then(|node| node.options.get(option_id))
+    else {
+        return ServerMessage::error(
+            ErrorCode::InvalidMessage,
+            format!("{} waits for a proper answer.", interrogator),
+        );
+    };
+    for effect in &option.effects {
+        effect.apply(character, interrogator);
+    }
+    if let Some(next) = &option.next {
+        character
+            .dialogue_nodes
+            .insert(interrogator.to_string(), next.clone());
+        return dialogue_prompt(interrogator, &questions, next).unwrap_or_else(|| {
+            ServerMessage::error(
+                ErrorCode::Internal,
+                format!("{} falls silent.", interrogator),
+            )
+        });
+    }
+
+    character.dialogue_nodes.remove(interrogator);
+    if character.thoughtcrime <= REFORM_MAX_THOUGHTCRIME {
+        info!("{} has been reformed", character.name);
+        character.detention = None;
+        character.suspicion = 0;
+        character.thoughtcrime = 0;
+        character.rebellion_score = 0;
+        character.loyalty = 100;
+        arrive_at(character, RELEASE_LOCATION.to_string());
+        ServerMessage::NarrativeUpdate("They let you go home. The struggle is finished. You have won the victory over yourself. You love Big Brother.".to_string())
+    } else {
+        info!("{} would not be reformed", character.name);
+        character.detention = Some(ThoughtcrimeConsequence::Arrest {
+            reason: "Unrepentant thoughtcrime".to_string(),
+        });
+        ServerMessage::NarrativeUpdate(
+            "You will not bend, so you will be broken. The door of the cell closes behind you."
+                .to_string(),
+        )
+    }
+}
+
+/// Track how long `character` has been loyal and promote them into the Inner Party once
+/// they have held high loyalty for enough days and completed enough tasks.
+///
+/// Returns whether the character was promoted.
+pub fn check_promotion(character: &mut Character, day: u32) -> bool {
+    if character.loyalty < PROMOTION_LOYALTY {
+        character.loyal_since_day = None;
+        return false;
+    }
+    let since = *character.loyal_since_day.get_or_insert(day);
+    if character.is_inner_party()
+        || day.saturating_sub(since) < PROMOTION_DAYS
+        || character.tasks_completed < PROMOTION_TASKS
+    {
+        return false;
+    }
+    character.occupation = INNER_PARTY_OCCUPATION.to_string();
+    true
+}
+
+/// Probability that the Thought Police search `character`'s home and find the diary
+/// during the next `dt` seconds.
+///
+/// Scales with suspicion; zero without journal entries or below the search threshold.
+pub fn diary_discovery_chance(character: &Character, dt: f32) -> f32 {
+    if character.journal_entries.is_empty() || character.suspicion < HOME_SEARCH_MIN_SUSPICION {
+        return 0.0;
+    }
+    let mut chance = HOME_SEARCH_RATE * dt * character.suspicion.min(100) as f32 / 100.0;
+    if character.journal_hiding_place.is_some() {
+        chance *= HIDDEN_DIARY_FACTOR;
+    }
+    chance
+}
+
+/// Chance that `character` sheds a point of suspicion during the next `dt` seconds on `day`.
+///
+/// Only characters lying low get any: a loyal act within the last day, somewhere safe, and no
+/// detention or surveillance. Slower than the telescreens add suspicion, so it takes effort.
+pub fn suspicion_decay_chance(character: &Character, world: &WorldState, day: u32, dt: f32) -> f32 {
+    let loyal_lately = character
+        .last_loyal_act_day
+        .is_some_and(|last| day.saturating_sub(last) <= LYING_LOW_DAYS);
+    let safe = world
+        .locations
+        .get(&character.location)
+        .is_some_and(|location| location.safety >= LYING_LOW_MIN_SAFETY);
+    if character.suspicion == 0 || character.detention.is_some() || !loyal_lately || !safe {
+        return 0.0;
+    }
+    SUSPICION_DECAY_RATE * dt
+}
+
+// Slowly veer the horizontal wind while holding its strength near `base_strength`
+fn drift_wind(wind: &mut Vector3<f32>, base_strength: f32, dt: f32, rng: &mut impl Rng) {
+    if base_strength <= 0.0 {
+        *wind = Vector3::zeros();
+        return;
+    }
+    let heading = wind.z.atan2(wind.x) + rng.gen_range(-1.0..=1.0) * WIND_VEER_RATE * dt;
+    let strength = (wind.norm() + rng.gen_range(-1.0..=1.0) * WIND_GUST_RATE * dt)
+        .clamp(base_strength * 0.5, base_strength * 1.5);
+    *wind = Vector3::new(heading.cos() * strength, 0.0, heading.sin() * strength);
+}
+
+// Small random perturbation on top of the prevailing wind, scaled by its strength
+fn turbulence(base_strength: f32, rng: &mut impl Rng) -> Vector3<f32> {
+    if base_strength <= 0.0 {
+        return Vector3::zeros();
+    }
+    Vector3::new(
+        rng.gen_range(-1.0..=1.0),
+        rng.gen_range(-1.0..=1.0),
+        rng.gen_range(-1.0..=1.0),
+    ) * base_strength
+        * TURBULENCE_INTENSITY
+}
+
+// A pair of aircraft found touching during a physics tick
+struct Collision {
+    a: Uuid,
+    b: Uuid,
+    normal: Vector3<f32>, // Unit vector pointing from a to b
+    closing_speed: f32,   // Positive when a and b approach each other
+}
+
+// Broad phase: pairs of players close enough to be touching, each pair once
+fn collision_candidates(
+    players: &HashMap<Uuid, Character>,
+    index: &SpatialIndex,
+) -> Vec<(Uuid, Uuid)> {
+    let mut pairs = Vec::new();
+    for (a, character) in players {
+        for b in index.query_radius(&character.position, COLLISION_RADIUS) {
+            if *a < b {
+                pairs.push((*a, b));
+            }
+        }
+    }
+    pairs
+}
+
+// Narrow phase: keep candidate pairs that overlap and are closing fast enough to hurt
+fn detect_collisions(players: &HashMap<Uuid, Character>, index: &SpatialIndex) -> Vec<Collision> {
+    let mut collisions = Vec::new();
+    for (a, b) in collision_candidates(players, index) {
+        let (char_a, char_b) = match (players.get(&a), players.get(&b)) {
+            (Some(char_a), Some(char_b)) => (char_a, char_b),
+            _ => continue,
+        };
+        let offset = char_b.position - char_a.position;
+        if offset.norm() > COLLISION_RADIUS {
+            continue;
+        }
+        // Aircraft sitting on top of each other have no meaningful contact normal
+        let normal = match offset.try_normalize(1.0e-6) {
+            Some(normal) => normal,
+            None => continue,
+        };
+        let closing_speed = (char_a.velocity - char_b.velocity).dot(&normal);
+        if closing_speed > MIN_COLLISION_SPEED {
+            collisions.push(Collision {
+                a,
+                b,
+                normal,
+                closing_speed,
+            });
+        }
+    }
+    collisions
+}
+
+// Damage both aircraft and bounce them apart; returns both names for the narrative
+fn resolve_collision(
+    players: &mut HashMap<Uuid, Character>,
+    collision: &Collision,
+) -> Option<(String, String)> {
+    // Split the impulse evenly so the pair separates at COLLISION_SEPARATION_SPEED
+    let impulse = collision.normal * (collision.closing_speed + COLLISION_SEPARATION_SPEED) / 2.0;
+
+    let char_a = players.get_mut(&collision.a)?;
+    char_a.injure("Mid-air collision", COLLISION_DAMAGE);
+    char_a.velocity -= impulse;
+    let name_a = char_a.name.clone();
+
+    let char_b = players.get_mut(&collision.b)?;
+    char_b.injure("Mid-air collision", COLLISION_DAMAGE);
+    char_b.velocity += impulse;
+    let name_b = char_b.name.clone();
+
+    Some((name_a, name_b))
+}
+
+/// Flight state last sent to a player's client
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SentPose {
+    /// Position in meters
+    pub position: Point3<f32>,
+    /// Body orientation
+    pub orientation: UnitQuaternion<f32>,
+    /// `seq` of the last FlyInput applied
+    pub last_input_seq: u32,
+}
+
+impl SentPose {
+    /// The pose `character` is in now
+    pub fn of(character: &Character) -> Self {
+        SentPose {
+            position: character.position,
+            orientation: character.orientation,
+            last_input_seq: character.last_input_seq,
+        }
+    }
 
-// Helper: Broadcast Message to All Clients (Optionally Exclude One)
-// Ensure the signature correctly uses Option<&Uuid>
-fn broadcast_message(clients: &Clients, exclude_player_id: Option<&Uuid>, message: &ServerMessage) {
-    match serde_json::to_string(message) {
-        Ok(serialized_msg) => {
-            let clients_map = clients.lock().unwrap();
-            for (id, sender) in clients_map.iter() {
-                // Send if not excluded
-                if exclude_player_id.map_or(true, |exclude_id| id != exclude_id) {
-                    if sender
-                        .send(TungsteniteMessage::Text(serialized_msg.clone()))
-                        .is_err()
-                    {
-                        warn!("Failed to broadcast to {} (already disconnected?)", id);
-                        // Disconnect logic will handle cleanup.
-                    }
+    /// Whether `character` has moved, turned or had input applied noticeably since this pose
+    /// was sent
+    pub fn differs_from(&self, character: &Character) -> bool {
+        (character.position - self.position).norm() > MOTION_EPSILON
+            || character.orientation.angle_to(&self.orientation) > ROTATION_EPSILON
+            || character.last_input_seq != self.last_input_seq
+    }
+}
+
+// Where and how often the game loop writes the world to disk
+struct Autosave {
+    path: PathBuf,
+    interval: Duration,
+}
+
+// Hash of everything worth persisting; the drifting wind and the time of day alone don't count as a change
+fn save_fingerprint(state: &GameState) -> Option<u64> {
+    let mut snapshot = state.clone();
+    snapshot.world_state.wind = Vector3::zeros();
+    snapshot.day_elapsed = 0.0;
+    snapshot.world_state.hour = 0;
+    let json = serde_json::to_string(&snapshot).ok()?;
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// Hash of what clients see of `world`, ignoring the wind, which drifts every tick, the hour,
+// which clients follow from time pulses, and the Hate countdown, which they don't show
+fn world_fingerprint(world: &WorldState) -> Option<u64> {
+    let mut snapshot = world.clone();
+    snapshot.wind = Vector3::zeros();
+    snapshot.hour = 0;
+    snapshot.hate_seconds_left = 0.0;
+    let json = serde_json::to_string(&snapshot).ok()?;
+    let mut hasher = DefaultHasher::new();
+    json.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// Keep the tick rate within what the loop can sensibly run at
+fn clamp_tick_hz(tick_hz: f32) -> f32 {
+    if !tick_hz.is_finite() {
+        warn!(
+            "Invalid tick rate {}, using {} Hz",
+            tick_hz, DEFAULT_TICK_HZ
+        );
+        return DEFAULT_TICK_HZ;
+    }
+    let clamped = tick_hz.clamp(MIN_TICK_HZ, MAX_TICK_HZ);
+    if clamped != tick_hz {
+        warn!(
+            "Tick rate {} Hz out of range, clamped to {} Hz",
+            tick_hz, clamped
+        );
+    }
+    clamped
+}
+
+/// What the game loop keeps from one tick to the next, apart from the game state itself
+pub struct Simulation {
+    dt: f32,
+    wind_strength: f32,
+    physics: PhysicsConfig,
+    disconnect_grace: Duration,
+    idle_timeout: Duration,
+    restore_characters: bool,
+    end_conditions: EndConditions,
+    broadcast_interval: Duration,
+    last_flight_broadcast: Instant,
+    sent_poses: HashMap<Uuid, SentPose>,
+    proximity: SpatialIndex, // Rebuilt from player positions every tick
+}
+
+impl Simulation {
+    /// Ticks at `config.tick_hz`, with the configured wind, physics and timeouts
+    pub fn new(config: &ServerConfig) -> Self {
+        let tick_hz = clamp_tick_hz(config.tick_hz);
+        let broadcast_hz = clamp_broadcast_hz(config.broadcast_hz, tick_hz);
+        info!(
+            "Simulating at {} Hz, flight updates at {} Hz",
+            tick_hz, broadcast_hz
+        );
+        Simulation {
+            dt: 1.0 / tick_hz,
+            wind_strength: config.wind.max(0.0),
+            physics: config.physics,
+            disconnect_grace: config.disconnect_grace(),
+            idle_timeout: Duration::from_secs(config.idle_timeout_secs),
+            restore_characters: config.restore_characters,
+            end_conditions: config.end_conditions,
+            broadcast_interval: Duration::from_secs_f32(1.0 / broadcast_hz),
+            last_flight_broadcast: Instant::now(),
+            sent_poses: HashMap::new(),
+            proximity: SpatialIndex::new(COLLISION_RADIUS),
+        }
+    }
+
+    /// Seconds of game time a tick covers
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    /// Advance `state` by one tick, telling `clients` what happened to them; returns whether
+    /// the state changed enough to send everyone a full update
+    pub fn tick(&mut self, state: &mut GameState, clients: &Clients) -> bool {
+        let mut state_changed = false;
+        state.tick += 1;
+        // Everything the tick has to tell a client goes out together at the end
+        let mut outbox = TickOutbox::default();
+
+        // --- Time Progression ---
+        // Clients keep their clock from the pulse; time alone doesn't need a full update
+        let day = state.day;
+        if let Some(pulse) = advance_clock(state, self.dt) {
+            outbox.broadcast(clients, None, &pulse);
+        }
+
+        // --- Injuries ---
+        // Wounds heal a little with every new day
+        if state.day != day {
+            for (id, character) in active_players(&mut state.players) {
+                if character.injuries.is_empty() {
+                    continue;
+                }
+                if let Some(narrative) = heal_injuries(character) {
+                    outbox.send(*id, &ServerMessage::NarrativeUpdate(narrative));
                 }
+                outbox.send(*id, &stat_update(character));
             }
         }
-        Err(e) => {
-            warn!("Failed to serialize broadcast message {:?}: {}", message, e);
+        // TODO: state.world_state.current_date = calculate_new_date(state.day);
+
+        // --- Disconnected Players ---
+        // Characters whose players didn't come back in time leave for good
+        let purged = purge_disconnected(
+            state,
+            clients,
+            self.disconnect_grace,
+            self.restore_characters,
+        );
+        if !purged.is_empty() {
+            state_changed = true;
         }
-    }
-}
 
-// Helper to broadcast the entire game state
-fn broadcast_state_update(clients: &Clients, game_state: &GameState) {
-    let update_msg = ServerMessage::GameStateUpdate(game_state.clone());
-    if let Ok(serialized_msg) = serde_json::to_string(&update_msg) {
-        let clients_map = clients.lock().unwrap();
-        for (id, sender) in clients_map.iter() {
-            if sender
-                .send(TungsteniteMessage::Text(serialized_msg.clone()))
-                .is_err()
+        // --- Random World Events ---
+        // TODO: Implement random events based on python code (e.g., ration changes, enemy changes, patrols)
+        // Example:
+        // if state.rng.gen_bool(0.01) { // 1% chance per tick
+        //    state.world_state.chocolate_ration = state.world_state.chocolate_ration.saturating_sub(1);
+        //    let narrative = ServerMessage::NarrativeUpdate("The chocolate ration has been reduced!".to_string());
+        //    broadcast_message(clients, &Uuid::nil(), &narrative); // Broadcast to all
+        // }
+
+        // --- NPC Movement/State Changes ---
+        // TODO: Implement NPC logic (e.g., moving between locations)
+
+        // --- Player Stat Decay/Changes ---
+        // TODO: Implement passive changes (e.g., slight loyalty decrease over time?)
+
+        // --- Travel ---
+        // Players on a journey advance one stop per tick
+        let mut journeys_ended = Vec::new();
+        for (id, character) in active_players(&mut state.players) {
+            if character.travel_route.is_empty() {
+                continue;
+            }
+            let next = character.travel_route.remove(0);
+            let connected = state
+                .world_state
+                .locations
+                .get(&character.location)
+                .is_some_and(|location| location.connections.contains(&next));
+            let narrative = if connected {
+                arrive_at(character, next.clone());
+                let mut narrative = if character.travel_route.is_empty() {
+                    journeys_ended.push((*id, next.clone()));
+                    format!("You arrive at {}, your destination.", next)
+                } else {
+                    format!("You pass through {}.", next)
+                };
+                if let Some(event) = roll_travel_risk(character, &state.world_state, &mut state.rng)
+                {
+                    narrative.push(' ');
+                    narrative.push_str(&event);
+                    outbox.send(*id, &stat_update(character));
+                }
+                narrative
+            } else {
+                character.travel_route.clear();
+                format!("The way to {} is blocked. You abandon your journey.", next)
+            };
+            outbox.send(*id, &ServerMessage::NarrativeUpdate(narrative));
+            state_changed = true;
+        }
+        // Travellers take in their destination once everyone has moved this tick
+        for (id, destination) in journeys_ended {
+            if let Some(scene) = arrival_narrative(state, id, &destination) {
+                outbox.send(id, &ServerMessage::NarrativeUpdate(scene));
+            }
+        }
+
+        // --- Cats ---
+        for (id, character) in active_players(&mut state.players) {
+            if let Some(narrative) =
+                tick_cat(character, &state.world_state, self.dt, &mut state.rng)
             {
-                warn!(
-                    "Failed to broadcast state update to client {} (already disconnected?)",
-                    id
-                );
+                outbox.send(*id, &ServerMessage::NarrativeUpdate(narrative));
+                state_changed = true;
             }
         }
-    } else {
-        warn!("Failed to serialize game state for broadcast update");
-    }
-}
 
-// Main game loop - Now focused on RPG state updates, time progression, events
-async fn game_loop(clients: Clients, game_state: SharedGameState) {
-    let tick_duration = Duration::from_secs_f32(FRAME_TIME);
-    info!(
-        "Game loop started with tick rate: {} Hz ({:?})",
-        1.0 / FRAME_TIME,
-        tick_duration
-    );
+        // --- O'Brien ---
+        let day = state.day;
+        for (id, character) in active_players(&mut state.players) {
+            if character.detention.is_some() {
+                continue;
+            }
+            if let Some(narrative) = advance_entrapment(character, day) {
+                info!("O'Brien betrayed player {} ({})", id, character.name);
+                outbox.send(*id, &ServerMessage::NarrativeUpdate(narrative));
+                outbox.send(*id, &stat_update(character));
+                state_changed = true;
+            }
+        }
 
-    loop {
-        let loop_start_time = Instant::now();
+        // --- Inner Party Promotions ---
+        let day = state.day;
+        for (id, character) in active_players(&mut state.players) {
+            if check_promotion(character, day) {
+                info!(
+                    "Player {} ({}) promoted to the Inner Party",
+                    id, character.name
+                );
+                let promotion_msg = ServerMessage::NarrativeUpdate(format!(
+                    "Your unwavering loyalty has been noticed. You are now an {}. The doors of the {} are open to you, and every eye is on you.",
+                    INNER_PARTY_OCCUPATION, MINISTRY_OF_LOVE
+                ));
+                outbox.send(*id, &promotion_msg);
+                state_changed = true;
+            }
+        }
 
-        // --- Game Logic Tick ---
-        let mut state_changed = false; // Track if state needs broadcasting
-        {
-            // Lock scope for game state modification
-            let mut state_guard = game_state.lock().unwrap();
-
-            // --- Time Progression ---
-            // TODO: Implement day/date progression logic
-            // state_guard.day += 1;
-            // state_guard.world_state.current_date = calculate_new_date(state_guard.day);
-
-            // --- Random World Events ---
-            // TODO: Implement random events based on python code (e.g., ration changes, enemy changes, patrols)
-            // Example:
-            // if rand::thread_rng().gen_bool(0.01) { // 1% chance per tick
-            //    state_guard.world_state.chocolate_ration = state_guard.world_state.chocolate_ration.saturating_sub(1);
-            //    let narrative = ServerMessage::NarrativeUpdate("The chocolate ration has been reduced!".to_string());
-            //    broadcast_message(&clients, &Uuid::nil(), &narrative); // Broadcast to all
-            // }
-
-            // --- NPC Movement/State Changes ---
-            // TODO: Implement NPC logic (e.g., moving between locations)
-
-            // --- Player Stat Decay/Changes ---
-            // TODO: Implement passive changes (e.g., slight loyalty decrease over time?)
-
-            // --- Check for Player End Conditions ---
-            let mut players_to_remove = Vec::new();
-            for (id, character) in state_guard.players.iter() {
-                if character.health == 0 {
-                    info!("Player {} ({}) has died.", id, character.name);
-                    players_to_remove.push(*id);
-                    let death_msg = ServerMessage::NarrativeUpdate(
-                        "Your health reached zero. You succumb to the harsh realities of Oceania."
-                            .to_string(),
-                    );
-                    send_message_to_client(&clients, *id, &death_msg);
-                } else if character.suspicion >= 100 {
-                    info!(
-                        "Player {} ({}) has been arrested by the Thought Police!",
-                        id, character.name
-                    );
-                    players_to_remove.push(*id);
-                    let arrest_msg = ServerMessage::NarrativeUpdate("Your suspicion level reached its peak. You are arrested by the Thought Police and taken to the Ministry of Love. Your journey ends here.".to_string());
-                    send_message_to_client(&clients, *id, &arrest_msg);
-                }
+        // --- Check for Player End Conditions ---
+        let mut players_to_remove = Vec::new();
+        let mut news = Vec::new();
+        let end = self.end_conditions;
+        for (id, character) in active_players(&mut state.players) {
+            if character.health <= end.death_health && !end.permadeath {
+                info!("Player {} ({}) collapsed.", id, character.name);
+                collapse(character, end.death_health);
+                let collapse_msg = ServerMessage::NarrativeUpdate(format!(
+                    "Everything goes dark. You come round in {}, aching, with no memory of how you got there.",
+                    COLLAPSE_LOCATION
+                ));
+                outbox.send(*id, &collapse_msg);
+                outbox.send(*id, &stat_update(character));
+                state_changed = true;
+            } else if character.health <= end.death_health {
+                info!("Player {} ({}) has died.", id, character.name);
+                players_to_remove.push(*id);
+                let death_msg = ServerMessage::notify(
+                    NotificationLevel::Danger,
+                    "Your health reached zero. You succumb to the harsh realities of Oceania.",
+                );
+                outbox.send(*id, &death_msg);
+            } else if matches!(
+                character.detention,
+                Some(ThoughtcrimeConsequence::Arrest { .. })
+            ) {
+                info!("Player {} ({}) has been broken.", id, character.name);
+                players_to_remove.push(*id);
+                let unperson_msg = ServerMessage::NarrativeUpdate("Your name vanishes from every record. You have become an unperson. Your journey ends here.".to_string());
+                outbox.send(*id, &unperson_msg);
+            } else if character.detention.is_some() {
+                // Under interrogation; nothing else can happen to them
+            } else if character.suspicion >= end.arrest_suspicion
+                && end.arrest == ArrestConsequence::Removal
+            {
+                info!(
+                    "Player {} ({}) has been arrested and vaporized!",
+                    id, character.name
+                );
+                players_to_remove.push(*id);
+                let vaporized_msg = ServerMessage::NarrativeUpdate("The Thought Police come for you in the night. There is no trial. Your name vanishes from every record. You have become an unperson.".to_string());
+                outbox.send(*id, &vaporized_msg);
+            } else if character.suspicion >= end.arrest_suspicion {
+                info!(
+                    "Player {} ({}) has been arrested by the Thought Police!",
+                    id, character.name
+                );
+                let arrest_msg = ServerMessage::notify(NotificationLevel::Danger, "Your suspicion level reached its peak. You are arrested by the Thought Police and taken to the Ministry of Love.");
+                outbox.send(*id, &arrest_msg);
+                let question = begin_interrogation(character);
+                outbox.send(*id, &question);
+                state_changed = true;
+            } else if character.rebellion_score >= RESISTANCE_VICTORY_SCORE
+                && character.suspicion < RESISTANCE_MAX_SUSPICION
+            {
+                info!(
+                    "Player {} ({}) has sparked a resistance cell!",
+                    id, character.name
+                );
+                players_to_remove.push(*id);
+                let victory_msg = ServerMessage::NarrativeUpdate("Quietly, without the telescreens noticing, you have gathered others who think as you do. You have sparked a resistance cell. The Party's grip is not as total as it seems. You have won.".to_string());
+                outbox.send(*id, &victory_msg);
+                let whisper = format!(
+                    "Whispers spread through Oceania: {} has sparked a resistance cell.",
+                    character.name
+                );
+                outbox.broadcast(
+                    clients,
+                    Some(id),
+                    &ServerMessage::NarrativeUpdate(whisper.clone()),
+                );
+                news.push(whisper);
             }
+        }
+        for whisper in news {
+            state.record_event(None, whisper);
+        }
 
-            // Remove players who met end conditions
-            let mut _player_left_during_tick = false;
-            for id_to_remove in players_to_remove {
-                if state_guard.players.remove(&id_to_remove).is_some() {
-                    let leave_msg = ServerMessage::PlayerLeft {
-                        player_id: id_to_remove,
-                    };
-                    broadcast_message(&clients, Some(&id_to_remove), &leave_msg);
-                    state_changed = true;
-                    _player_left_during_tick = true;
+        // Remove players who met end conditions
+        let mut _player_left_during_tick = false;
+        for id_to_remove in players_to_remove {
+            if state.players.remove(&id_to_remove).is_some() {
+                let leave_msg = ServerMessage::PlayerLeft {
+                    player_id: id_to_remove,
+                };
+                outbox.broadcast(clients, Some(&id_to_remove), &leave_msg);
+                state_changed = true;
+                _player_left_during_tick = true;
 
-                    if let Some(sender) = clients.lock().unwrap().get(&id_to_remove) {
-                        let _ = sender.send(TungsteniteMessage::Close(None));
+                // Their last news has to go out before the connection closes
+                outbox.flush_client(clients, id_to_remove);
+                let mut clients_map = lock_clients(clients);
+                if let Some(sender) = clients_map.get(&id_to_remove) {
+                    if queue_message(&id_to_remove, sender, TungsteniteMessage::Close(None)) {
                         info!("Sent close message to removed player {}", id_to_remove);
+                    } else {
+                        clients_map.remove(&id_to_remove);
                     }
                 }
             }
+        }
+
+        // --- Telescreens ---
+        let night = state.world_state.is_night();
+        for (id, character) in active_players(&mut state.players) {
+            let chance = telescreen_suspicion_chance(character, night, self.dt);
+            if chance > 0.0 && state.rng.gen::<f32>() < chance {
+                character.suspicion = character.suspicion.saturating_add(1).min(100);
+                outbox.send(*id, &stat_update(character));
+                state_changed = true;
+            }
+        }
+
+        // --- Two Minutes Hate ---
+        for id in advance_hate(state, self.dt) {
+            let absence_msg = ServerMessage::notify(
+                NotificationLevel::Warning,
+                "The Hate is over. Someone noticed that you were not there to scream with the rest.",
+            );
+            outbox.send(id, &absence_msg);
+            if let Some(character) = state.players.get(&id) {
+                outbox.send(id, &stat_update(character));
+            }
+            state_changed = true;
+        }
+
+        // --- Idle Players ---
+        // Free the slots of players who walked away from the keyboard
+        let mut idle_players = Vec::new();
+        for (id, character) in active_players(&mut state.players) {
+            match idle_action(character, self.idle_timeout) {
+                Some(IdleAction::Warn) => {
+                    character.idle_warned = true;
+                    let warning = ServerMessage::notify(
+                        NotificationLevel::Warning,
+                        format!(
+                            "The telescreen notes your stillness. Do something within {} seconds or you will be disconnected.",
+                            IDLE_WARNING_SECS.min(self.idle_timeout.as_secs())
+                        ),
+                    );
+                    outbox.send(*id, &warning);
+                }
+                Some(IdleAction::Disconnect) => idle_players.push(*id),
+                None => {}
+            }
+        }
+        for id in idle_players {
+            info!("Disconnecting idle player {}", id);
+            if let Some(character) = state.players.remove(&id) {
+                retire_character(state, clients, id, character, self.restore_characters);
+            }
+            let close = TungsteniteMessage::Close(Some(CloseFrame {
+                code: CloseCode::Away,
+                reason: "Disconnected for inactivity".into(),
+            }));
+            outbox.flush_client(clients, id);
+            let mut clients_map = lock_clients(clients);
+            if let Some(sender) = clients_map.remove(&id) {
+                queue_message(&id, &sender, close);
+            }
+            state_changed = true;
+        }
+
+        // --- Lying Low ---
+        // The Party slowly forgets those who keep their heads down
+        for (id, character) in active_players(&mut state.players) {
+            let chance = suspicion_decay_chance(character, &state.world_state, state.day, self.dt);
+            if chance > 0.0 && state.rng.gen::<f32>() < chance {
+                character.suspicion -= 1;
+                outbox.send(*id, &stat_update(character));
+                state_changed = true;
+            }
+        }
+
+        // --- Morale ---
+        // Morale slowly settles wherever the character's circumstances leave it
+        for (id, character) in active_players(&mut state.players) {
+            let settled = settled_morale(character);
+            if character.morale == settled || state.rng.gen::<f32>() >= MORALE_DRIFT_RATE * self.dt
+            {
+                continue;
+            }
+            if character.morale < settled {
+                character.morale += 1;
+            } else {
+                character.morale -= 1;
+            }
+            outbox.send(*id, &stat_update(character));
+            state_changed = true;
+        }
+
+        // --- Home Searches ---
+        for (id, character) in active_players(&mut state.players) {
+            let chance = diary_discovery_chance(character, self.dt);
+            if chance > 0.0 && state.rng.gen::<f32>() < chance {
+                info!(
+                    "Thought Police found the diary of player {} ({})",
+                    id, character.name
+                );
+                character.journal_entries.clear();
+                character.journal_hiding_place = None;
+                character.suspicion = character
+                    .suspicion
+                    .saturating_add(DIARY_FOUND_SUSPICION)
+                    .min(100);
+                let search_msg = ServerMessage::NarrativeUpdate(
+                    "Boots on the stairs. The Thought Police tear your room apart and hold up your diary. Every word you wrote is now evidence.".to_string(),
+                );
+                outbox.send(*id, &search_msg);
+                outbox.send(*id, &stat_update(character));
+                state_changed = true;
+            }
+        }
+
+        // --- 3D Physics Update ---
+        // Let the prevailing wind drift slowly, then fly everyone through it
+        drift_wind(
+            &mut state.world_state.wind,
+            self.wind_strength,
+            self.dt,
+            &mut state.rng,
+        );
+        let wind = state.world_state.wind;
+
+        for (id, character) in active_players(&mut state.players) {
+            let gust = turbulence(self.wind_strength, &mut state.rng);
+            let previous = character.position;
+            let stats = (character.health, character.suspicion);
+            let step = step_character_physics(character, wind + gust, self.dt, &self.physics);
+
+            if let Some(landing) = step.hard_landing {
+                info!(
+                    "Player {} crashed at {:.1} m/s, taking {} damage",
+                    id, landing.impact_speed, landing.damage
+                );
+                let crash_msg = ServerMessage::notify(
+                    NotificationLevel::Danger,
+                    format!(
+                        "You slam into the ground at {:.1} m/s. Metal screams and you lose {} health.",
+                        landing.impact_speed, landing.damage
+                    ),
+                );
+                outbox.send(*id, &crash_msg);
+            }
+
+            // Warn only on entering the stall
+            if step.stall_began {
+                info!(
+                    "Player {} stalled (airspeed {:.1}, AoA {:.2} rad)",
+                    id, step.aero.airspeed, step.aero.angle_of_attack
+                );
+                let stall_msg = ServerMessage::StallWarning {
+                    airspeed: step.aero.airspeed,
+                    angle_of_attack: step.aero.angle_of_attack,
+                };
+                outbox.send(*id, &stall_msg);
+            }
 
-            // --- 3D Physics Update ---
-            let gravity = Vector3::new(0.0, -9.81, 0.0);
-            let drag_coefficient = 0.5; // Simple linear drag
+            for msg in apply_flight_to_map(character, &state.world_state, &previous, &step) {
+                outbox.send(*id, &msg);
+            }
+            // Crashing and straying into restricted airspace both cost the pilot
+            if (character.health, character.suspicion) != stats {
+                outbox.send(*id, &stat_update(character));
+            }
+        }
 
-            for (_id, character) in state_guard.players.iter_mut() {
-                // 1. Calculate Forces
-                // Thrust (forward direction based on orientation)
-                // Get the underlying vector from the unit quaternion's rotation
-                // Dereference the result of the multiplication to get Vector3
-                let forward_vector: Vector3<f32> = *(character.orientation * Vector3::z_axis()); // Assuming Z is forward
-                let thrust_force: Vector3<f32> = forward_vector * character.throttle * 20.0; // Arbitrary thrust scaling
+        // Flight is sent at the broadcast rate, and only for aircraft that moved
+        if self.last_flight_broadcast.elapsed() >= self.broadcast_interval {
+            self.last_flight_broadcast = Instant::now();
+            self.sent_poses
+                .retain(|id, _| state.players.contains_key(id));
+            for (id, character) in &state.players {
+                if self
+                    .sent_poses
+                    .get(id)
+                    .is_some_and(|sent| !sent.differs_from(character))
+                {
+                    continue;
+                }
+                self.sent_poses.insert(*id, SentPose::of(character));
+                // Only the moving aircraft goes out; the owner also reconciles its prediction
+                // with what the server applied
+                let state_msg = ServerMessage::PlayerState {
+                    player_id: *id,
+                    position: character.position,
+                    orientation: character.orientation,
+                    last_input_seq: character.last_input_seq,
+                };
+                outbox.broadcast(clients, None, &state_msg);
+            }
+        }
+        // --- End 3D Physics Update ---
 
-                // Drag (opposite to velocity)
-                let drag_force: Vector3<f32> = -character.velocity * drag_coefficient;
+        // --- Aircraft Collisions ---
+        self.proximity.clear();
+        for (id, character) in active_players(&mut state.players) {
+            self.proximity.insert(*id, character.position);
+        }
+        for collision in detect_collisions(&state.players, &self.proximity) {
+            if let Some((name_a, name_b)) = resolve_collision(&mut state.players, &collision) {
+                info!(
+                    "Collision between {} ({}) and {} ({})",
+                    collision.a, name_a, collision.b, name_b
+                );
+                let msg_a = ServerMessage::NarrativeUpdate(format!(
+                    "You collide with {}'s aircraft! The impact shakes you to the bone.",
+                    name_b
+                ));
+                let msg_b = ServerMessage::NarrativeUpdate(format!(
+                    "You collide with {}'s aircraft! The impact shakes you to the bone.",
+                    name_a
+                ));
+                outbox.send(collision.a, &msg_a);
+                outbox.send(collision.b, &msg_b);
+                for id in [collision.a, collision.b] {
+                    if let Some(character) = state.players.get(&id) {
+                        outbox.send(id, &stat_update(character));
+                    }
+                }
+                state_changed = true;
+            }
+        }
 
-                // Net force (assuming mass = 1 for simplicity)
-                let net_force: Vector3<f32> = thrust_force + gravity + drag_force;
+        outbox.flush(clients);
+        state_changed
+    }
+}
 
-                // 2. Update Velocity
-                let acceleration: Vector3<f32> = net_force; // Since mass = 1
-                character.velocity += acceleration * FRAME_TIME;
+// Main game loop - Now focused on RPG state updates, time progression, events
+async fn game_loop(
+    clients: Clients,
+    game_state: SharedGameState,
+    metrics: Arc<Metrics>,
+    config: ServerConfig,
+) {
+    let mut simulation = Simulation::new(&config);
+    let tick_duration = Duration::from_secs_f32(simulation.dt());
+    let mut clock = TickClock::new(tick_duration, config.max_catch_up_ticks);
+    info!("Game loop started, one tick every {:?}", tick_duration);
+    let mut last_loop_start = Instant::now();
+    let autosave = config
+        .save_file
+        .clone()
+        .zip(config.autosave_interval())
+        .map(|(path, interval)| Autosave { path, interval });
+    let mut last_autosave = Instant::now();
+    let mut last_saved_fingerprint = save_fingerprint(&lock_state(&game_state));
+    // Clients keep the world from the last update that carried it
+    let mut last_world = None;
 
-                // 3. Update Position
-                character.position += character.velocity * FRAME_TIME;
+    loop {
+        let loop_start_time = Instant::now();
+        let ticks = clock.advance(loop_start_time - last_loop_start);
+        last_loop_start = loop_start_time;
 
-                // Prevent falling through a hypothetical ground plane at y=0
-                if character.position.y < 0.0 {
-                    character.position.y = 0.0;
-                    // Zero out vertical velocity on collision
-                    if character.velocity.y < 0.0 {
-                        character.velocity.y = 0.0;
+        // --- Autosave ---
+        if let Some(autosave) = &autosave {
+            if last_autosave.elapsed() >= autosave.interval {
+                last_autosave = Instant::now();
+                let snapshot = lock_state(&game_state).clone();
+                let fingerprint = save_fingerprint(&snapshot);
+                if fingerprint == last_saved_fingerprint {
+                    debug!("Autosave skipped, nothing changed since the last save");
+                } else {
+                    match snapshot.save_to_path(&autosave.path) {
+                        Ok(()) => {
+                            info!("Autosaved world to {}", autosave.path.display());
+                            last_saved_fingerprint = fingerprint;
+                        }
+                        Err(e) => warn!("Autosave to {} failed: {}", autosave.path.display(), e),
                     }
-                    // Optional: Add some friction on ground contact
-                    character.velocity.x *= 0.9;
-                    character.velocity.z *= 0.9;
                 }
+            }
+        }
 
-                state_changed = true; // Assume physics always changes state for now
+        // --- Game Logic Tick ---
+        {
+            // Lock scope for game state modification
+            let mut state_guard = lock_state(&game_state);
+            let mut state_changed = false;
+            for _ in 0..ticks {
+                state_changed |= simulation.tick(&mut state_guard, &clients);
             }
-            // --- End 3D Physics Update ---
 
             if state_changed {
-                broadcast_state_update(&clients, &state_guard);
+                let mut update = state_guard.public_view();
+                let world = world_fingerprint(&state_guard.world_state);
+                if world.is_some() && world == last_world {
+                    update.world_state = None;
+                } else {
+                    last_world = world;
+                }
+                broadcast_state_update(&clients, update);
             }
         } // MutexGuard for game_state dropped here
 
         // Maintain target tick rate
         let elapsed = loop_start_time.elapsed();
+        metrics.record_tick(elapsed);
         if elapsed < tick_duration {
-            tokio::time::sleep(tick_duration - elapsed).await;
+            tokio::time::sleep(clock.until_next_tick().saturating_sub(elapsed)).await;
         } else {
             warn!("Game loop tick duration exceeded target: {:?}", elapsed);
         }
     }
 }
 
-// Public function to run the server
-pub async fn run_server(addr: SocketAddr) {
-    env_logger::builder().format_timestamp_micros().init(); // Ensure logger is initialized
+/// Fixed-timestep accumulator deciding how many ticks the game loop runs to keep up with real
+/// time, so every tick covers the same `dt` however late the loop wakes
+#[derive(Debug, Clone)]
+pub struct TickClock {
+    tick: Duration,
+    max_catch_up: u32,
+    accumulated: Duration,
+}
+
+impl TickClock {
+    /// A clock ticking every `tick`, running at most `max_catch_up` extra ticks to catch up;
+    /// with 0 it runs exactly one tick per loop, however long the loop took
+    pub fn new(tick: Duration, max_catch_up: u32) -> Self {
+        TickClock {
+            tick,
+            max_catch_up,
+            accumulated: Duration::ZERO,
+        }
+    }
+
+    /// Account for `elapsed` real time since the last call; returns how many ticks to run now
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        if self.max_catch_up == 0 {
+            return 1;
+        }
+        self.accumulated += elapsed;
+        let due = (self.accumulated.as_nanos() / self.tick.as_nanos()) as u32;
+        let ticks = due.min(1 + self.max_catch_up);
+        if due > ticks {
+            warn!(
+                "Game loop {} ticks behind, dropping {:?} of game time",
+                due,
+                self.tick * (due - ticks)
+            );
+        } else if ticks > 1 {
+            info!("Game loop catching up with {} extra ticks", ticks - 1);
+        }
+        self.accumulated -= self.tick * due;
+        ticks
+    }
+
+    /// Real time left until the next tick is due
+    pub fn until_next_tick(&self) -> Duration {
+        self.tick.saturating_sub(self.accumulated)
+    }
+}
+
+// Keep flight updates between once a second and once a tick
+fn clamp_broadcast_hz(broadcast_hz: f32, tick_hz: f32) -> f32 {
+    if !broadcast_hz.is_finite() || broadcast_hz <= 0.0 {
+        warn!(
+            "Invalid broadcast rate {}, sending flight every tick",
+            broadcast_hz
+        );
+        return tick_hz;
+    }
+    broadcast_hz.clamp(MIN_TICK_HZ, tick_hz)
+}
+
+// Fresh game with the configured overrides applied to the built-in world
+fn new_game_state(world: &WorldConfig) -> GameState {
+    let mut state = GameState::new();
+    match world.starting_world() {
+        Ok(world_state) => state.world_state = world_state,
+        Err(e) => {
+            warn!(
+                "Failed to load the scenario, using the built-in world: {}",
+                e
+            );
+            world.apply(&mut state.world_state);
+        }
+    }
+    state
+}
+
+// Load the saved world if there is one, falling back to a fresh world on any problem
+fn load_or_new_game_state(save_path: Option<&Path>, world: &WorldConfig) -> GameState {
+    let path = match save_path {
+        Some(path) if path.exists() => path,
+        Some(path) => {
+            info!("No save file at {}, starting a new world", path.display());
+            return new_game_state(world);
+        }
+        None => return new_game_state(world),
+    };
+    match GameState::load_from_path(path) {
+        Ok(mut state) => {
+            // Characters belong to connections from the previous run, which are gone;
+            // archive them so their owners can reclaim them by name
+            if !state.players.is_empty() {
+                info!(
+                    "Archiving {} characters from the previous session",
+                    state.players.len()
+                );
+                for (_, character) in state.players.drain() {
+                    state
+                        .saved_characters
+                        .insert(character.name.clone(), character);
+                }
+            }
+            info!("Loaded world (day {}) from {}", state.day, path.display());
+            state
+        }
+        Err(e) => {
+            warn!(
+                "Failed to load save file {}: {}. Starting a new world.",
+                path.display(),
+                e
+            );
+            new_game_state(world)
+        }
+    }
+}
+
+/// Run the WebSocket game server described by `config` until Ctrl-C is received.
+///
+/// `config.wind` is the base wind speed in m/s; zero disables wind and turbulence.
+/// When `config.save_file` is given the world is loaded from it at startup (if
+/// present) and written back to it on shutdown, and additionally every
+/// `autosave_minutes` while running. With `restore_characters` a disconnecting
+/// player's character is archived by name and handed back when someone creates
+/// a character with the same name and occupation.
+pub async fn run_server(config: ServerConfig) {
+    let addr = config.addr();
     info!("Starting 1984 RPG Server (flight-rs base) on {}...", addr);
 
     // Initialize shared state
     let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
-    let game_state: SharedGameState = Arc::new(Mutex::new(GameState::new())); // Initialize RPG GameState
+    let mut initial_state = load_or_new_game_state(config.save_file.as_deref(), &config.world);
+    if let Some(seed) = config.seed {
+        info!("Seeding random events with {}", seed);
+        initial_state.rng = WorldRng::from_seed(seed);
+    }
+    if let Some(path) = &config.record_file {
+        match replay::Recorder::create(path) {
+            Ok(recorder) => {
+                info!("Recording client messages to {}", path.display());
+                initial_state.recorder = Some(recorder);
+            }
+            Err(e) => warn!("Failed to start recording to {}: {}", path.display(), e),
+        }
+    }
+    let game_state: SharedGameState = Arc::new(Mutex::new(initial_state)); // Initialize RPG GameState
 
     // Start the game loop in a separate task
     let game_loop_clients = clients.clone();
     let game_loop_state = game_state.clone();
+    let metrics = Arc::new(Metrics::new());
+    let game_loop_metrics = metrics.clone();
+    let game_loop_config = config.clone();
     tokio::spawn(async move {
-        game_loop(game_loop_clients, game_loop_state).await;
+        game_loop(
+            game_loop_clients,
+            game_loop_state,
+            game_loop_metrics,
+            game_loop_config,
+        )
+        .await;
     });
 
     // --- Define Warp Routes ---
+    let save_path = config.save_file.clone();
+    let state_admin_token = config.admin_token.clone();
+    let heat_map_admin_token = config.admin_token.clone();
+    let web_dir = config.web_dir.clone();
+    if !web_dir.is_dir() {
+        warn!(
+            "Web directory {} not found, serving only the /ws endpoint",
+            web_dir.display()
+        );
+    }
+    let connection_config = Arc::new(config);
+    // Clients opt in to deflated frames with `/ws?compress=deflate`
     let ws_route = warp::path("ws")
         .and(warp::ws())
+        .and(warp::addr::remote())
+        .and(warp::query::<HashMap<String, String>>())
+        .and(with_clients(clients.clone()))
+        .and(with_game_state(game_state.clone()))
+        .and(with_metrics(metrics.clone()))
+        .map(
+            move |ws: Ws,
+                  peer: Option<SocketAddr>,
+                  query: HashMap<String, String>,
+                  clients_map,
+                  game_state_map,
+                  metrics_map| {
+                let config = connection_config.clone();
+                // Turn away addresses outside the allowlist before upgrading
+                if !access::is_allowed(&config.allow_cidr, peer.map(|addr| addr.ip()).as_ref()) {
+                    warn!(
+                        "Refusing WebSocket from {} outside the allowed ranges",
+                        peer.map_or_else(|| "unknown address".to_string(), |addr| addr.to_string())
+                    );
+                    return warp::reply::with_status(
+                        warp::reply::json(&ServerMessage::error(
+                            ErrorCode::NotAllowed,
+                            "Connections from your address are not allowed",
+                        )),
+                        warp::http::StatusCode::FORBIDDEN,
+                    )
+                    .into_response();
+                }
+                let compress =
+                    config.compress && query.get("compress").map(String::as_str) == Some("deflate");
+                ws.on_upgrade(move |socket| {
+                    handle_connection(
+                        socket,
+                        peer,
+                        clients_map,
+                        game_state_map,
+                        config,
+                        metrics_map,
+                        compress,
+                    )
+                })
+                .into_response()
+            },
+        );
+
+    // Liveness probe for load balancers
+    let health_route = warp::path("health")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_clients(clients.clone()))
+        .and(with_game_state(game_state.clone()))
+        .and(with_metrics(metrics.clone()))
+        .map(
+            |clients: Clients, game_state: SharedGameState, metrics: Arc<Metrics>| {
+                let day = lock_state(&game_state).day;
+                warp::reply::json(&serde_json::json!({
+                    "status": "ok",
+                    "uptime_seconds": metrics.uptime().as_secs(),
+                    "players": lock_clients(&clients).len(),
+                    "day": day,
+                }))
+            },
+        );
+
+    // Prometheus scrape target
+    let metrics_route = warp::path("metrics")
+        .and(warp::path::end())
+        .and(warp::get())
         .and(with_clients(clients.clone()))
+        .and(with_metrics(metrics))
+        .map(|clients: Clients, metrics: Arc<Metrics>| {
+            let connected = lock_clients(&clients).len();
+            warp::reply::with_header(
+                metrics.render_prometheus(connected),
+                "content-type",
+                "text/plain; version=0.0.4",
+            )
+        });
+
+    // Read-only dump of the whole world for operators. The payload grows with
+    // every player and location, so this is for debugging, not polling.
+    let state_route = warp::path("state")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::header::optional::<String>("x-admin-token"))
+        .and(with_game_state(game_state.clone()))
+        .map(move |token: Option<String>, game_state: SharedGameState| {
+            if !admin_token_matches(&state_admin_token, &token) {
+                return invalid_admin_token();
+            }
+            // Hold the lock only long enough to clone
+            let snapshot = lock_state(&game_state).clone();
+            match serde_json::to_string_pretty(&snapshot) {
+                Ok(json) => warp::reply::with_header(json, "content-type", "application/json")
+                    .into_response(),
+                Err(e) => {
+                    warn!("Failed to serialize game state for /state: {}", e);
+                    warp::http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            }
+        });
+
+    // Public standings as JSON, the same entries as the Leaderboard message
+    let leaderboard_route = warp::path("leaderboard")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(with_game_state(game_state.clone()))
+        .map(|game_state: SharedGameState| {
+            let entries = leaderboard(&lock_state(&game_state));
+            warp::reply::json(&entries)
+        });
+
+    // Where suspicion and thoughtcrime are concentrated, for moderators; behind the admin
+    // token so players can't use it to hunt rebels, and closed outright while none is set
+    let heat_map_route = warp::path("heatmap")
+        .and(warp::path::end())
+        .and(warp::get())
+        .and(warp::header::optional::<String>("x-admin-token"))
         .and(with_game_state(game_state.clone()))
-        .map(|ws: Ws, clients_map, game_state_map| {
-            ws.on_upgrade(move |socket| handle_connection(socket, clients_map, game_state_map))
+        .map(move |token: Option<String>, game_state: SharedGameState| {
+            if token.is_none() || token != heat_map_admin_token {
+                return invalid_admin_token();
+            }
+            let heat_map = surveillance_heat_map(&lock_state(&game_state));
+            warp::reply::json(&heat_map).into_response()
         });
 
     let index = warp::get()
         .and(warp::path::end())
-        .and(warp::fs::file("web/index.html"));
+        .and(warp::fs::file(web_dir.join("index.html")));
 
     // Combine routes, using warp::fs::dir directly
-    let routes = ws_route.or(index).or(warp::fs::dir("web")); // Corrected route definition
+    let routes = ws_route
+        .or(health_route)
+        .or(metrics_route)
+        .or(state_route)
+        .or(leaderboard_route)
+        .or(heat_map_route)
+        .or(index)
+        .or(warp::fs::dir(web_dir));
 
     // Start the server
     info!("Listening for connections on http://{}", addr);
-    warp::serve(routes).run(addr).await;
+    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(addr, async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for shutdown signal: {}", e);
+            std::future::pending::<()>().await;
+        }
+        info!("Shutdown signal received");
+    });
+    server.await;
+
+    // Persist the world on the way out
+    if let Some(path) = save_path {
+        let state = lock_state(&game_state).clone();
+        match state.save_to_path(&path) {
+            Ok(()) => info!("Saved world (day {}) to {}", state.day, path.display()),
+            Err(e) => warn!("Failed to save world to {}: {}", path.display(), e),
+        }
+    }
 }
 
 // Remove the misplaced module declarations from the end if they exist
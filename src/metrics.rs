@@ -0,0 +1,127 @@
+//! Server counters exposed on the `/metrics` endpoint in Prometheus text format.
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// Upper bounds in seconds of the tick duration histogram buckets
+const TICK_BUCKETS: [f64; 8] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25];
+
+/// Counters shared between the connection handlers and the game loop
+#[derive(Debug)]
+pub struct Metrics {
+    started: Instant,
+    messages_processed: AtomicU64,
+    disconnects: AtomicU64,
+    tick_buckets: [AtomicU64; TICK_BUCKETS.len()],
+    tick_count: AtomicU64,
+    tick_sum_micros: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Start counting from zero now
+    pub fn new() -> Self {
+        Metrics {
+            started: Instant::now(),
+            messages_processed: AtomicU64::new(0),
+            disconnects: AtomicU64::new(0),
+            tick_buckets: Default::default(),
+            tick_count: AtomicU64::new(0),
+            tick_sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    /// Time since the server started
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Count a client message that was handled
+    pub fn record_message(&self) {
+        self.messages_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a client that went away
+    pub fn record_disconnect(&self) {
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Add one game loop tick to the duration histogram
+    pub fn record_tick(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(bucket) = TICK_BUCKETS.iter().position(|&le| seconds <= le) {
+            self.tick_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.tick_count.fetch_add(1, Ordering::Relaxed);
+        self.tick_sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render_prometheus(&self, connected_clients: usize) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP flight_sim_connected_clients Open WebSocket connections"
+        );
+        let _ = writeln!(out, "# TYPE flight_sim_connected_clients gauge");
+        let _ = writeln!(out, "flight_sim_connected_clients {}", connected_clients);
+
+        let _ = writeln!(
+            out,
+            "# HELP flight_sim_messages_processed_total Client messages handled"
+        );
+        let _ = writeln!(out, "# TYPE flight_sim_messages_processed_total counter");
+        let _ = writeln!(
+            out,
+            "flight_sim_messages_processed_total {}",
+            self.messages_processed.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP flight_sim_disconnects_total Clients that disconnected"
+        );
+        let _ = writeln!(out, "# TYPE flight_sim_disconnects_total counter");
+        let _ = writeln!(
+            out,
+            "flight_sim_disconnects_total {}",
+            self.disconnects.load(Ordering::Relaxed)
+        );
+
+        let _ = writeln!(
+            out,
+            "# HELP flight_sim_tick_duration_seconds Game loop tick processing time"
+        );
+        let _ = writeln!(out, "# TYPE flight_sim_tick_duration_seconds histogram");
+        // Prometheus buckets are cumulative
+        let mut cumulative = 0;
+        for (le, bucket) in TICK_BUCKETS.iter().zip(&self.tick_buckets) {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "flight_sim_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                le, cumulative
+            );
+        }
+        let count = self.tick_count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            out,
+            "flight_sim_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            out,
+            "flight_sim_tick_duration_seconds_sum {}",
+            self.tick_sum_micros.load(Ordering::Relaxed) as f64 / 1.0e6
+        );
+        let _ = writeln!(out, "flight_sim_tick_duration_seconds_count {}", count);
+        out
+    }
+}
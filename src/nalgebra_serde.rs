@@ -0,0 +1,109 @@
+//! Serde representations of the nalgebra types sent to clients and written to saves.
+//!
+//! Points and vectors are objects `{"x": .., "y": .., "z": ..}`; unit quaternions are objects
+//! `{"w": .., "x": .., "y": .., "z": ..}` where `w` is the scalar part and `x`, `y`, `z` the
+//! vector part. Fields are attached with `#[serde(with = "crate::nalgebra_serde::point3")]`
+//! and its siblings.
+//!
+//! Fields are declared in nalgebra's own coordinate order (`w` last), so saves written with
+//! nalgebra's default representation still load.
+
+use nalgebra::{Quaternion, UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+struct Xyz {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+impl From<Vector3<f32>> for Xyz {
+    fn from(v: Vector3<f32>) -> Self {
+        Xyz {
+            x: v.x,
+            y: v.y,
+            z: v.z,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Wxyz {
+    x: f32,
+    y: f32,
+    z: f32,
+    w: f32,
+}
+
+/// `Point3<f32>` as `{x, y, z}`
+pub mod point3 {
+    use super::Xyz;
+    use nalgebra::Point3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Write the point as an `{x, y, z}` object
+    pub fn serialize<S: Serializer>(point: &Point3<f32>, serializer: S) -> Result<S::Ok, S::Error> {
+        Xyz::from(point.coords).serialize(serializer)
+    }
+
+    /// Read a point written by [`serialize`]
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Point3<f32>, D::Error> {
+        let Xyz { x, y, z } = Xyz::deserialize(deserializer)?;
+        Ok(Point3::new(x, y, z))
+    }
+}
+
+/// `Vector3<f32>` as `{x, y, z}`
+pub mod vector3 {
+    use super::Xyz;
+    use nalgebra::Vector3;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Write the vector as an `{x, y, z}` object
+    pub fn serialize<S: Serializer>(
+        vector: &Vector3<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Xyz::from(*vector).serialize(serializer)
+    }
+
+    /// Read a vector written by [`serialize`]
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vector3<f32>, D::Error> {
+        let Xyz { x, y, z } = Xyz::deserialize(deserializer)?;
+        Ok(Vector3::new(x, y, z))
+    }
+}
+
+/// `UnitQuaternion<f32>` as `{w, x, y, z}`
+pub mod unit_quaternion {
+    use super::{Quaternion, UnitQuaternion, Wxyz};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Write the rotation as a `{w, x, y, z}` object
+    pub fn serialize<S: Serializer>(
+        rotation: &UnitQuaternion<f32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Wxyz {
+            x: rotation.i,
+            y: rotation.j,
+            z: rotation.k,
+            w: rotation.w,
+        }
+        .serialize(serializer)
+    }
+
+    /// Read a rotation written by [`serialize`], taking it to be normalized already so values
+    /// round-trip exactly
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<UnitQuaternion<f32>, D::Error> {
+        let Wxyz { x, y, z, w } = Wxyz::deserialize(deserializer)?;
+        Ok(UnitQuaternion::new_unchecked(Quaternion::new(w, x, y, z)))
+    }
+}
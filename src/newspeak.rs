@@ -0,0 +1,51 @@
+//! Newspeak filter that flags oldspeak vocabulary in player-written text.
+
+use serde::{Deserialize, Serialize};
+
+/// Settings for the optional Newspeak filter
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NewspeakConfig {
+    /// Whether player text is checked at all
+    pub enabled: bool,
+    /// Oldspeak words, matched case-insensitively as whole words
+    pub banned_words: Vec<String>,
+    /// Extra thoughtcrime per flagged word
+    pub thoughtcrime_per_word: u8,
+}
+
+impl Default for NewspeakConfig {
+    fn default() -> Self {
+        NewspeakConfig {
+            enabled: false,
+            banned_words: [
+                "freedom",
+                "liberty",
+                "equality",
+                "justice",
+                "democracy",
+                "honour",
+                "science",
+                "bad",
+                "excellent",
+                "splendid",
+            ]
+            .iter()
+            .map(|word| word.to_string())
+            .collect(),
+            thoughtcrime_per_word: 2,
+        }
+    }
+}
+
+/// Every oldspeak word in `text`, lowercased, in order of appearance (repeats included)
+pub fn flag_oldspeak(text: &str, banned_words: &[String]) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| {
+            banned_words
+                .iter()
+                .any(|banned| banned.eq_ignore_ascii_case(word))
+        })
+        .collect()
+}
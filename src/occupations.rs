@@ -0,0 +1,77 @@
+//! Where each occupation starts and how it adjusts a new character.
+
+use crate::items::apply_delta;
+use crate::rpg_structs::Character;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Starting conditions for characters of one occupation
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OccupationProfile {
+    /// Location the character starts at
+    pub starting_location: String,
+    /// Change to the starting loyalty
+    #[serde(default)]
+    pub loyalty: i8,
+    /// Change to the starting suspicion
+    #[serde(default)]
+    pub suspicion: i8,
+    /// Change to the starting thoughtcrime
+    #[serde(default)]
+    pub thoughtcrime: i8,
+    /// Items the character starts with
+    #[serde(default)]
+    pub inventory: Vec<String>,
+}
+
+impl OccupationProfile {
+    /// Adjust a freshly created `character`'s stats and inventory; the caller moves it to
+    /// `starting_location`
+    pub fn apply(&self, character: &mut Character) {
+        character.loyalty = apply_delta(character.loyalty, self.loyalty);
+        character.suspicion = apply_delta(character.suspicion, self.suspicion);
+        character.thoughtcrime = apply_delta(character.thoughtcrime, self.thoughtcrime);
+        character.inventory.extend(self.inventory.iter().cloned());
+    }
+}
+
+fn profile(
+    starting_location: &str,
+    (loyalty, suspicion, thoughtcrime): (i8, i8, i8),
+    inventory: &[&str],
+) -> OccupationProfile {
+    OccupationProfile {
+        starting_location: starting_location.to_string(),
+        loyalty,
+        suspicion,
+        thoughtcrime,
+        inventory: inventory.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+/// Built-in profiles keyed by occupation; occupations missing here start with no adjustments
+/// at Victory Mansions
+pub fn default_occupations() -> HashMap<String, OccupationProfile> {
+    HashMap::from([
+        (
+            "Records Department Worker".to_string(),
+            profile("Victory Mansions", (-5, 0, 10), &[]),
+        ),
+        (
+            "Junior Spy Instructor".to_string(),
+            profile("Ministry of Truth", (15, -10, 0), &[]),
+        ),
+        (
+            "Fiction Department Writer".to_string(),
+            profile("Victory Mansions", (0, 0, 15), &[]),
+        ),
+        (
+            "Maintenance Technician".to_string(),
+            profile("Victory Mansions", (0, 0, 0), &[crate::items::RAZOR_BLADE]),
+        ),
+        (
+            "Prole".to_string(),
+            profile("Prole District", (-10, 0, 0), &["Victory Gin"]),
+        ),
+    ])
+}
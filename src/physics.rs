@@ -1,40 +1,269 @@
+//! Flight physics shared by the server's 3D game loop and the 2D `Aircraft` model.
+
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
 // Physics constants
-const M: f32 = 1000.0;           // Aircraft mass in kg
-const G: f32 = 9.8;              // Gravity in m/s^2
-const T_MAX: f32 = 10000.0;      // Maximum thrust in N
-const K_D: f32 = 0.1;            // Drag coefficient
-const K_L: f32 = 10.0;           // Lift coefficient
+/// Gravity in m/s^2
+pub const G: f32 = 9.81;
+/// Critical angle of attack in radians (15 deg)
+pub const STALL_ANGLE: f32 = 0.2618;
+/// Fraction of peak lift left once stalled
+pub const POST_STALL_LIFT_FACTOR: f32 = 0.3;
+/// Below this airspeed (m/s) the wing produces no meaningful lift
+pub const MIN_LIFT_AIRSPEED: f32 = 1.0;
 const PITCH_RATE_MAX: f32 = 0.1745; // Max pitch rate in rad/s (10 deg/s)
 const THROTTLE_CHANGE_RATE: f32 = 0.5; // Throttle change rate per second
+const GROUND_WARNING_ALTITUDE: f32 = 50.0; // Altitude in m below which sink rate is checked
+const GROUND_WARNING_SINK_RATE: f32 = 10.0; // Descent rate in m/s that triggers the warning
+const SERVER_MASS: f32 = 1.0; // Server aircraft are unit mass, so forces are accelerations
+const SERVER_LIFT_COEFFICIENT: f32 = 0.1; // Lift per (m/s)^2 per radian on the server airframe
+const STORM_GUST_ACCEL: f32 = 4.0; // Strongest random gust in a storm, in m/s^2 on each axis
+
+/// Weather the 2D aircraft flies through
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Weather {
+    /// Nothing in the way
+    #[default]
+    Clear,
+    /// Grey sky, slightly shorter view
+    Overcast,
+    /// Ground fades out close by
+    Fog,
+    /// Short view and random gusts
+    Storm,
+}
+
+impl Weather {
+    /// The weather that follows this one when cycling through them
+    pub fn next(self) -> Self {
+        match self {
+            Weather::Clear => Weather::Overcast,
+            Weather::Overcast => Weather::Fog,
+            Weather::Fog => Weather::Storm,
+            Weather::Storm => Weather::Clear,
+        }
+    }
+
+    /// Distance in meters beyond which the ground can't be seen
+    pub fn visibility(self) -> f32 {
+        match self {
+            Weather::Clear => 10_000.0,
+            Weather::Overcast => 5_000.0,
+            Weather::Fog => 300.0,
+            Weather::Storm => 1_500.0,
+        }
+    }
+}
+
+/// How aerodynamic drag scales with airspeed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "model", rename_all = "lowercase", deny_unknown_fields)]
+pub enum DragModel {
+    /// Drag force = coeff * airspeed
+    Linear {
+        /// Drag per m/s of airspeed
+        coeff: f32,
+    },
+    /// Drag force = coeff * airspeed^2
+    Quadratic {
+        /// Drag per (m/s)^2 of airspeed
+        coeff: f32,
+    },
+}
+
+/// Mass and aerodynamic coefficients of an aircraft type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Airframe {
+    /// Mass in kg
+    pub mass: f32,
+    /// Thrust at full throttle in N
+    pub max_thrust: f32,
+    /// Lift per (m/s)^2 per radian of angle of attack
+    pub lift_coefficient: f32,
+    /// Drag law
+    pub drag: DragModel,
+}
+
+/// Flight feel of the aircraft RPG characters fly on the server
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PhysicsConfig {
+    /// Gravity in m/s^2
+    pub gravity: f32,
+    /// Drag law; quadratic unless a config picks the simpler linear model
+    pub drag: DragModel,
+    /// Thrust at full throttle in m/s^2
+    pub thrust_scale: f32,
+    /// Share of horizontal speed kept each tick while on the ground
+    pub ground_friction: f32,
+}
+
+impl Default for PhysicsConfig {
+    fn default() -> Self {
+        PhysicsConfig {
+            gravity: G,
+            // Same 40 m/s top speed at full throttle as linear drag of 0.5
+            drag: DragModel::Quadratic { coeff: 0.0125 },
+            thrust_scale: 20.0,
+            ground_friction: 0.9,
+        }
+    }
+}
+
+impl PhysicsConfig {
+    /// Unit-mass airframe with this config's thrust and drag
+    pub fn airframe(&self) -> Airframe {
+        Airframe {
+            mass: SERVER_MASS,
+            max_thrust: self.thrust_scale,
+            lift_coefficient: SERVER_LIFT_COEFFICIENT,
+            drag: self.drag,
+        }
+    }
+}
+
+/// One-tonne light aircraft used by the 2D `Aircraft` model
+pub const LIGHT_AIRCRAFT: Airframe = Airframe {
+    mass: 1000.0,
+    max_thrust: 10000.0,
+    lift_coefficient: 10.0,
+    drag: DragModel::Quadratic { coeff: 0.1 },
+};
+
+/// Kinematic state of an aircraft in 3D; +Z of `orientation` is forward, +Y is up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlightState {
+    /// Position in meters
+    pub position: Point3<f32>,
+    /// Velocity in m/s
+    pub velocity: Vector3<f32>,
+    /// Body orientation
+    pub orientation: UnitQuaternion<f32>,
+    /// Throttle level (0.0 to 1.0)
+    pub throttle: f32,
+}
+
+/// Airflow measurements taken during an integration step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AeroSample {
+    /// Speed relative to the surrounding air in m/s
+    pub airspeed: f32,
+    /// Angle of attack in radians, positive with the nose above the flight path
+    pub angle_of_attack: f32,
+}
+
+impl AeroSample {
+    /// Whether the wing is past its critical angle of attack
+    pub fn is_stalled(&self) -> bool {
+        self.airspeed > MIN_LIFT_AIRSPEED && self.angle_of_attack.abs() > STALL_ANGLE
+    }
+}
+
+/// Advance `state` by `dt` seconds through air moving at `wind`.
+///
+/// Applies thrust, `gravity` (in m/s^2), drag and lift (with stall). Ground
+/// contact is left to the caller.
+pub fn integrate(
+    airframe: &Airframe,
+    state: &mut FlightState,
+    wind: Vector3<f32>,
+    gravity: f32,
+    dt: f32,
+) -> AeroSample {
+    let forward: Vector3<f32> = *(state.orientation * Vector3::z_axis());
+    let up: Vector3<f32> = *(state.orientation * Vector3::y_axis());
+
+    // Thrust along the nose
+    let thrust = forward * airframe.max_thrust * state.throttle;
+
+    // Aerodynamic forces depend on motion relative to the air, not the ground
+    let airflow = state.velocity - wind;
+    let airspeed = airflow.norm();
+
+    // Drag opposes the airflow
+    let drag = match airframe.drag {
+        DragModel::Linear { coeff } => -airflow * coeff,
+        DragModel::Quadratic { coeff } => -airflow * coeff * airspeed,
+    };
+
+    // Lift acts perpendicular to the airflow, in the aircraft's pitch plane
+    let mut lift = Vector3::zeros();
+    let mut angle_of_attack = 0.0;
+    if airspeed > MIN_LIFT_AIRSPEED {
+        let airflow_dir = airflow / airspeed;
+        angle_of_attack = (-airflow_dir.dot(&up)).atan2(airflow_dir.dot(&forward));
+        let lift_dir = up - airflow_dir * up.dot(&airflow_dir);
+        if let Some(lift_dir) = lift_dir.try_normalize(1.0e-6) {
+            // Past the critical angle the airflow separates and lift collapses
+            let effective_aoa = if angle_of_attack.abs() <= STALL_ANGLE {
+                angle_of_attack
+            } else {
+                STALL_ANGLE * POST_STALL_LIFT_FACTOR * angle_of_attack.signum()
+            };
+            lift = lift_dir * airframe.lift_coefficient * airspeed.powi(2) * effective_aoa;
+        }
+    }
+
+    let weight = Vector3::new(0.0, -airframe.mass * gravity, 0.0);
+    let acceleration = (thrust + drag + lift + weight) / airframe.mass;
+
+    state.velocity += acceleration * dt;
+    state.position += state.velocity * dt;
+
+    AeroSample {
+        airspeed,
+        angle_of_attack,
+    }
+}
 
 // Input state structure
+/// Digital control input of the 2D aircraft
+#[derive(Debug, Clone, Default)]
 pub struct InputState {
+    /// Pitch the nose up
     pub pitch_up: bool,
+    /// Pitch the nose down
     pub pitch_down: bool,
+    /// Increase throttle
     pub throttle_up: bool,
+    /// Decrease throttle
     pub throttle_down: bool,
-}
-
-impl Default for InputState {
-    fn default() -> Self {
-        Self {
-            pitch_up: false,
-            pitch_down: false,
-            throttle_up: false,
-            throttle_down: false,
-        }
-    }
+    /// Analog pitch deflection, -1.0 (full down) to 1.0 (full up)
+    pub pitch_axis: f32,
+    /// Analog throttle deflection, -1.0 (full decrease) to 1.0 (full increase)
+    pub throttle_axis: f32,
 }
 
 // Aircraft struct to hold state
+/// Aircraft flying in the vertical x/y plane
 pub struct Aircraft {
-    pub x: f32,           // Horizontal position in meters
-    pub y: f32,           // Altitude in meters
-    pub vx: f32,          // Horizontal velocity in m/s
-    pub vy: f32,          // Vertical velocity in m/s
-    pub theta: f32,       // Pitch angle in radians
-    pub throttle_level: f32, // Throttle level (0.0 to 1.0)
-    pub input: InputState, // Current input state
+    /// Horizontal position in meters
+    pub x: f32,
+    /// Altitude in meters
+    pub y: f32,
+    /// Horizontal velocity in m/s
+    pub vx: f32,
+    /// Vertical velocity in m/s
+    pub vy: f32,
+    /// Pitch angle in radians
+    pub theta: f32,
+    /// Throttle level (0.0 to 1.0)
+    pub throttle_level: f32,
+    /// Current input state
+    pub input: InputState,
+    /// Weather being flown through; storms buffet the aircraft
+    pub weather: Weather,
+    // Source of storm gusts
+    gusts: StdRng,
+}
+
+impl Default for Aircraft {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Aircraft {
@@ -42,76 +271,65 @@ impl Aircraft {
     pub fn new() -> Self {
         Aircraft {
             x: 0.0,
-            y: 100.0,    // Start at 100m altitude
-            vx: 50.0,    // Initial horizontal speed of 50 m/s
+            y: 100.0, // Start at 100m altitude
+            vx: 50.0, // Initial horizontal speed of 50 m/s
             vy: 0.0,
             theta: 0.0,
             throttle_level: 0.0,
             input: InputState::default(),
+            weather: Weather::default(),
+            gusts: StdRng::from_entropy(),
+        }
+    }
+
+    /// Create a new aircraft whose storm gusts are drawn from `seed`, so the same seed flies
+    /// through the same storm
+    pub fn with_seed(seed: u64) -> Self {
+        Aircraft {
+            gusts: StdRng::seed_from_u64(seed),
+            ..Self::new()
         }
     }
 
     /// Update aircraft state based on physics and input
     pub fn update(&mut self, dt: f32) {
-        // Process control inputs
-        let mut pitch_rate = 0.0;
+        // Process control inputs; keys act as full deflection on top of the analog axes
+        let mut pitch_input = self.input.pitch_axis;
         if self.input.pitch_up {
-            pitch_rate = PITCH_RATE_MAX;
+            pitch_input += 1.0;
         } else if self.input.pitch_down {
-            pitch_rate = -PITCH_RATE_MAX;
+            pitch_input -= 1.0;
         }
+        let pitch_rate = PITCH_RATE_MAX * pitch_input.clamp(-1.0, 1.0);
 
-        let mut throttle_change = 0.0;
+        let mut throttle_input = self.input.throttle_axis;
         if self.input.throttle_up {
-            throttle_change = THROTTLE_CHANGE_RATE;
+            throttle_input += 1.0;
         } else if self.input.throttle_down {
-            throttle_change = -THROTTLE_CHANGE_RATE;
+            throttle_input -= 1.0;
         }
+        let throttle_change = THROTTLE_CHANGE_RATE * throttle_input.clamp(-1.0, 1.0);
         self.throttle_level += throttle_change * dt;
         self.throttle_level = self.throttle_level.clamp(0.0, 1.0);
 
         // Update pitch angle and clamp it between -PI/2 and PI/2 radians (-90 to +90 degrees)
         self.theta += pitch_rate * dt;
-        self.theta = self.theta.clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
-
-        // Compute total speed
-        let s = (self.vx.powi(2) + self.vy.powi(2)).sqrt();
-
-        // Compute velocity direction
-        let phi = self.vy.atan2(self.vx);
-
-        // Angle of attack
-        let alpha = self.theta - phi;
-
-        // Calculate forces
-        let lift = K_L * s.powi(2) * alpha;
-        let drag = K_D * s.powi(2);
-        let thrust = T_MAX * self.throttle_level;
-
-        // Forces in x and y directions
-        let (f_x, f_y) = if s > 0.001 { // Avoid division by zero
-            let drag_x = drag * self.vx / s;
-            let drag_y = drag * self.vy / s;
-            let lift_dir_x = -self.vy / s; // Perpendicular to velocity
-            let lift_dir_y = self.vx / s;
-            (
-                thrust * self.theta.cos() - drag_x - lift * lift_dir_x,
-                thrust * self.theta.sin() - drag_y + lift * lift_dir_y - M * G,
-            )
-        } else {
-            (
-                thrust * self.theta.cos(),
-                thrust * self.theta.sin() - M * G,
-            )
-        };
+        self.theta = self
+            .theta
+            .clamp(-std::f32::consts::FRAC_PI_2, std::f32::consts::FRAC_PI_2);
 
-        // Update velocities
-        self.vx += (f_x / M) * dt;
-        self.vy += (f_y / M) * dt;
+        // Fly the shared 3D model in the x/y plane
+        let mut state = self.flight_state();
+        integrate(&LIGHT_AIRCRAFT, &mut state, Vector3::zeros(), G, dt);
+        self.x = state.position.x;
+        self.y = state.position.y;
+        self.vx = state.velocity.x;
+        self.vy = state.velocity.y;
 
-        // Update position
-        self.x += self.vx * dt;
-        self.y += self.vy * dt;
+        if self.weather == Weather::Storm {
+            self.vx += self.gusts.gen_range(-STORM_GUST_ACCEL..=STORM_GUST_ACCEL) * dt;
+            self.vy += self.gusts.gen_range(-STORM_GUST_ACCEL..=STORM_GUST_ACCEL) * dt;
+        }
 
         // Prevent aircraft from going below ground and stop movement
         if self.y < 0.0 {
@@ -121,4 +339,44 @@ impl Aircraft {
             self.theta = 0.0; // Level the aircraft on ground impact
         }
     }
-} 
\ No newline at end of file
+
+    /// Airspeed and angle of attack in still air
+    pub fn aero_sample(&self) -> AeroSample {
+        let airspeed = (self.vx.powi(2) + self.vy.powi(2)).sqrt();
+        let angle_of_attack = if airspeed > MIN_LIFT_AIRSPEED {
+            // Wrap into (-PI, PI] so a tail-first slide reads as a deep stall
+            let alpha = self.theta - self.vy.atan2(self.vx);
+            alpha.sin().atan2(alpha.cos())
+        } else {
+            0.0
+        };
+        AeroSample {
+            airspeed,
+            angle_of_attack,
+        }
+    }
+
+    /// Whether the wing is stalled and a stall warning should be shown
+    pub fn stall_warning(&self) -> bool {
+        self.aero_sample().is_stalled()
+    }
+
+    /// Whether the aircraft is low and descending fast enough to warn about terrain
+    pub fn ground_proximity_warning(&self) -> bool {
+        self.y < GROUND_WARNING_ALTITUDE && -self.vy > GROUND_WARNING_SINK_RATE
+    }
+
+    /// The aircraft as a 3D flight state: nose along +X at `theta` = 0, up along +Y
+    pub fn flight_state(&self) -> FlightState {
+        // Turn the body's +Z nose onto +X, then pitch about Z
+        let nose_to_x =
+            UnitQuaternion::from_axis_angle(&Vector3::y_axis(), std::f32::consts::FRAC_PI_2);
+        let pitch = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), self.theta);
+        FlightState {
+            position: Point3::new(self.x, self.y, 0.0),
+            velocity: Vector3::new(self.vx, self.vy, 0.0),
+            orientation: pitch * nose_to_x,
+            throttle: self.throttle_level,
+        }
+    }
+}
@@ -0,0 +1,135 @@
+//! Recording the messages a server handles and playing them back against a fresh world.
+
+use crate::{
+    handle_client_message, lock_clients, new_game_state, ClientMessage, Clients, GameState,
+    ServerConfig, Simulation, WorldRng,
+};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::{Entry, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+/// One message as the server handled it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayEntry {
+    /// Game loop ticks run before the message was handled
+    pub tick: u64,
+    /// Connection the message came from
+    pub player_id: Uuid,
+    /// The message itself
+    pub message: ClientMessage,
+}
+
+/// Appends every handled message to a file, one JSON entry per line
+#[derive(Clone)]
+pub struct Recorder {
+    path: PathBuf,
+    out: Arc<Mutex<LineWriter<File>>>,
+}
+
+impl fmt::Debug for Recorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recorder")
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Recorder {
+    /// Start a recording at `path`, replacing any file there
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            out: Arc::new(Mutex::new(LineWriter::new(File::create(path)?))),
+        })
+    }
+
+    /// Append a message; a failed write is logged rather than interrupting the game
+    pub fn record(&self, tick: u64, player_id: Uuid, message: &ClientMessage) {
+        let entry = ReplayEntry {
+            tick,
+            player_id,
+            message: message.clone(),
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize {:?} for the recording: {}", message, e);
+                return;
+            }
+        };
+        let mut out = self
+            .out
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Err(e) = writeln!(out, "{}", line) {
+            warn!(
+                "Failed to write to recording {}: {}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Read a recording made by [`Recorder`]
+pub fn load(path: &Path) -> io::Result<Vec<ReplayEntry>> {
+    fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str(line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+        .collect()
+}
+
+/// Play `entries` back against a fresh world built from `config`'s world overrides and seed,
+/// running the game loop's ticks between messages as the server did; returns the final state.
+///
+/// Only the recorded messages are replayed: dropped connections and anything that depends on
+/// wall-clock time, such as idle timeouts, are not reproduced.
+pub fn replay(entries: &[ReplayEntry], config: &ServerConfig) -> GameState {
+    let mut state = new_game_state(&config.world);
+    if let Some(seed) = config.seed {
+        state.rng = WorldRng::from_seed(seed);
+    }
+    let mut simulation = Simulation::new(config);
+    // Every player gets a connection so the handlers behave as they did live; what they are
+    // sent is thrown away
+    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
+    let mut outboxes = Vec::new();
+    for entry in entries {
+        if let Entry::Vacant(slot) = lock_clients(&clients).entry(entry.player_id) {
+            let (sender, receiver) = mpsc::channel(config.client_buffer);
+            slot.insert(sender);
+            outboxes.push(receiver);
+        }
+        while state.tick < entry.tick {
+            simulation.tick(&mut state, &clients);
+            discard(&mut outboxes);
+        }
+        handle_client_message(
+            entry.player_id,
+            entry.message.clone(),
+            &mut state,
+            &clients,
+            config,
+        );
+        discard(&mut outboxes);
+    }
+    state
+}
+
+// Empty the players' outboxes before they fill up and get them dropped as too slow
+fn discard(outboxes: &mut [mpsc::Receiver<Message>]) {
+    for outbox in outboxes {
+        while outbox.try_recv().is_ok() {}
+    }
+}
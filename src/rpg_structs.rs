@@ -1,22 +1,45 @@
+//! Core RPG data types shared between the server and its clients.
+
 use nalgebra::{Point3, UnitQuaternion, Vector3};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+use crate::dialogue::{o_brien_dialogue, old_trader_dialogue, DialogueNode};
+use crate::replay::Recorder;
+
 // --- New Structs for Cat Companion and Quest ---
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Behaviour state of a player's cat companion
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum CatStatus {
+    /// Trailing the player around
     Following,
+    /// Staying put until called
     Waiting,
+    /// Hurt and in need of care
     Injured,
+    /// Separated from the player
     Lost, // Maybe add more states later
 }
 
+/// State of a player's cat companion
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CatState {
+    /// The cat's name
     pub name: String,
-    pub health: u8, // 0-100
+    /// Cat health, 0-100
+    pub health: u8,
+    /// What the cat is currently doing
     pub status: CatStatus,
+    /// Key into WorldState.locations; where the cat is, which trails its owner while following
+    #[serde(default)]
+    pub location: String,
     // We could add 3D position/orientation here too if the cat moves independently in 3D
     // pub position: Point3<f32>,
     // pub orientation: UnitQuaternion<f32>,
@@ -24,62 +47,344 @@ pub struct CatState {
 // --- End New Structs ---
 
 /// Language of the forbidden text
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum TextLanguage {
+    /// Czech original
     Czech,
+    /// English translation
     English,
 }
 
 /// Represents a fragment of forbidden anarcho-capitalist knowledge
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ForbiddenText {
+    /// Unique text identifier
     pub id: String,
+    /// Title of the fragment
     pub title: String,
+    /// Body of the fragment
     pub content: String,
-    pub language: TextLanguage, // Czech or English
-    pub difficulty: u8,         // 1-10 difficulty to understand
-    pub suspicion_risk: u8,     // 1-10 risk of being caught with this text
+    /// Language the text is written in
+    pub language: TextLanguage,
+    /// Knowledge topic reading the text teaches
+    #[serde(default = "default_text_topic")]
+    pub topic: String,
+    /// 1-10 difficulty to understand
+    pub difficulty: u8,
+    /// 1-10 risk of being caught with this text
+    pub suspicion_risk: u8,
+}
+
+// Texts saved before topics existed teach the most basic one
+fn default_text_topic() -> String {
+    "Voluntary Exchange".to_string()
+}
+
+/// A forbidden text a character has stashed somewhere
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HiddenText {
+    /// Location the text was hidden at
+    pub location: String,
+    /// Where at that location it is hidden
+    pub hiding_place: String,
+}
+
+/// How far O'Brien's entrapment of a character has gone
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Entrapment {
+    /// O'Brien is listening
+    Watching {
+        /// Times the character has shared forbidden knowledge with him
+        confidences: u8,
+    },
+    /// O'Brien has made up his mind and will betray the character on `day`
+    Scheduled {
+        /// In-game day of the betrayal
+        day: u32,
+    },
+    /// The trap has closed
+    Betrayed,
+}
+
+impl Default for Entrapment {
+    fn default() -> Self {
+        Entrapment::Watching { confidences: 0 }
+    }
+}
+
+/// How closely the characters at one location are being watched
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LocationHeat {
+    /// Characters at the location
+    pub players: usize,
+    /// Mean suspicion of those characters, 0 when there are none
+    pub average_suspicion: f32,
+    /// Mean thoughtcrime of those characters, 0 when there are none
+    pub average_thoughtcrime: f32,
+}
+
+/// A character's public standing on the leaderboard
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LeaderboardEntry {
+    /// Character name
+    pub name: String,
+    /// Rebellion score, 0-100
+    pub rebellion_score: u8,
+    /// Loyalty to the Party, 0-100
+    pub loyalty: u8,
+    /// Party tasks completed
+    pub tasks_completed: u32,
+    /// In-game days since the character was created
+    pub days_survived: u32,
+    /// Composite score the leaderboard is sorted by
+    pub score: u32,
+}
+
+/// How healthy a character looks to others, without giving the number away
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApparentHealth {
+    /// 75 health or more
+    Healthy,
+    /// 40 to 74 health
+    Worn,
+    /// 15 to 39 health
+    Injured,
+    /// Under 15 health
+    Dying,
+}
+
+impl ApparentHealth {
+    /// How `health` (0-100) looks from the outside
+    pub fn of(health: u8) -> Self {
+        match health {
+            75.. => ApparentHealth::Healthy,
+            40..=74 => ApparentHealth::Worn,
+            15..=39 => ApparentHealth::Injured,
+            _ => ApparentHealth::Dying,
+        }
+    }
+}
+
+/// What other players can see of a character when they examine it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PublicProfile {
+    /// Character name
+    pub name: String,
+    /// Occupation within the Party
+    pub occupation: String,
+    /// Where the character is
+    pub location: String,
+    /// Items carried openly; forbidden texts and the Book stay out of sight
+    pub visible_inventory: Vec<String>,
+    /// Rough state of health
+    pub apparent_health: ApparentHealth,
+    /// Where the character's aircraft is, as anyone looking up can see
+    #[serde(with = "crate::nalgebra_serde::point3")]
+    pub position: Point3<f32>,
+    /// Which way the aircraft is pointing
+    #[serde(with = "crate::nalgebra_serde::unit_quaternion")]
+    pub orientation: UnitQuaternion<f32>,
 }
 
-// Represents a single player's character
+/// A wound that holds back the character's maximum health until it heals or is treated
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Injury {
+    /// How the character came by it, e.g. "Hard landing"
+    pub cause: String,
+    /// Health it takes off the maximum; shrinks as the injury heals
+    pub severity: u8,
+}
+
+/// Share of damage taken that lingers as an injury
+const INJURY_SHARE: f32 = 0.5;
+/// Maximum health injuries can push a character down to
+const MIN_MAX_HEALTH: u8 = 20;
+
+/// Represents a single player's character
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Character {
+    /// Owning player's connection id
     pub player_id: Uuid,
+    /// Character name
     pub name: String,
+    /// Party job title
     pub occupation: String,
-    pub loyalty: u8,      // 0-100
-    pub suspicion: u8,    // 0-100
-    pub thoughtcrime: u8, // 0-100
-    pub health: u8,       // 0-100
+    /// Loyalty to the Party, 0-100
+    pub loyalty: u8,
+    /// How closely the Thought Police watch this character, 0-100
+    pub suspicion: u8,
+    /// Accumulated thoughtcrime, 0-100
+    pub thoughtcrime: u8,
+    /// Health, 0 up to `max_health()`
+    pub health: u8,
+    /// Wounds that have yet to heal, oldest first
+    #[serde(default)]
+    pub injuries: Vec<Injury>,
+    /// Will to carry on, 0-100; low morale makes other actions go worse
+    #[serde(default = "default_morale")]
+    pub morale: u8,
+    /// Item names carried by the character
     pub inventory: Vec<String>,
-    pub relationships: HashMap<String, i8>, // NPC name -> Trust level (-100 to 100)
-    pub location: String,                   // Key into WorldState.locations (RPG location)
+    /// NPC name -> Trust level (-100 to 100)
+    #[serde(serialize_with = "sorted_map")]
+    pub relationships: HashMap<String, i8>,
+    /// `seq` of the last FlyInput applied to the character
+    #[serde(default)]
+    pub last_input_seq: u32,
+    /// NPC name -> dialogue node the conversation with them is at
+    #[serde(default, serialize_with = "sorted_map")]
+    pub dialogue_nodes: HashMap<String, String>,
+    /// Key into WorldState.locations (RPG location)
+    pub location: String,
+    /// Remaining stops of a multi-hop journey, next stop first
+    #[serde(default)]
+    pub travel_route: Vec<String>,
+    /// Secret diary entries
     pub journal_entries: Vec<String>,
+    /// Where the diary is hidden, if it has been hidden since it was last written in
+    pub journal_hiding_place: Option<String>,
+    /// Text id -> where the character hid it
+    #[serde(default, serialize_with = "sorted_map")]
+    pub hidden_texts: HashMap<String, HiddenText>,
+    /// What the Thought Police are doing with the character; blocks normal actions while set
+    #[serde(default)]
+    pub detention: Option<ThoughtcrimeConsequence>,
+    /// Number of Party tasks completed
     pub tasks_completed: u32,
-    pub rebellion_score: u8, // 0-100
+    /// Progress towards rebellion, 0-100
+    pub rebellion_score: u8,
+    /// In-game day since which loyalty has stayed high enough for promotion
+    #[serde(default)]
+    pub loyal_since_day: Option<u32>,
+    /// In-game day of the character's last loyal act, such as work or the Two Minutes Hate
+    #[serde(default)]
+    pub last_loyal_act_day: Option<u32>,
+    /// In-game day of the character's last lesson from an NPC; one is all a day allows
+    #[serde(default)]
+    pub last_lesson_day: Option<u32>,
+    /// In-game day the character was created on
+    #[serde(default)]
+    pub created_day: u32,
+    /// Whether the character has joined the current or most recent Two Minutes Hate
+    #[serde(default)]
+    pub attended_hate: bool,
+    /// When the owning player's connection dropped; the character is frozen until they
+    /// return or the grace period runs out
+    #[serde(skip)]
+    pub disconnected_at: Option<Instant>,
+    /// When the owning player last sent a message
+    #[serde(skip, default = "Instant::now")]
+    pub last_input_at: Instant,
+    /// Whether the player has been warned that they are about to be disconnected for idling
+    #[serde(skip)]
+    pub idle_warned: bool,
+    /// Whether the player has been told they can't work the throttle, since they last let go
+    #[serde(skip)]
+    pub flight_refused: bool,
+    /// Progress of O'Brien's entrapment of the character
+    #[serde(default)]
+    pub entrapment: Entrapment,
 
     // --- Forbidden Knowledge State ---
-    pub anarcho_knowledge: HashMap<String, u8>, // Topic -> Understanding level (0-100)
-    pub economic_freedom_score: u8, // 0-100, affected by anarcho-capitalist understanding
-    pub voluntary_actions: u32,     // Counter for voluntary exchanges/actions taken
+    /// Topic -> Understanding level (0-100)
+    #[serde(serialize_with = "sorted_map")]
+    pub anarcho_knowledge: HashMap<String, u8>,
+    /// 0-100, affected by anarcho-capitalist understanding
+    pub economic_freedom_score: u8,
+    /// Counter for voluntary exchanges/actions taken
+    pub voluntary_actions: u32,
+    /// Languages the character can read forbidden texts in
+    #[serde(default = "default_languages", serialize_with = "sorted_set")]
+    pub languages: HashSet<TextLanguage>,
+    /// Language -> lessons taken towards reading it
+    #[serde(default, serialize_with = "sorted_map")]
+    pub language_lessons: HashMap<TextLanguage, u8>,
+    /// Subject -> proficiency (0-100) learned from NPCs
+    #[serde(default, serialize_with = "sorted_map")]
+    pub skills: HashMap<String, u8>,
     // --- End Forbidden Knowledge State ---
 
     // --- 3D Flight State ---
+    /// World-space position
+    #[serde(with = "crate::nalgebra_serde::point3")]
     pub position: Point3<f32>,
+    /// World-space velocity
+    #[serde(with = "crate::nalgebra_serde::vector3")]
     pub velocity: Vector3<f32>,
+    /// Body orientation; +Z is forward, +Y is up
+    #[serde(with = "crate::nalgebra_serde::unit_quaternion")]
     pub orientation: UnitQuaternion<f32>,
-    pub throttle: f32, // 0.0 to 1.0
+    /// Throttle, 0.0 to 1.0
+    pub throttle: f32,
+    /// Whether the wing was stalled on the last physics tick
+    pub stalled: bool,
+    /// Whether the autopilot levels the wings while the stick is left alone
+    #[serde(default = "default_flight_assist")]
+    pub flight_assist: bool,
+    /// Whether stick input has arrived since the last physics tick
+    #[serde(skip)]
+    pub stick_held: bool,
     // --- End 3D Flight State ---
 
     // --- Cat Companion & Quest State ---
+    /// The cat companion, if the character still has one
     pub cat_companion: Option<CatState>,
+    /// Whether the Kocourek quest is in progress
     pub kocourka_quest_active: bool,
+    /// Whether the Kocourek quest has been failed
     pub kocourka_quest_failed: bool,
     // --- End Cat Companion & Quest State ---
 }
 
+// Write a map in key order, so the same state always serializes the same way for saves,
+// snapshots and update diffs
+fn sorted_map<K: Ord + Serialize, V: Serialize, S: Serializer>(
+    map: &HashMap<K, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().collect::<BTreeMap<_, _>>())
+}
+
+// Write a set in order, as `sorted_map` does for maps
+fn sorted_set<T: Ord + Serialize, S: Serializer>(
+    set: &HashSet<T>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(set.iter().collect::<BTreeSet<_>>())
+}
+
+// Characters saved before morale existed start out even
+fn default_morale() -> u8 {
+    STARTING_MORALE
+}
+
+// Characters saved before the assist existed get it like new ones
+fn default_flight_assist() -> bool {
+    true
+}
+
+// Every citizen reads English
+fn default_languages() -> HashSet<TextLanguage> {
+    HashSet::from([TextLanguage::English])
+}
+
+/// Occupation given to characters promoted into the Inner Party
+pub const INNER_PARTY_OCCUPATION: &str = "Inner Party Member";
+
+/// Occupation trained to fly the Party's aircraft from the start
+pub const PILOT_OCCUPATION: &str = "Pilot";
+
+/// Morale a new character starts with, and drifts back towards when nothing weighs on it
+pub const STARTING_MORALE: u8 = 50;
+
+/// Skill that lets anyone fly once learned well enough
+pub const PILOTING_SKILL: &str = "Piloting";
+
+/// `PILOTING_SKILL` proficiency needed to open the throttle
+pub const PILOTING_SKILL_TO_FLY: u8 = 30;
+
 impl Character {
-    // Basic constructor for a new character
+    /// Basic constructor for a new character
     pub fn new(player_id: Uuid, name: String, occupation: String) -> Self {
         // Initialize base character
         let mut character = Character {
@@ -90,23 +395,47 @@ impl Character {
             suspicion: 0,
             thoughtcrime: 0,
             health: 100,
+            injuries: Vec::new(),
+            morale: STARTING_MORALE,
             inventory: Vec::new(),
             relationships: HashMap::new(),
+            last_input_seq: 0,
+            dialogue_nodes: HashMap::new(),
             location: String::from("Victory Mansions"), // Starting RPG location
+            travel_route: Vec::new(),
             journal_entries: Vec::new(),
+            journal_hiding_place: None,
+            hidden_texts: HashMap::new(),
+            detention: None,
             tasks_completed: 0,
             rebellion_score: 0,
+            loyal_since_day: None,
+            last_loyal_act_day: None,
+            last_lesson_day: None,
+            created_day: 0,
+            attended_hate: false,
+            disconnected_at: None,
+            last_input_at: Instant::now(),
+            idle_warned: false,
+            flight_refused: false,
+            entrapment: Entrapment::default(),
 
             // Initialize Forbidden Knowledge state
             anarcho_knowledge: HashMap::new(),
             economic_freedom_score: 0,
             voluntary_actions: 0,
+            languages: default_languages(),
+            language_lessons: HashMap::new(),
+            skills: HashMap::new(),
 
             // Initialize 3D state
             position: Point3::new(0.0, 0.0, 1.7),
             velocity: Vector3::zeros(),
             orientation: UnitQuaternion::identity(),
             throttle: 0.0,
+            stalled: false,
+            flight_assist: true,
+            stick_held: false,
 
             // Initialize Cat & Quest state
             cat_companion: None, // Initially no cat
@@ -136,6 +465,7 @@ impl Character {
             name: "Kocourek".to_string(), // Default cat name
             health: 100,
             status: CatStatus::Following,
+            location: character.location.clone(),
             // Initialize position if needed
             // position: character.position, // Start near player
             // orientation: UnitQuaternion::identity(),
@@ -144,43 +474,245 @@ impl Character {
 
         character
     }
+
+    /// Whether the character has been promoted into the Inner Party
+    pub fn is_inner_party(&self) -> bool {
+        self.occupation == INNER_PARTY_OCCUPATION
+    }
+
+    /// Health the character can recover to with their current injuries
+    pub fn max_health(&self) -> u8 {
+        let held_back: u32 = self
+            .injuries
+            .iter()
+            .map(|injury| injury.severity as u32)
+            .sum();
+        100u32.saturating_sub(held_back).max(MIN_MAX_HEALTH as u32) as u8
+    }
+
+    /// Take `damage`, part of which lingers as an injury from `cause`
+    pub fn injure(&mut self, cause: &str, damage: u8) {
+        self.health = self.health.saturating_sub(damage);
+        let severity = (damage as f32 * INJURY_SHARE).round() as u8;
+        if severity > 0 {
+            self.injuries.push(Injury {
+                cause: cause.to_string(),
+                severity,
+            });
+        }
+        self.health = self.health.min(self.max_health());
+    }
+
+    /// Recover up to `amount` health, no further than the injuries allow
+    pub fn heal(&mut self, amount: u8) {
+        self.health = self
+            .health
+            .saturating_add(amount)
+            .min(self.max_health())
+            .max(self.health);
+    }
+
+    /// Dress the most severe injury so it stops holding health back; returns it
+    pub fn treat_worst_injury(&mut self) -> Option<Injury> {
+        let worst = self
+            .injuries
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, injury)| injury.severity)
+            .map(|(index, _)| index)?;
+        Some(self.injuries.remove(worst))
+    }
+
+    /// What others see of the character: never suspicion, thoughtcrime or the diary
+    pub fn public_view(&self, world: &WorldState) -> PublicProfile {
+        PublicProfile {
+            name: self.name.clone(),
+            occupation: self.occupation.clone(),
+            location: self.location.clone(),
+            visible_inventory: self
+                .inventory
+                .iter()
+                .filter(|item| {
+                    item.as_str() != crate::items::THE_BOOK
+                        && !world.forbidden_texts.contains_key(item.as_str())
+                })
+                .cloned()
+                .collect(),
+            apparent_health: ApparentHealth::of(self.health),
+            position: self.position,
+            orientation: self.orientation,
+        }
+    }
+
+    /// Whether the character may open the throttle: pilots and the Inner Party always may,
+    /// anyone else once they have completed `min_tasks` Party tasks or learned to pilot
+    pub fn can_fly(&self, min_tasks: u32) -> bool {
+        self.occupation == PILOT_OCCUPATION
+            || self.is_inner_party()
+            || self.tasks_completed >= min_tasks
+            || self.skills.get(PILOTING_SKILL).copied().unwrap_or(0) >= PILOTING_SKILL_TO_FLY
+    }
 }
 
-// Represents a location in the world
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// Represents a location in the world
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Location {
+    /// Location name, also its key in `WorldState.locations`
     pub name: String,
+    /// Flavour text shown to players
     pub description: String,
-    pub connections: Vec<String>, // Names of connected locations
-    pub safety: u8,               // 1-5 scale (5 is safest)
+    /// Names of connected locations
+    pub connections: Vec<String>,
+    /// 1-5 scale (5 is safest)
+    pub safety: u8,
+}
+
+/// Something the character can do right now, as the `ClientMessage` that does it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AvailableAction {
+    /// Name of the `ClientMessage` variant, e.g. "VoluntaryExchange"
+    pub message: String,
+    /// NPCs, players, items, texts or places it can be aimed at; empty if it takes none
+    pub targets: Vec<String>,
 }
 
-// Represents a Non-Player Character
+/// A location's place on the map, without its description or anything that changes in play
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MapLocation {
+    /// Location name
+    pub name: String,
+    /// Names of connected locations
+    pub connections: Vec<String>,
+    /// 1-5 scale (5 is safest)
+    pub safety: u8,
+}
+
+/// Represents a Non-Player Character
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Npc {
+    /// NPC name, also its key in `WorldState.npcs`
     pub name: String,
+    /// Flavour text shown to players
     pub description: String,
-    pub trust: i8,        // Base trust/betrayal factor
-    pub location: String, // Key into WorldState.locations
+    /// Base trust/betrayal factor
+    pub trust: i8,
+    /// Key into WorldState.locations
+    pub location: String,
+    /// Which side the NPC is on, deciding how players' deeds sway them
+    #[serde(default)]
+    pub alignment: NpcAlignment,
+    /// Conversation tree keyed by node id; empty if the NPC has nothing to say
+    #[serde(default, serialize_with = "sorted_map")]
+    pub dialogue: HashMap<String, DialogueNode>,
 }
 
-// Represents the static and dynamic state of the game world
+/// Which side an NPC is on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NpcAlignment {
+    /// Loyal to the Party, or working for it
+    Party,
+    /// Quietly against the Party
+    Rebel,
+    /// Keeps out of it
+    #[default]
+    Neutral,
+}
+
+/// Hour night falls
+const NIGHT_START_HOUR: u8 = 22;
+/// Hour night ends
+const NIGHT_END_HOUR: u8 = 6;
+
+/// Represents the static and dynamic state of the game world
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct WorldState {
+    /// All locations keyed by name
+    #[serde(serialize_with = "sorted_map")]
     pub locations: HashMap<String, Location>,
+    /// All NPCs keyed by name
+    #[serde(serialize_with = "sorted_map")]
     pub npcs: HashMap<String, Npc>,
+    /// In-game calendar date
     pub current_date: String,
+    /// Whether today's Two Minutes Hate is scheduled
     pub two_minutes_hate_today: bool,
+    /// Real seconds left of the Two Minutes Hate in progress, 0 when none is
+    #[serde(default)]
+    pub hate_seconds_left: f32,
+    /// Weekly chocolate ration in grams
     pub chocolate_ration: u8,
-    pub current_enemy: String, // "Eurasia" or "Eastasia"
+    /// "Eurasia" or "Eastasia"
+    pub current_enemy: String,
+    /// Prevailing wind velocity in m/s
+    #[serde(with = "crate::nalgebra_serde::vector3")]
+    pub wind: Vector3<f32>,
+    /// Hour of the in-game day, 0-23
+    #[serde(default)]
+    pub hour: u8,
 
     // Add forbidden knowledge collection
-    pub forbidden_texts: HashMap<String, ForbiddenText>, // id -> ForbiddenText
-    pub text_locations: HashMap<String, Vec<String>>,    // location -> text_ids
+    /// id -> ForbiddenText
+    #[serde(serialize_with = "sorted_map")]
+    pub forbidden_texts: HashMap<String, ForbiddenText>,
+    /// location -> text_ids
+    #[serde(serialize_with = "sorted_map")]
+    pub text_locations: HashMap<String, Vec<String>>,
+    /// Record id -> what the record says now, after any alterations
+    #[serde(default, serialize_with = "sorted_map")]
+    pub records: HashMap<String, String>,
 }
 
 impl WorldState {
-    // Initialize the world with default 1984 settings
+    /// Whether a Two Minutes Hate is being held right now
+    pub fn hate_in_progress(&self) -> bool {
+        self.two_minutes_hate_today && self.hate_seconds_left > 0.0
+    }
+
+    /// Whether it is currently night, when telescreens watch less closely
+    pub fn is_night(&self) -> bool {
+        self.hour >= NIGHT_START_HOUR || self.hour < NIGHT_END_HOUR
+    }
+
+    /// Shortest chain of connected locations leading from `from` to `to`.
+    ///
+    /// The route excludes `from` and ends with `to`; `None` when `to` can't be reached.
+    pub fn find_route(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(Vec::new());
+        }
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                let mut route = vec![to.to_string()];
+                let mut step = to;
+                while let Some(&prev) = previous.get(step) {
+                    if prev == from {
+                        break;
+                    }
+                    route.push(prev.to_string());
+                    step = prev;
+                }
+                route.reverse();
+                return Some(route);
+            }
+            let Some(location) = self.locations.get(current) else {
+                continue;
+            };
+            for next in &location.connections {
+                if next != from
+                    && self.locations.contains_key(next)
+                    && !previous.contains_key(next.as_str())
+                {
+                    previous.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// Initialize the world with default 1984 settings
     pub fn initialize() -> Self {
         let mut locations = HashMap::new();
         let mut npcs = HashMap::new();
@@ -258,8 +790,10 @@ impl WorldState {
                 description:
                     "A high-ranking Inner Party member who seems to have rebellious tendencies."
                         .to_string(),
-                trust: 0, // Will betray you
+                trust: 0, // Will betray you; see Entrapment
                 location: "Ministry of Truth".to_string(),
+                alignment: NpcAlignment::Party,
+                dialogue: o_brien_dialogue(),
             },
         );
         npcs.insert(
@@ -271,6 +805,8 @@ impl WorldState {
                         .to_string(),
                 trust: 80,
                 location: "Ministry of Truth".to_string(),
+                alignment: NpcAlignment::Rebel,
+                dialogue: HashMap::new(),
             },
         );
         npcs.insert(
@@ -281,6 +817,8 @@ impl WorldState {
                     .to_string(),
                 trust: -100, // Thought Police agent
                 location: "Charrington's Shop".to_string(),
+                alignment: NpcAlignment::Party,
+                dialogue: HashMap::new(),
             },
         );
         npcs.insert(
@@ -292,6 +830,8 @@ impl WorldState {
                         .to_string(),
                 trust: 20,
                 location: "Victory Mansions".to_string(),
+                alignment: NpcAlignment::Party,
+                dialogue: HashMap::new(),
             },
         );
         npcs.insert(
@@ -303,6 +843,8 @@ impl WorldState {
                         .to_string(),
                 trust: 50,
                 location: "Canteen".to_string(),
+                alignment: NpcAlignment::Party,
+                dialogue: HashMap::new(),
             },
         );
         npcs.insert(
@@ -313,6 +855,8 @@ impl WorldState {
                     .to_string(),
                 trust: 70,
                 location: "Prole District".to_string(),
+                alignment: NpcAlignment::Rebel,
+                dialogue: old_trader_dialogue(),
             },
         );
 
@@ -328,6 +872,7 @@ impl WorldState {
                 title: "Principy dobrovolnosti".to_string(), // Principles of Voluntariness
                 content: "Anarchokapitalismus je založen na myšlence oboustranné dobrovolnosti: Nikdo by neměl být nucen a nikomu by nemělo být bráněno nabízet ostatním produkty své práce za libovolných podmínek...".to_string(),
                 language: TextLanguage::Czech,
+                topic: "Principles of Non-Aggression".to_string(),
                 difficulty: 5,
                 suspicion_risk: 8,
             },
@@ -340,6 +885,7 @@ impl WorldState {
                 title: "Volný trh a svoboda jednotlivce".to_string(), // Free Market and Individual Freedom
                 content: "Chceme jen svobodně žít v klidu a míru; chceme milovat, bavit se, pracovat, rozhodovat o sobě. Nechceme a nepotřebujeme nikoho, kdo si bude násilím brát plody naší práce...".to_string(),
                 language: TextLanguage::Czech,
+                topic: "Free Market Economy".to_string(),
                 difficulty: 6,
                 suspicion_risk: 9,
             },
@@ -352,6 +898,7 @@ impl WorldState {
                 title: "Mýtus nezbytnosti státu".to_string(), // The Myth of State Necessity
                 content: "To je sice hezká pohádka, ale bez státu by naše společnost prostě nefungovala. Tak zněla má reakce, když jsem o anarchokapitalismu slyšel poprvé...".to_string(),
                 language: TextLanguage::Czech,
+                topic: "Decentralization".to_string(),
                 difficulty: 7,
                 suspicion_risk: 10,
             },
@@ -365,6 +912,7 @@ impl WorldState {
                 title: "The Path to Freedom".to_string(),
                 content: "We want only to live freely in peace; we want to love, have fun, work, and make our own decisions. We don't want or need anyone who would forcibly take the fruits of our labor...".to_string(),
                 language: TextLanguage::English,
+                topic: "Voluntary Exchange".to_string(),
                 difficulty: 3,
                 suspicion_risk: 7,
             },
@@ -389,136 +937,1058 @@ impl WorldState {
             npcs,
             current_date: "April 4, 1984".to_string(),
             two_minutes_hate_today: true,
+            hate_seconds_left: 0.0,
             chocolate_ration: 30, // grams
             current_enemy: "Eurasia".to_string(),
+            wind: Vector3::zeros(),
+            hour: 6, // Days start at dawn
             forbidden_texts,
             text_locations,
+            records: HashMap::new(),
         }
     }
+
+    /// Load a scenario's locations, NPCs and forbidden texts from a TOML file, or JSON for any
+    /// other extension; the calendar, ration and enemy keep their built-in starting values.
+    ///
+    /// Fails with every dangling reference listed when the scenario does not hold together.
+    pub fn from_config(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let scenario: Scenario = if path
+            .extension()
+            .is_some_and(|extension| extension == "toml")
+        {
+            toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        } else {
+            serde_json::from_str(&text)?
+        };
+        let world = WorldState {
+            locations: keyed(scenario.locations, |location| &location.name),
+            npcs: keyed(scenario.npcs, |npc| &npc.name),
+            forbidden_texts: keyed(scenario.forbidden_texts, |text| &text.id),
+            text_locations: scenario.text_locations,
+            ..WorldState::initialize()
+        };
+        let problems = world.problems();
+        if problems.is_empty() {
+            Ok(world)
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} problem(s) in scenario {}:\n  {}",
+                    problems.len(),
+                    path.display(),
+                    problems.join("\n  ")
+                ),
+            ))
+        }
+    }
+
+    /// The map's topology, sorted by location name
+    pub fn map(&self) -> Vec<MapLocation> {
+        sorted(&self.locations)
+            .into_values()
+            .map(|location| MapLocation {
+                name: location.name.clone(),
+                connections: location.connections.clone(),
+                safety: location.safety,
+            })
+            .collect()
+    }
+
+    /// References in the map that lead nowhere, one description per problem
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.locations.is_empty() {
+            problems.push("the world has no locations".to_string());
+        }
+        for (name, location) in sorted(&self.locations) {
+            for connection in &location.connections {
+                if !self.locations.contains_key(connection) {
+                    problems.push(format!(
+                        "location {} connects to unknown location {}",
+                        name, connection
+                    ));
+                }
+            }
+        }
+        for (name, npc) in sorted(&self.npcs) {
+            if !self.locations.contains_key(&npc.location) {
+                problems.push(format!(
+                    "NPC {} is at unknown location {}",
+                    name, npc.location
+                ));
+            }
+        }
+        for (location, text_ids) in sorted(&self.text_locations) {
+            if !self.locations.contains_key(location) {
+                problems.push(format!("texts are hidden at unknown location {}", location));
+            }
+            for text_id in text_ids {
+                if !self.forbidden_texts.contains_key(text_id) {
+                    problems.push(format!(
+                        "location {} hides unknown text {}",
+                        location, text_id
+                    ));
+                }
+            }
+        }
+        problems
+    }
+}
+
+// The parts of a world a scenario file describes; locations, NPCs and texts are lists and keyed
+// by their own names
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Scenario {
+    locations: Vec<Location>,
+    #[serde(default)]
+    npcs: Vec<Npc>,
+    #[serde(default)]
+    forbidden_texts: Vec<ForbiddenText>,
+    #[serde(default)]
+    text_locations: HashMap<String, Vec<String>>,
+}
+
+// Key a scenario list by each entry's name
+fn keyed<T>(items: Vec<T>, key: impl Fn(&T) -> &String) -> HashMap<String, T> {
+    items
+        .into_iter()
+        .map(|item| (key(&item).clone(), item))
+        .collect()
+}
+
+// Entries in name order, so problems are reported the same way every run
+fn sorted<V>(map: &HashMap<String, V>) -> BTreeMap<&String, &V> {
+    map.iter().collect()
+}
+
+/// Ground height in meters under the given RPG location.
+///
+/// Unknown locations sit at sea level.
+pub fn terrain_height(location: &str) -> f32 {
+    match location {
+        "Victory Mansions" => 15.0, // Tower block on the hill
+        "Ministry of Truth" => 10.0,
+        "Canteen" => 10.0, // Inside the Ministry of Truth
+        "Ministry of Love" => 10.0,
+        "Victory Square" => 5.0,
+        "Charrington's Shop" => 2.0,
+        "Prole District" => 0.0, // Low-lying slums by the river
+        _ => 0.0,
+    }
+}
+
+/// Ground coordinates `(x, z)` in meters of the given RPG location on the flight map.
+///
+/// Locations without a place on the map, such as ones added by config, return `None` and can't
+/// be landed at.
+pub fn location_coordinates(location: &str) -> Option<(f32, f32)> {
+    match location {
+        "Victory Mansions" => Some((0.0, 0.0)),
+        "Ministry of Truth" => Some((400.0, 0.0)),
+        "Canteen" => Some((420.0, 40.0)),
+        "Ministry of Love" => Some((800.0, -300.0)), // Windowless, far from everything
+        "Victory Square" => Some((200.0, 250.0)),
+        "Charrington's Shop" => Some((-150.0, 350.0)),
+        "Prole District" => Some((-300.0, 500.0)),
+        _ => None,
+    }
+}
+
+/// Change how `npc` feels about `character` by `delta`, keeping trust within -100..=100.
+///
+/// Returns the new trust level.
+pub fn adjust_relationship(character: &mut Character, npc: &str, delta: i8) -> i8 {
+    let trust = character.relationships.entry(npc.to_string()).or_insert(0);
+    *trust = trust.saturating_add(delta).clamp(-100, 100);
+    *trust
+}
+
+/// Random number generator for game events; seed it for reproducible runs
+#[derive(Debug, Clone)]
+pub struct WorldRng(StdRng);
+
+impl WorldRng {
+    /// Generator that always produces the same sequence for the same `seed`
+    pub fn from_seed(seed: u64) -> Self {
+        WorldRng(StdRng::seed_from_u64(seed))
+    }
 }
 
-// Represents the overall state of the game, including all players
+impl Default for WorldRng {
+    fn default() -> Self {
+        WorldRng(StdRng::from_entropy())
+    }
+}
+
+impl RngCore for WorldRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+/// Represents the overall state of the game, including all players
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GameState {
+    /// Active characters keyed by player id
+    #[serde(serialize_with = "sorted_map")]
     pub players: HashMap<Uuid, Character>,
+    /// Characters of disconnected players, keyed by character name
+    #[serde(default, serialize_with = "sorted_map")]
+    pub saved_characters: HashMap<String, Character>,
+    /// Shared world state
     pub world_state: WorldState,
+    /// Current in-game day, starting at 1
+    pub day: u32,
+    /// Seconds of the current in-game day that have passed
+    #[serde(default)]
+    pub day_elapsed: f32,
+    /// Source of every random game event; not saved, so a loaded world is reseeded
+    #[serde(skip)]
+    pub rng: WorldRng,
+    /// Recent narrative events, oldest first; not saved or sent with the state
+    #[serde(skip)]
+    pub event_log: VecDeque<WorldEvent>,
+    /// Game loop ticks run since the server started; not saved
+    #[serde(skip)]
+    pub tick: u64,
+    /// Where handled client messages are recorded for replay, if anywhere
+    #[serde(skip)]
+    pub recorder: Option<Recorder>,
+    /// The `WorldMap` message, serialized on first request; the map doesn't change after load
+    #[serde(skip)]
+    pub world_map_json: Option<String>,
+}
+
+/// What every client is told about the game: the world and the public face of each active
+/// character, never anyone's stats, diary or saved characters
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PublicGameState {
+    /// Public profiles of active characters keyed by player id
+    #[serde(serialize_with = "sorted_map")]
+    pub players: HashMap<Uuid, PublicProfile>,
+    /// Shared world state; left out of game loop updates while it hasn't changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world_state: Option<WorldState>,
+    /// Current in-game day, starting at 1
     pub day: u32,
+    /// Seconds of the current in-game day that have passed
+    pub day_elapsed: f32,
+}
+
+/// Most events kept in `GameState::event_log`
+pub const EVENT_LOG_CAPACITY: usize = 100;
+
+/// Narrative told to more than one player, kept for those who missed it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WorldEvent {
+    /// Unix time in seconds it happened at
+    pub timestamp: u64,
+    /// In-game day it happened on
+    pub day: u32,
+    /// Location it was told at, or `None` for news told everywhere
+    pub location: Option<String>,
+    /// What happened
+    pub text: String,
 }
 
 impl GameState {
+    /// Create a fresh game with the default world and no players
     pub fn new() -> Self {
         GameState {
             players: HashMap::new(),
+            saved_characters: HashMap::new(),
             world_state: WorldState::initialize(),
             day: 1,
+            day_elapsed: 0.0,
+            rng: WorldRng::default(),
+            event_log: VecDeque::new(),
+            tick: 0,
+            recorder: None,
+            world_map_json: None,
+        }
+    }
+
+    /// The part of the state every client may see
+    pub fn public_view(&self) -> PublicGameState {
+        PublicGameState {
+            players: self
+                .players
+                .iter()
+                .map(|(id, character)| (*id, character.public_view(&self.world_state)))
+                .collect(),
+            world_state: Some(self.world_state.clone()),
+            day: self.day,
+            day_elapsed: self.day_elapsed,
+        }
+    }
+
+    /// Log `text`, told at `location` or everywhere, dropping the oldest event when full
+    pub fn record_event(&mut self, location: Option<&str>, text: impl Into<String>) {
+        if self.event_log.len() == EVENT_LOG_CAPACITY {
+            self.event_log.pop_front();
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        self.event_log.push_back(WorldEvent {
+            timestamp,
+            day: self.day,
+            location: location.map(str::to_string),
+            text: text.into(),
+        });
+    }
+
+    /// The last `limit` events told everywhere or at `location`, oldest first
+    pub fn recent_events(&self, location: Option<&str>, limit: usize) -> Vec<WorldEvent> {
+        let mut events: Vec<WorldEvent> = self
+            .event_log
+            .iter()
+            .rev()
+            .filter(|event| event.location.is_none() || event.location.as_deref() == location)
+            .take(limit)
+            .cloned()
+            .collect();
+        events.reverse();
+        events
+    }
+
+    /// Write the game state to `path`: a compact bincode snapshot if the
+    /// extension is `.bin`, pretty-printed JSON otherwise.
+    ///
+    /// The file is written next to `path` first and then renamed over it, so a
+    /// crash mid-write never leaves a truncated save behind.
+    pub fn save_to_path(&self, path: &Path) -> io::Result<()> {
+        let bytes = if is_binary_snapshot(path) {
+            bincode::serialize(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+        } else {
+            serde_json::to_vec_pretty(self)?
+        };
+        let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+        tmp_name.push(".tmp");
+        let tmp_path = path.with_file_name(tmp_name);
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Read a game state previously written by [`GameState::save_to_path`],
+    /// picking the format from the extension the same way
+    pub fn load_from_path(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if is_binary_snapshot(path) {
+            bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        } else {
+            Ok(serde_json::from_slice(&bytes)?)
         }
     }
 }
 
-// Enum for messages sent from Server to Client
+// Whether `path` names a bincode snapshot rather than a JSON save
+fn is_binary_snapshot(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "bin")
+}
+
+impl Default for GameState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Version of the client/server protocol, bumped whenever `ClientMessage` or `ServerMessage`
+/// change in a way older clients can't handle
+pub const PROTOCOL_VERSION: u32 = 7;
+
+/// WebSocket close code sent to clients that speak a different protocol version
+pub const PROTOCOL_MISMATCH_CLOSE_CODE: u16 = 4000;
+
+/// Why a request could not be processed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The message was malformed or named an option that doesn't exist
+    InvalidMessage,
+    /// A missing or wrong admin token
+    NotAuthenticated,
+    /// No room for another connection
+    ServerFull,
+    /// The rules don't allow this player to do that
+    NotAllowed,
+    /// The character is held in the Ministry of Love
+    Detained,
+    /// The player has to create a character first
+    NoCharacter,
+    /// The character or name already exists
+    CharacterExists,
+    /// The destination can't be reached from here
+    InvalidMove,
+    /// No such NPC, player, location, topic or text, or it isn't here
+    UnknownTarget,
+    /// The character isn't carrying what the action needs
+    MissingItem,
+    /// The action makes no sense in the character's situation
+    InvalidAction,
+    /// The server's own state is inconsistent
+    Internal,
+    /// The server doesn't handle this message yet
+    NotImplemented,
+    /// The client speaks a different protocol version; the server closes the connection
+    IncompatibleProtocol,
+}
+
+/// How urgent a `Notification` is, for clients to colour it by
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationLevel {
+    /// Something worth knowing
+    Info,
+    /// Something went the player's way
+    Success,
+    /// Something the player should act on before it gets worse
+    Warning,
+    /// The character is in serious trouble
+    Danger,
+}
+
+/// Enum for messages sent from Server to Client
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ServerMessage {
+    /// First message on every connection
     Welcome {
+        /// Id assigned to the new connection
         player_id: Uuid,
-        initial_game_state: GameState,
+        /// Protocol version the server speaks, `PROTOCOL_VERSION`
+        protocol_version: u32,
+        /// Snapshot of the world at connect time
+        initial_game_state: PublicGameState,
     },
+    /// The player's character was created or restored; sent only to that player
+    CharacterCreated {
+        /// The player's new character
+        character: Character,
+    },
+    /// Another player created a character
     PlayerJoined {
+        /// Id of the joining player
         player_id: Uuid,
-        character: Character,
+        /// What others can see of the new character
+        profile: PublicProfile,
+    },
+    /// A player changed their character's name or occupation
+    ProfileUpdated {
+        /// Id of the player
+        player_id: Uuid,
+        /// What others can see of the character after the change
+        profile: PublicProfile,
     },
+    /// A player left the game
     PlayerLeft {
+        /// Id of the departed player
+        player_id: Uuid,
+    },
+    /// A player changed location
+    PlayerMoved {
+        /// Id of the player who moved
         player_id: Uuid,
+        /// Location they left
+        from: String,
+        /// Location they arrived at
+        to: String,
+    },
+    /// The in-game clock moved on to a new hour
+    TimePulse {
+        /// Current in-game day, starting at 1
+        day: u32,
+        /// In-game calendar date
+        current_date: String,
+        /// Hour of the day, 0-23
+        hour: u8,
+    },
+    /// What everyone can see of the game, sent to every client
+    GameStateUpdate(PublicGameState),
+    /// Text description of events
+    NarrativeUpdate(String), // Text description of events
+    /// System or gameplay feedback, kept apart from the story so clients can style it
+    Notification {
+        /// How urgent it is
+        level: NotificationLevel,
+        /// What to tell the player
+        text: String,
+    },
+    /// Request could not be processed
+    Error {
+        /// What kind of problem it was, for clients to act on
+        code: ErrorCode,
+        /// Explanation to show the player
+        message: String,
+    },
+    /// The player's own character, sent only to that player in answer to `RequestSelf`
+    SelfState(Character),
+    /// The player's own stats changed; sent only to that player
+    StatUpdate {
+        /// Loyalty to the Party (0-100)
+        loyalty: u8,
+        /// Suspicion level (0-100)
+        suspicion: u8,
+        /// Thoughtcrime level (0-100)
+        thoughtcrime: u8,
+        /// Health (0-100)
+        health: u8,
+        /// Wounds still holding health back
+        injuries: Vec<Injury>,
+        /// Item names carried, forbidden or not
+        inventory: Vec<String>,
+        /// Morale (0-100)
+        morale: u8,
+        /// Rebellion score (0-100)
+        rebellion_score: u8,
+        /// Economic freedom score (0-100)
+        economic_freedom_score: u8,
+        /// Subject -> proficiency (0-100) learned from NPCs
+        #[serde(serialize_with = "sorted_map")]
+        skills: HashMap<String, u8>,
+    },
+    /// Authoritative flight state of an aircraft that moved, sent to every client at the
+    /// broadcast rate
+    PlayerState {
+        /// Whose aircraft this is
+        player_id: Uuid,
+        /// Position after the tick
+        #[serde(with = "crate::nalgebra_serde::point3")]
+        position: Point3<f32>,
+        /// Orientation after the tick
+        #[serde(with = "crate::nalgebra_serde::unit_quaternion")]
+        orientation: UnitQuaternion<f32>,
+        /// `seq` of the last FlyInput applied
+        last_input_seq: u32,
+    },
+    /// The player's wing has stalled and lift has collapsed
+    StallWarning {
+        /// Airspeed at the moment of the stall (m/s)
+        airspeed: f32,
+        /// Angle of attack at the moment of the stall (radians)
+        angle_of_attack: f32,
     },
-    GameStateUpdate(GameState), // Send the whole state (can be optimized later)
-    NarrativeUpdate(String),    // Text description of events
-    Error(String),
 
     // --- Anarcho-Capitalist Mechanics Messages ---
+    /// Forbidden texts discovered by a search
     ForbiddenTextFound {
-        texts: Vec<String>, // List of text IDs found at current location
+        /// List of text IDs found at current location
+        texts: Vec<String>,
     },
+    /// Result of reading a forbidden text
     ForbiddenTextContent {
+        /// The text that was read
         text: ForbiddenText,
+        /// Understanding gained
         understanding_increase: u8,
+        /// Suspicion gained
         suspicion_increase: u8,
     },
+    /// Result of sharing knowledge with an NPC
     KnowledgeShared {
+        /// Whether the NPC was receptive
         success: bool,
+        /// How the NPC reacted
         target_reaction: String,
+        /// What happened as a result
         consequence: String,
     },
+    /// The telescreen noticed something
     TeleScreenWarning {
-        message: String, // Warning about thoughtcrime
-        severity: u8,    // 1-5 severity level
+        /// Warning about thoughtcrime
+        message: String,
+        /// 1-5 severity level
+        severity: u8,
     },
+    /// Result of a voluntary exchange with an NPC
     VoluntaryExchangeResult {
+        /// Whether the exchange happened
         success: bool,
+        /// Description of the outcome
         result_message: String,
+        /// Item received, if any
         gained_item: Option<String>,
+        /// Item given away, if any
         lost_item: Option<String>,
     },
     // --- End Anarcho-Capitalist Mechanics Messages ---
+    /// What the player can see of a character they examined
+    PlayerProfile {
+        /// The public view of that character
+        profile: PublicProfile,
+    },
+    /// What the character can do where they are, by the same rules the server enforces
+    AvailableActions {
+        /// Each action possible right now, in no particular order
+        actions: Vec<AvailableAction>,
+    },
+    /// Every location and how they connect
+    WorldMap {
+        /// Locations sorted by name
+        locations: Vec<MapLocation>,
+    },
+    /// Several messages produced by one game loop tick, to be applied in order and together
+    BatchUpdate {
+        /// The tick's messages, oldest first
+        messages: Vec<ServerMessage>,
+    },
+    /// Answer to a `Ping`
+    Pong {
+        /// The `client_time` the ping carried, for the client to work out the round trip
+        client_time: u64,
+        /// Server clock when the ping was answered, in milliseconds since the Unix epoch
+        server_time: u64,
+    },
+    /// Recent events the player could have witnessed, oldest first
+    EventLog {
+        /// The events, at most the number asked for
+        events: Vec<WorldEvent>,
+    },
+    /// Top characters by score, best first
+    Leaderboard {
+        /// Public standings, without private stats like suspicion
+        entries: Vec<LeaderboardEntry>,
+    },
+    /// Where the character's texts are hidden
+    HiddenTextList {
+        /// Text id -> where it is hidden
+        texts: HashMap<String, HiddenText>,
+    },
+    /// How NPCs feel about the character
+    RelationshipReport {
+        /// NPC name -> Trust level (-100 to 100)
+        relationships: HashMap<String, i8>,
+    },
+    /// An NPC speaks and waits for an answer
+    DialoguePrompt {
+        /// Who is speaking
+        npc_name: String,
+        /// What they say
+        text: String,
+        /// Possible answers; reply with the index in `DialogueChoice`
+        options: Vec<String>,
+    },
+    /// What the character sees at their location
+    LocationReport {
+        /// Flavour text of the location
+        description: String,
+        /// Locations reachable from here
+        connections: Vec<String>,
+        /// Names of NPCs here
+        npcs_present: Vec<String>,
+        /// Names of other players' characters here
+        other_players_present: Vec<String>,
+        /// Vague hint that forbidden texts are hidden here, if any are
+        texts_hint: Option<String>,
+    },
 }
 
-// Enum for messages sent from Client to Server
+impl ServerMessage {
+    /// A `Pong` answering a ping sent at `client_time`, stamped with the server clock
+    pub fn pong(client_time: u64) -> Self {
+        let server_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        ServerMessage::Pong {
+            client_time,
+            server_time,
+        }
+    }
+
+    /// A `Notification` at the given level
+    pub fn notify(level: NotificationLevel, text: impl Into<String>) -> Self {
+        ServerMessage::Notification {
+            level,
+            text: text.into(),
+        }
+    }
+
+    /// An `Error` with the given code and message
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        ServerMessage::Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Enum for messages sent from Client to Server
+///
+/// Unknown fields are rejected, so a client can't slip an absolute position or orientation into
+/// a message: where aircraft are is the server's alone to decide.
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
 pub enum ClientMessage {
+    /// Announce the protocol version the client speaks; on a mismatch the server replies with
+    /// an error and closes the connection
+    Hello {
+        /// The client's `PROTOCOL_VERSION`
+        protocol_version: u32,
+    },
+    /// Create the character for this connection
     RequestCharacterCreation {
+        /// Character name
         name: String,
+        /// Party job title
         occupation: String,
     },
+    /// Rename the character or change its occupation; `None` leaves a field as it is
+    UpdateProfile {
+        /// New character name
+        #[serde(default)]
+        name: Option<String>,
+        /// New occupation, refused unless the server allows occupation changes
+        #[serde(default)]
+        occupation: Option<String>,
+    },
+    /// For RPG map movement
     MoveRequest {
         // For RPG map movement
+        /// Name of a connected location
         target_location: String,
+        /// Go even if the destination is dangerous; otherwise the server only warns
+        #[serde(default)]
+        commit: bool,
     },
+    /// For 3D flight control
     FlyInput {
         // For 3D flight control
-        pitch: f32,           // -1.0 to 1.0
-        roll: f32,            // -1.0 to 1.0
-        yaw: f32,             // -1.0 to 1.0
-        throttle_change: f32, // -1.0 to 1.0 (change delta)
+        /// -1.0 to 1.0
+        pitch: f32,
+        /// -1.0 to 1.0
+        roll: f32,
+        /// -1.0 to 1.0
+        yaw: f32,
+        /// -1.0 to 1.0 (change delta)
+        throttle_change: f32,
+        /// Client-chosen increasing number echoed back once the input is applied
+        #[serde(default)]
+        seq: u32,
+    },
+    /// Turn the auto-level flight assist on or off
+    SetFlightAssist {
+        /// Whether the assist should level the wings while the stick is left alone
+        enabled: bool,
     },
+    /// Talk to or otherwise interact with an NPC
     InteractRequest {
+        /// NPC to interact with
         npc_name: String,
+        /// Which interaction choice was picked
         interaction_type: u8,
     }, // interaction_type maps to choices
+    /// Write an entry in the secret journal
     JournalWriteRequest {
+        /// Entry text
         entry: String,
     },
+    /// Search the current location
     SearchRequest,
+    /// Perform Party work
     WorkRequest,
+    /// Rewrite a record at the Ministry of Truth; only Records Department Workers may
+    AlterRecord {
+        /// Record to rewrite, e.g. "times-1983-12-19"
+        record_id: String,
+        /// What the record has always said
+        new_content: String,
+    },
+    /// Rest to recover health
     RestRequest,
+    /// Spend time alone with one's thoughts to recover morale; dangerous, as private thought is
+    /// thoughtcrime
+    Reflect,
+    /// Join the Two Minutes Hate in progress at the character's location
+    ParticipateInHate,
 
     // --- Anarcho-Capitalist Mechanics Messages ---
+    /// Look for forbidden texts at the current location
     SearchForForbiddenTexts,
+    /// Read a forbidden text
     ReadForbiddenText {
+        /// Text to read
         text_id: String,
     },
+    /// Hide a forbidden text, or the diary when `text_id` is `"journal"`
     HideForbiddenText {
+        /// Text to hide
         text_id: String,
-        hiding_place: String, // where to hide it (e.g., "under floorboard")
+        /// where to hide it (e.g., "under floorboard")
+        hiding_place: String,
     },
+    /// Destroy a forbidden text
     DestroyForbiddenText {
+        /// Text to destroy
+        text_id: String,
+    },
+    /// Take a hidden text back from its hiding place at the current location
+    RetrieveHiddenText {
+        /// Text to retrieve
         text_id: String,
     },
+    /// Ask where the character's texts are hidden
+    ListHiddenTexts,
+    /// Spend time internalizing a topic
     MemorizeForbiddenKnowledge {
-        topic: String,     // Which concept to focus on
-        time_invested: u8, // 1-10 hours
+        /// Which concept to focus on
+        topic: String,
+        /// 1-10 hours
+        time_invested: u8,
     },
+    /// Try to spread an idea to an NPC
     ShareForbiddenKnowledge {
+        /// NPC to talk to
         target_npc: String,
+        /// Topic to share
         knowledge_topic: String,
+        /// How to bring it up
         approach: SharingApproach,
     },
+    /// Propose a trade to an NPC
     VoluntaryExchange {
+        /// NPC to trade with
         target_npc: String,
+        /// Item offered
         offer: String,
+        /// Item requested
         request: String,
     },
+    /// Attempt to disable the local telescreen
     DisableTelescreen {
-        method: String, // How the player is attempting to disable surveillance
+        /// How the player is attempting to disable surveillance
+        method: String,
     },
     // --- End Anarcho-Capitalist Mechanics Messages ---
+    /// Ask how NPCs currently feel about the character
+    QueryRelationships,
+    /// Ask for a fresh copy of the player's own character, e.g. to resync after a reconnect
+    RequestSelf,
+    /// Ask for the top characters by score
+    RequestLeaderboard,
+    /// Log out on purpose; the server closes the connection once the character is dealt with
+    Disconnect {
+        /// Keep the character so it can be reclaimed by name later; otherwise it is discarded
+        save: bool,
+    },
+    /// Ask for recent news, to catch up after joining or reconnecting
+    RequestEventLog {
+        /// Most events to send; capped at `EVENT_LOG_CAPACITY`
+        limit: usize,
+    },
+    /// Ask what the character can do where they are
+    QueryActions,
+    /// Ask for the location graph, which stays the same for the whole session
+    RequestMap,
+    /// Measure latency; answered at once with a `Pong`, without waiting on the game state
+    Ping {
+        /// Any client clock reading, echoed back in the `Pong`
+        client_time: u64,
+    },
+    /// Ask what is at the character's location
+    LookAround,
+    /// Feed the cat, using up a food item, and nurse it back to health
+    CareForCat,
+    /// Call a lost cat back from a neighbouring location
+    CallCat,
+    /// Denounce another player to the Thought Police
+    ReportPlayer {
+        /// Name of the character being reported
+        target_name: String,
+        /// What they are accused of
+        accusation: String,
+    },
+    /// Look over another player at the same location
+    ExaminePlayer {
+        /// Name of the character to examine
+        target_name: String,
+    },
+    /// Hand a forbidden text to another player at the same location
+    GiveText {
+        /// Name of the character receiving the text
+        target_name: String,
+        /// Id of the text being passed on
+        text_id: String,
+    },
+    /// Operator command; needs the server's admin token
+    AdminCommand {
+        /// Must match the configured `admin_token`
+        token: String,
+        /// What to do
+        command: AdminCommand,
+    },
+    /// Start or resume a conversation with an NPC at the character's location
+    StartDialogue {
+        /// Who to talk to
+        npc_name: String,
+    },
+    /// Answer the NPC's current dialogue prompt
+    DialogueChoice {
+        /// Who is being answered
+        npc_name: String,
+        /// Index into the prompt's options
+        option_id: usize,
+    },
+    /// Ask an NPC at the character's location for a lesson; takes the day's free hours
+    LearnFromNpc {
+        /// Who to learn from
+        npc_name: String,
+        /// What to learn, e.g. "Newspeak"
+        subject: String,
+    },
+    /// Travel to any reachable location, one stop per tick
+    TravelTo {
+        /// Final location of the journey
+        destination: String,
+    },
+    /// Throw away an item from the inventory
+    DropItem {
+        /// Item name
+        item: String,
+    },
+    /// Use an item from the inventory
+    UseItem {
+        /// Item name
+        item: String,
+    },
+    /// List the inventory with item descriptions
+    InspectInventory,
+}
+
+impl ClientMessage {
+    /// Every free-text field the message carries, for checking their length
+    pub fn texts(&self) -> Vec<&str> {
+        use ClientMessage::*;
+        match self {
+            RequestCharacterCreation { name, occupation } => vec![name, occupation],
+            UpdateProfile { name, occupation } => name.iter().chain(occupation).collect(),
+            MoveRequest {
+                target_location, ..
+            } => vec![target_location],
+            InteractRequest { npc_name, .. }
+            | StartDialogue { npc_name }
+            | DialogueChoice { npc_name, .. } => vec![npc_name],
+            JournalWriteRequest { entry } => vec![entry],
+            AlterRecord {
+                record_id,
+                new_content,
+            } => vec![record_id, new_content],
+            ReadForbiddenText { text_id }
+            | DestroyForbiddenText { text_id }
+            | RetrieveHiddenText { text_id } => vec![text_id],
+            HideForbiddenText {
+                text_id,
+                hiding_place,
+            } => vec![text_id, hiding_place],
+            MemorizeForbiddenKnowledge { topic, .. } => vec![topic],
+            ShareForbiddenKnowledge {
+                target_npc,
+                knowledge_topic,
+                ..
+            } => vec![target_npc, knowledge_topic],
+            VoluntaryExchange {
+                target_npc,
+                offer,
+                request,
+            } => vec![target_npc, offer, request],
+            DisableTelescreen { method } => vec![method],
+            ReportPlayer {
+                target_name,
+                accusation,
+            } => vec![target_name, accusation],
+            ExaminePlayer { target_name } => vec![target_name],
+            GiveText {
+                target_name,
+                text_id,
+            } => vec![target_name, text_id],
+            ClientMessage::AdminCommand { token, command } => {
+                let mut texts = vec![token];
+                texts.extend(command.texts());
+                texts
+            }
+            TravelTo { destination } => vec![destination],
+            LearnFromNpc { npc_name, subject } => vec![npc_name, subject],
+            DropItem { item } | UseItem { item } => vec![item],
+            Hello { .. }
+            | FlyInput { .. }
+            | SetFlightAssist { .. }
+            | SearchRequest
+            | WorkRequest
+            | RestRequest
+            | Reflect
+            | ParticipateInHate
+            | SearchForForbiddenTexts
+            | ListHiddenTexts
+            | QueryRelationships
+            | RequestSelf
+            | RequestLeaderboard
+            | Disconnect { .. }
+            | RequestEventLog { .. }
+            | QueryActions
+            | RequestMap
+            | Ping { .. }
+            | LookAround
+            | CareForCat
+            | CallCat
+            | InspectInventory => Vec::new(),
+        }
+        .into_iter()
+        .map(String::as_str)
+        .collect()
+    }
+}
+
+/// World events and fixes an admin can trigger on demand
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum AdminCommand {
+    /// Switch the enemy between Eurasia and Eastasia
+    ForceEnemySwitch,
+    /// Hold a Two Minutes Hate now
+    TwoMinutesHate,
+    /// Set the weekly chocolate ration
+    SetRation {
+        /// New ration in grams
+        amount: u8,
+    },
+    /// Move a character anywhere, ignoring connections
+    Teleport {
+        /// Character name
+        player: String,
+        /// Destination location
+        location: String,
+    },
+    /// Hide a forbidden text at a location
+    SpawnText {
+        /// Location to hide it at
+        location: String,
+        /// Key into `WorldState.forbidden_texts`
+        text_id: String,
+    },
+}
+
+impl AdminCommand {
+    // The command's free-text fields, for `ClientMessage::texts`
+    fn texts(&self) -> Vec<&String> {
+        match self {
+            AdminCommand::Teleport { player, location } => vec![player, location],
+            AdminCommand::SpawnText { location, text_id } => vec![location, text_id],
+            AdminCommand::ForceEnemySwitch
+            | AdminCommand::TwoMinutesHate
+            | AdminCommand::SetRation { .. } => Vec::new(),
+        }
+    }
 }
 
 // --- Additional Anarcho-Capitalist types ---
@@ -526,27 +1996,41 @@ pub enum ClientMessage {
 /// Different approaches to sharing forbidden knowledge
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum SharingApproach {
-    Subtle,      // Reduced chance of detection but less understanding
-    Direct,      // Better understanding but higher risk
-    Metaphoric,  // Uses allegories and references
-    Questioning, // Socratic method, question Party dogma
+    /// Reduced chance of detection but less understanding
+    Subtle,
+    /// Better understanding but higher risk
+    Direct,
+    /// Uses allegories and references
+    Metaphoric,
+    /// Socratic method, question Party dogma
+    Questioning,
 }
 
 /// Types of consequences for engaging with forbidden knowledge
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum ThoughtcrimeConsequence {
+    /// Nothing happens
     None,
+    /// Suspicion rises
     Suspicion {
+        /// Suspicion added
         amount: u8,
     },
+    /// The character is watched more closely
     Surveillance {
+        /// How long the surveillance lasts
         duration_days: u8,
     },
+    /// The character is taken in for questioning
     Interrogation {
+        /// Where the interrogation happens
         location: String,
+        /// Who conducts it
         interrogator: String,
     },
+    /// The character is arrested
     Arrest {
+        /// Charge laid against the character
         reason: String,
     },
 }
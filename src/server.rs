@@ -1,29 +1,226 @@
 use clap::Parser;
-use std::net::{IpAddr, /* Ipv4Addr, */ SocketAddr};
+use log::LevelFilter;
+use std::net::IpAddr;
+use std::path::PathBuf;
 
 // Import the server logic from our library crate
-use flight_sim::run_server;
+use flight_sim::{access::CidrBlock, replay, run_server, ServerConfig, WorldState};
 
 /// Flight Simulator Server
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    /// IP address to bind to
-    #[clap(short, long, value_parser, default_value = "0.0.0.0")]
-    ip: IpAddr,
+    /// TOML config file; flags given on the command line override its values
+    #[clap(short, long, value_parser)]
+    config: Option<PathBuf>,
 
-    /// Port to bind to
-    #[clap(short, long, value_parser, default_value_t = 8080)]
-    port: u16,
+    /// Log verbosity (off, error, warn, info, debug, trace); RUST_LOG takes precedence
+    #[clap(long, value_parser, default_value_t = LevelFilter::Info)]
+    log_level: LevelFilter,
+
+    /// IP address to bind to [default: 0.0.0.0]
+    #[clap(short, long, value_parser)]
+    ip: Option<IpAddr>,
+
+    /// Port to bind to [default: 8080]
+    #[clap(short, long, value_parser)]
+    port: Option<u16>,
+
+    /// Address ranges allowed to connect, comma-separated CIDR blocks [default: anyone]
+    #[clap(long, value_parser, value_delimiter = ',')]
+    allow_cidr: Option<Vec<CidrBlock>>,
+
+    /// Game loop tick rate in Hz, clamped to 1-240 [default: 30]
+    #[clap(long, value_parser)]
+    tick_hz: Option<f32>,
+
+    /// Rate in Hz that moving aircraft are sent to clients, at most the tick rate [default: 10]
+    #[clap(long, value_parser)]
+    broadcast_hz: Option<f32>,
+
+    /// Most extra ticks run at once when the game loop falls behind, 0 never catches up [default: 5]
+    #[clap(long, value_parser)]
+    max_catch_up_ticks: Option<u32>,
+
+    /// Maximum number of simultaneous connections [default: 64]
+    #[clap(long, value_parser)]
+    max_players: Option<usize>,
+
+    /// Base wind strength in m/s, 0 disables wind and turbulence [default: 2]
+    #[clap(long, value_parser)]
+    wind: Option<f32>,
+
+    /// File to load the world from at startup and save it to on shutdown; a .bin extension saves a binary snapshot
+    #[clap(long, value_parser)]
+    save_file: Option<PathBuf>,
+
+    /// Minutes between autosaves to --save-file, 0 disables autosave [default: 5]
+    #[clap(long, value_parser)]
+    autosave_minutes: Option<u64>,
+
+    /// Keep characters of disconnected players so they can be reclaimed by name [default: true]
+    #[clap(long, action = clap::ArgAction::Set)]
+    restore_characters: Option<bool>,
+
+    /// Seconds a disconnected player's character lingers before it is removed [default: 30]
+    #[clap(long, value_parser)]
+    disconnect_grace_secs: Option<u64>,
+
+    /// Seconds without a message before an idle player is disconnected, 0 disables [default: 900]
+    #[clap(long, value_parser)]
+    idle_timeout_secs: Option<u64>,
+
+    /// Party tasks needed before non-pilots can open the throttle, 0 lets everyone fly [default: 5]
+    #[clap(long, value_parser)]
+    flight_min_tasks: Option<u32>,
+
+    /// Let players change their occupation after creation [default: false]
+    #[clap(long, action = clap::ArgAction::Set)]
+    allow_occupation_change: Option<bool>,
+
+    /// Deflate large messages for clients that ask for it; false eases debugging [default: true]
+    #[clap(long, action = clap::ArgAction::Set)]
+    compress: Option<bool>,
+
+    /// Directory the browser client is served from [default: web]
+    #[clap(long, value_parser)]
+    web_dir: Option<PathBuf>,
+
+    /// Token required in the x-admin-token header of /state
+    #[clap(long, value_parser)]
+    admin_token: Option<String>,
+
+    /// Seed for random game events, for reproducible runs
+    #[clap(long, value_parser)]
+    seed: Option<u64>,
+
+    /// Record every client message to this file for --replay
+    #[clap(long, value_parser)]
+    record: Option<PathBuf>,
+
+    /// Play a recording back against a fresh world with the same --seed, print the final state as JSON and exit
+    #[clap(long, value_parser)]
+    replay: Option<PathBuf>,
+}
+
+impl Args {
+    // Command-line flags take precedence over the config file
+    fn apply_to(self, config: &mut ServerConfig) {
+        if let Some(ip) = self.ip {
+            config.ip = ip;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(tick_hz) = self.tick_hz {
+            config.tick_hz = tick_hz;
+        }
+        if let Some(broadcast_hz) = self.broadcast_hz {
+            config.broadcast_hz = broadcast_hz;
+        }
+        if let Some(max_catch_up_ticks) = self.max_catch_up_ticks {
+            config.max_catch_up_ticks = max_catch_up_ticks;
+        }
+        if let Some(max_players) = self.max_players {
+            config.max_players = max_players;
+        }
+        if let Some(wind) = self.wind {
+            config.wind = wind;
+        }
+        if let Some(save_file) = self.save_file {
+            config.save_file = Some(save_file);
+        }
+        if let Some(autosave_minutes) = self.autosave_minutes {
+            config.autosave_minutes = autosave_minutes;
+        }
+        if let Some(restore_characters) = self.restore_characters {
+            config.restore_characters = restore_characters;
+        }
+        if let Some(disconnect_grace_secs) = self.disconnect_grace_secs {
+            config.disconnect_grace_secs = disconnect_grace_secs;
+        }
+        if let Some(idle_timeout_secs) = self.idle_timeout_secs {
+            config.idle_timeout_secs = idle_timeout_secs;
+        }
+        if let Some(flight_min_tasks) = self.flight_min_tasks {
+            config.flight_min_tasks = flight_min_tasks;
+        }
+        if let Some(allow_occupation_change) = self.allow_occupation_change {
+            config.allow_occupation_change = allow_occupation_change;
+        }
+        if let Some(compress) = self.compress {
+            config.compress = compress;
+        }
+        if let Some(web_dir) = self.web_dir {
+            config.web_dir = web_dir;
+        }
+        if let Some(allow_cidr) = self.allow_cidr {
+            config.allow_cidr = allow_cidr;
+        }
+        if let Some(admin_token) = self.admin_token {
+            config.admin_token = Some(admin_token);
+        }
+        if let Some(seed) = self.seed {
+            config.seed = Some(seed);
+        }
+        if let Some(record) = self.record {
+            config.record_file = Some(record);
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
 
-    // Construct the socket address
-    let addr = SocketAddr::new(args.ip, args.port);
+    // Apply the requested level first so RUST_LOG can still override it
+    env_logger::Builder::new()
+        .filter_level(args.log_level)
+        .parse_default_env()
+        .format_timestamp_micros()
+        .init();
+
+    // Start from the config file if one was given, otherwise the built-in defaults
+    let mut config = match &args.config {
+        Some(path) => match ServerConfig::load_from_path(path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Failed to load config {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => ServerConfig::default(),
+    };
+    let replay_path = args.replay.clone();
+    args.apply_to(&mut config);
+
+    // A broken scenario should stop the server rather than quietly start the built-in world
+    if let Some(path) = &config.world.scenario_file {
+        if let Err(e) = WorldState::from_config(path) {
+            eprintln!("Failed to load scenario {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(path) = replay_path {
+        let entries = match replay::load(&path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Failed to load recording {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+        let state = replay::replay(&entries, &config);
+        match serde_json::to_string_pretty(&state) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Failed to serialize the replayed state: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
     // Run the server using the function from the library
-    run_server(addr).await;
+    run_server(config).await;
 }
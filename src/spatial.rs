@@ -0,0 +1,87 @@
+//! Uniform grid for finding which players are near a point.
+
+use nalgebra::{Point3, Vector3};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+type Cell = (i32, i32, i32);
+
+/// Player positions bucketed into cubic cells, rebuilt once per tick
+#[derive(Debug, Clone)]
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<(Uuid, Point3<f32>)>>,
+}
+
+impl SpatialIndex {
+    /// Empty index with cells `cell_size` metres wide.
+    ///
+    /// Queries are cheapest when the cell size is close to the usual query radius.
+    pub fn new(cell_size: f32) -> Self {
+        assert!(
+            cell_size.is_finite() && cell_size > 0.0,
+            "cell size must be positive"
+        );
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Remove every entry
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Add `id` at `position`; positions that aren't finite are ignored
+    pub fn insert(&mut self, id: Uuid, position: Point3<f32>) {
+        if !position.iter().all(|c| c.is_finite()) {
+            return;
+        }
+        self.cells
+            .entry(self.cell_of(&position))
+            .or_default()
+            .push((id, position));
+    }
+
+    /// Ids of every entry within `radius` of `point`, including one at `point` itself
+    pub fn query_radius(&self, point: &Point3<f32>, radius: f32) -> Vec<Uuid> {
+        if !radius.is_finite() || radius < 0.0 || !point.iter().all(|c| c.is_finite()) {
+            return Vec::new();
+        }
+        let min = self.cell_of(&(point - Vector3::repeat(radius)));
+        let max = self.cell_of(&(point + Vector3::repeat(radius)));
+        let span = |lo: i32, hi: i32| u64::from(hi.abs_diff(lo)) + 1;
+        let cells_in_range = span(min.0, max.0)
+            .saturating_mul(span(min.1, max.1))
+            .saturating_mul(span(min.2, max.2));
+        let within = |&&(_, position): &&(Uuid, Point3<f32>)| (position - point).norm() <= radius;
+
+        // A radius covering more cells than are occupied is cheaper to answer by scanning them all
+        if cells_in_range > self.cells.len() as u64 {
+            return self
+                .cells
+                .values()
+                .flatten()
+                .filter(within)
+                .map(|(id, _)| *id)
+                .collect();
+        }
+        let mut found = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(entries) = self.cells.get(&(x, y, z)) {
+                        found.extend(entries.iter().filter(within).map(|(id, _)| *id));
+                    }
+                }
+            }
+        }
+        found
+    }
+
+    fn cell_of(&self, position: &Point3<f32>) -> Cell {
+        let index = |c: f32| (c / self.cell_size).floor() as i32;
+        (index(position.x), index(position.y), index(position.z))
+    }
+}
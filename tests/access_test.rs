@@ -0,0 +1,54 @@
+use flight_sim::access::{is_allowed, CidrBlock};
+use flight_sim::ServerConfig;
+use std::net::IpAddr;
+
+fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+}
+
+#[test]
+fn blocks_match_addresses_under_their_prefix() {
+    let block: CidrBlock = "10.1.0.0/16".parse().unwrap();
+    assert!(block.contains(&ip("10.1.200.3")));
+    assert!(!block.contains(&ip("10.2.0.1")));
+    // IPv4 clients reaching a dual-stack listener arrive mapped into IPv6
+    assert!(block.contains(&ip("::ffff:10.1.0.9")));
+
+    let loopback: CidrBlock = "::1".parse().unwrap();
+    assert!(loopback.contains(&ip("::1")));
+    assert!(!loopback.contains(&ip("::2")));
+
+    let everyone: CidrBlock = "0.0.0.0/0".parse().unwrap();
+    assert!(everyone.contains(&ip("203.0.113.7")));
+}
+
+#[test]
+fn malformed_blocks_are_rejected() {
+    for bad in [
+        "10.0.0.0/33",
+        "::/129",
+        "10.0.0/8",
+        "example.com/8",
+        "10.0.0.0/x",
+    ] {
+        assert!(bad.parse::<CidrBlock>().is_err(), "{} parsed", bad);
+    }
+}
+
+#[test]
+fn empty_allowlist_lets_everyone_in() {
+    assert!(is_allowed(&[], None));
+    assert!(is_allowed(&[], Some(&ip("198.51.100.1"))));
+
+    let allowlist = ["127.0.0.0/8".parse().unwrap()];
+    assert!(is_allowed(&allowlist, Some(&ip("127.0.0.1"))));
+    assert!(!is_allowed(&allowlist, Some(&ip("198.51.100.1"))));
+    assert!(!is_allowed(&allowlist, None));
+}
+
+#[test]
+fn allowlist_is_read_from_config() {
+    let config: ServerConfig = toml::from_str(r#"allow_cidr = ["192.168.0.0/24"]"#).unwrap();
+    assert_eq!(config.allow_cidr, vec!["192.168.0.0/24".parse().unwrap()]);
+    assert!(toml::from_str::<ServerConfig>(r#"allow_cidr = ["192.168.0.0/99"]"#).is_err());
+}
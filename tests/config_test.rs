@@ -0,0 +1,147 @@
+use flight_sim::physics::DragModel;
+use flight_sim::{ServerConfig, WorldState};
+use std::path::Path;
+
+#[test]
+fn shipped_config_matches_built_in_defaults() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/server.toml");
+    let config = ServerConfig::load_from_path(&path).unwrap();
+
+    assert_eq!(config, ServerConfig::default());
+}
+
+#[test]
+fn world_overrides_apply_to_built_in_world() {
+    let config: ServerConfig = toml::from_str(
+        r#"
+        port = 9000
+
+        [world]
+        chocolate_ration = 20
+        current_enemy = "Eastasia"
+
+        [world.npc_trust]
+        "Julia" = -10
+
+        [world.locations."Golden Country"]
+        name = "Golden Country"
+        description = "A pasture from your dreams."
+        connections = ["Prole District"]
+        safety = 5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.tick_hz, ServerConfig::default().tick_hz);
+
+    let mut world = WorldState::initialize();
+    config.world.apply(&mut world);
+
+    assert_eq!(world.chocolate_ration, 20);
+    assert_eq!(world.current_enemy, "Eastasia");
+    assert_eq!(world.current_date, WorldState::initialize().current_date);
+    assert_eq!(world.npcs["Julia"].trust, -10);
+    assert_eq!(world.locations["Golden Country"].safety, 5);
+    assert!(world.locations.contains_key("Victory Mansions"));
+}
+
+#[test]
+fn unknown_setting_is_rejected() {
+    assert!(toml::from_str::<ServerConfig>("prot = 9000").is_err());
+}
+
+#[test]
+fn drag_model_is_chosen_by_name() {
+    let config: ServerConfig = toml::from_str(
+        r#"
+        [physics.drag]
+        model = "linear"
+        coeff = 0.5
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.physics.drag, DragModel::Linear { coeff: 0.5 });
+
+    assert!(
+        toml::from_str::<ServerConfig>("[physics.drag]\nmodel = \"cubic\"\ncoeff = 1.0").is_err()
+    );
+}
+
+fn write_scenario(name: &str, text: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("flight_sim_{}_{}", std::process::id(), name));
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn built_in_world_holds_together() {
+    assert_eq!(WorldState::initialize().problems(), Vec::<String>::new());
+}
+
+#[test]
+fn scenario_file_replaces_the_built_in_map() {
+    let path = write_scenario(
+        "scenario.toml",
+        r#"
+        [[locations]]
+        name = "Airstrip One"
+        description = "A rain-soaked runway."
+        connections = ["Hangar"]
+        safety = 2
+
+        [[locations]]
+        name = "Hangar"
+        description = "Rows of grounded helicopters."
+        connections = ["Airstrip One"]
+        safety = 4
+
+        [[npcs]]
+        name = "Mechanic"
+        description = "Oil to the elbows."
+        trust = 10
+        location = "Hangar"
+
+        [[forbidden_texts]]
+        id = "flight_manual"
+        title = "Unauthorised Flight Manual"
+        content = "Lift is a matter of airspeed, not Party loyalty."
+        language = "English"
+        difficulty = 2
+        suspicion_risk = 6
+
+        [text_locations]
+        "Hangar" = ["flight_manual"]
+        "#,
+    );
+    let config: ServerConfig = toml::from_str(&format!(
+        "[world]\nscenario_file = {:?}\nchocolate_ration = 20",
+        path.display().to_string()
+    ))
+    .unwrap();
+    let world = config.world.starting_world().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(world.locations.len(), 2);
+    assert!(!world.locations.contains_key("Victory Mansions"));
+    assert_eq!(world.npcs["Mechanic"].location, "Hangar");
+    assert_eq!(world.text_locations["Hangar"], vec!["flight_manual"]);
+    assert_eq!(world.chocolate_ration, 20);
+}
+
+#[test]
+fn scenario_with_dangling_references_is_rejected() {
+    let path = write_scenario(
+        "broken_scenario.json",
+        r#"{
+            "locations": [
+                {"name": "Hangar", "description": "", "connections": ["Runway"], "safety": 3}
+            ],
+            "text_locations": {"Hangar": ["lost_text"]}
+        }"#,
+    );
+    let error = WorldState::from_config(&path).unwrap_err().to_string();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(error.contains("location Hangar connects to unknown location Runway"));
+    assert!(error.contains("location Hangar hides unknown text lost_text"));
+}
@@ -1,62 +1,103 @@
 use anyhow::{Context, Result};
-use flight_sim::{ClientInput, GameState};
+use flate2::read::ZlibDecoder;
+use flight_sim::{
+    Character, ClientMessage, ErrorCode, ServerMessage, PROTOCOL_MISMATCH_CLOSE_CODE,
+    PROTOCOL_VERSION,
+};
 use futures::{SinkExt, StreamExt};
 use insta::assert_yaml_snapshot;
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+use serde::Serialize;
+use std::io::Read;
 use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::time::{timeout, Duration};
+use tokio::time::{timeout, Duration, Instant};
 use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 use url::Url;
+use uuid::Uuid;
+
+type Ws = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// The flight-relevant part of a Character, free of per-run ids
+#[derive(Serialize)]
+struct FlightSnapshot {
+    location: String,
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    orientation: UnitQuaternion<f32>,
+    throttle: f32,
+}
+
+impl From<&Character> for FlightSnapshot {
+    fn from(character: &Character) -> Self {
+        FlightSnapshot {
+            location: character.location.clone(),
+            position: character.position,
+            velocity: character.velocity,
+            orientation: character.orientation,
+            throttle: character.throttle,
+        }
+    }
+}
 
 // Helper to connect to the WebSocket server
-async fn connect_client(
-    addr: SocketAddr,
-) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, String)> {
+async fn connect_client(addr: SocketAddr) -> Result<(Ws, Uuid)> {
     let url_str = format!("ws://{}/ws", addr);
     let url = Url::parse(&url_str)?;
 
-    let (ws_stream, _response) = connect_async(url)
+    let (mut ws_stream, _response) = connect_async(url)
         .await
         .context("Failed to connect to WebSocket")?;
 
-    // Wait for the welcome message and extract client ID
-    let welcome_msg = timeout(Duration::from_secs(2), ws_stream.next())
-        .await?? // Timeout error, then WebSocket error
-        .context("Did not receive welcome message")?;
-
-    let client_id = match welcome_msg {
-        Message::Text(text) if text.starts_with("Welcome!") => text
-            .split(":")
-            .nth(1)
-            .map(|s| s.trim().to_string())
-            .context("Could not parse client ID from welcome message")?,
-        _ => anyhow::bail!("Received unexpected message instead of welcome"),
-    };
-
-    Ok((ws_stream, client_id))
+    // The first message is a JSON Welcome carrying our player id
+    match receive_message(&mut ws_stream).await? {
+        ServerMessage::Welcome { player_id, .. } => Ok((ws_stream, player_id)),
+        other => anyhow::bail!("Received {:?} instead of Welcome", other),
+    }
 }
 
-// Helper to send input
-async fn send_input(
-    ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
-    input: ClientInput,
-) -> Result<()> {
-    let msg = serde_json::to_string(&input)?;
+// Helper to send a client message
+async fn send_message(ws: &mut Ws, message: &ClientMessage) -> Result<()> {
+    let msg = serde_json::to_string(message)?;
     ws.send(Message::Text(msg))
         .await
-        .context("Failed to send input")
+        .context("Failed to send client message")
 }
 
-// Helper to receive and parse game state
-async fn receive_state(ws: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> Result<GameState> {
-    let msg = timeout(Duration::from_secs(1), ws.next())
-        .await?? // Timeout error, then WebSocket error
-        .context("Did not receive game state message")?;
+// Helper to receive and parse the next server message
+async fn receive_message(ws: &mut Ws) -> Result<ServerMessage> {
+    let msg = timeout(Duration::from_secs(2), ws.next())
+        .await? // Timeout error
+        .context("Connection closed")??; // Stream end, then WebSocket error
 
     match msg {
-        Message::Text(text) => serde_json::from_str(&text).context("Failed to parse game state"),
-        _ => anyhow::bail!("Received unexpected message type instead of game state"),
+        Message::Text(text) => {
+            serde_json::from_str(&text).context("Failed to parse server message")
+        }
+        other => anyhow::bail!("Received unexpected message type: {:?}", other),
+    }
+}
+
+// Ask for our own character until it satisfies `condition`; game state updates only carry
+// what everyone may see
+async fn wait_for_character(
+    ws: &mut Ws,
+    condition: impl Fn(&Character) -> bool,
+) -> Result<Character> {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        send_message(ws, &ClientMessage::RequestSelf).await?;
+        while Instant::now() < deadline {
+            if let ServerMessage::SelfState(character) = receive_message(ws).await? {
+                if condition(&character) {
+                    return Ok(character);
+                }
+                break;
+            }
+        }
     }
+    anyhow::bail!("Character never reached the expected state")
 }
 
 // Integration test
@@ -65,79 +106,222 @@ async fn test_basic_flight_maneuvers() -> Result<()> {
     // Start the server in a background task
     // Use a different port for testing to avoid conflicts
     let addr: SocketAddr = "127.0.0.1:8082".parse()?;
-    tokio::spawn(flight_sim::run_server(addr));
+    let config = flight_sim::ServerConfig {
+        ip: addr.ip(),
+        port: addr.port(),
+        wind: 0.0,
+        restore_characters: false,
+        flight_min_tasks: 0,
+        ..Default::default()
+    };
+    tokio::spawn(flight_sim::run_server(config));
     // Give the server a moment to start up
     tokio::time::sleep(Duration::from_millis(500)).await;
 
     // Connect a client
-    let (mut ws, _client_id) = connect_client(addr).await?;
-
-    // --- Test Scenario: Takeoff and Climb ---
-    // Receive initial state
-    let _initial_state = receive_state(&mut ws).await?;
+    let (mut ws, _) = connect_client(addr).await?;
 
-    // Apply full throttle
-    send_input(
+    // --- Test Scenario: Character Creation ---
+    send_message(
         &mut ws,
-        ClientInput {
-            throttle_up: true,
-            ..Default::default()
+        &ClientMessage::RequestCharacterCreation {
+            name: "Winston".to_string(),
+            occupation: "Records Department Worker".to_string(),
         },
     )
     .await?;
-    // Wait for physics to update (a few frames)
-    tokio::time::sleep(Duration::from_millis(100)).await;
-    let _state_after_throttle = receive_state(&mut ws).await?;
-
-    // Apply pitch up
-    send_input(
-        &mut ws,
-        ClientInput {
-            throttle_up: true,
-            pitch_up: true,
-            ..Default::default()
-        },
-    )
+    // Wait until the aircraft has settled on the ground at its starting location
+    let parked = wait_for_character(&mut ws, |c| {
+        c.velocity == Vector3::zeros() && c.position.y > 0.0
+    })
     .await?;
-    tokio::time::sleep(Duration::from_millis(200)).await;
-    let state_after_climb = receive_state(&mut ws).await?;
+    assert_eq!(parked.name, "Winston");
 
-    // --- Test Scenario: Level Flight ---
-    // Reduce pitch input
-    send_input(
+    // --- Test Scenario: Pitch Up On The Ground ---
+    // Without throttle the aircraft stays put but the nose comes up
+    send_message(
         &mut ws,
-        ClientInput {
-            throttle_up: true,
-            ..Default::default()
+        &ClientMessage::FlyInput {
+            pitch: 1.0,
+            roll: 0.0,
+            yaw: 0.0,
+            throttle_change: 0.0,
+            seq: 1,
         },
     )
     .await?;
-    tokio::time::sleep(Duration::from_millis(200)).await;
-    let state_after_level = receive_state(&mut ws).await?;
+    let pitched =
+        wait_for_character(&mut ws, |c| c.orientation != UnitQuaternion::identity()).await?;
 
-    // --- Test Scenario: Descent ---
-    // Reduce throttle and pitch down
-    send_input(
+    // --- Test Scenario: Takeoff Roll ---
+    send_message(
         &mut ws,
-        ClientInput {
-            throttle_down: true,
-            pitch_down: true,
-            ..Default::default()
+        &ClientMessage::FlyInput {
+            pitch: 0.0,
+            roll: 0.0,
+            yaw: 0.0,
+            throttle_change: 1.0,
+            seq: 2,
         },
     )
     .await?;
-    tokio::time::sleep(Duration::from_millis(200)).await;
-    let state_after_descent = receive_state(&mut ws).await?;
+    let rolling = wait_for_character(&mut ws, |c| {
+        c.throttle > 0.0 && c.position.z > parked.position.z
+    })
+    .await?;
+    assert!(rolling.position.y >= parked.position.y);
 
     // --- Snapshot Testing ---
-    // Use insta to capture the state at various points
+    // Use insta to capture the deterministic states
     // Snapshots are stored in tests/snapshots/
-    assert_yaml_snapshot!("takeoff_climb", state_after_climb);
-    assert_yaml_snapshot!("level_flight", state_after_level);
-    assert_yaml_snapshot!("descent", state_after_descent);
+    assert_yaml_snapshot!("parked", FlightSnapshot::from(&parked));
+    assert_yaml_snapshot!("pitched_on_ground", FlightSnapshot::from(&pitched));
 
     // Close the connection gracefully
     ws.close(None).await?;
 
     Ok(())
 }
+
+#[tokio::test]
+async fn large_messages_are_deflated_on_request() -> Result<()> {
+    let addr: SocketAddr = "127.0.0.1:8083".parse()?;
+    let config = flight_sim::ServerConfig {
+        ip: addr.ip(),
+        port: addr.port(),
+        ..Default::default()
+    };
+    tokio::spawn(flight_sim::run_server(config));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let url = Url::parse(&format!("ws://{}/ws?compress=deflate", addr))?;
+    let (mut ws, _response) = connect_async(url).await?;
+    let frame = timeout(Duration::from_secs(2), ws.next())
+        .await?
+        .context("Connection closed")??;
+    let Message::Binary(bytes) = frame else {
+        anyhow::bail!("Welcome was not compressed: {:?}", frame);
+    };
+    let mut json = String::new();
+    ZlibDecoder::new(bytes.as_slice()).read_to_string(&mut json)?;
+    assert!(json.len() > bytes.len());
+    assert!(matches!(
+        serde_json::from_str(&json)?,
+        ServerMessage::Welcome { .. }
+    ));
+
+    // Clients that don't ask keep getting plain JSON
+    let (mut plain, _) = connect_client(addr).await?;
+    plain.close(None).await?;
+    ws.close(None).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn outdated_clients_are_told_why_and_disconnected() -> Result<()> {
+    let addr: SocketAddr = "127.0.0.1:8084".parse()?;
+    let config = flight_sim::ServerConfig {
+        ip: addr.ip(),
+        port: addr.port(),
+        ..Default::default()
+    };
+    tokio::spawn(flight_sim::run_server(config));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // A client on the current version is left alone
+    let (mut current, _) = connect_client(addr).await?;
+    let hello = ClientMessage::Hello {
+        protocol_version: PROTOCOL_VERSION,
+    };
+    send_message(&mut current, &hello).await?;
+
+    let (mut outdated, _) = connect_client(addr).await?;
+    let hello = ClientMessage::Hello {
+        protocol_version: PROTOCOL_VERSION - 1,
+    };
+    send_message(&mut outdated, &hello).await?;
+    assert!(matches!(
+        receive_message(&mut outdated).await?,
+        ServerMessage::Error {
+            code: ErrorCode::IncompatibleProtocol,
+            ..
+        }
+    ));
+    let frame = timeout(Duration::from_secs(2), outdated.next())
+        .await?
+        .context("Connection closed")??;
+    let Message::Close(Some(close)) = frame else {
+        anyhow::bail!("Expected a close frame, got {:?}", frame);
+    };
+    assert_eq!(u16::from(close.code), PROTOCOL_MISMATCH_CLOSE_CODE);
+    assert!(close.reason.contains("Update your client"));
+
+    // The current client still gets answers
+    send_message(&mut current, &ClientMessage::RequestLeaderboard).await?;
+    assert!(matches!(
+        receive_message(&mut current).await?,
+        ServerMessage::Leaderboard { .. }
+    ));
+    current.close(None).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn pings_are_answered_with_the_client_time() -> Result<()> {
+    let addr: SocketAddr = "127.0.0.1:8085".parse()?;
+    let config = flight_sim::ServerConfig {
+        ip: addr.ip(),
+        port: addr.port(),
+        ..Default::default()
+    };
+    tokio::spawn(flight_sim::run_server(config));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let (mut ws, _) = connect_client(addr).await?;
+    let sent = Instant::now();
+    send_message(&mut ws, &ClientMessage::Ping { client_time: 1984 }).await?;
+    // Skip whatever the game loop broadcasts in the meantime
+    let (client_time, server_time) = loop {
+        if let ServerMessage::Pong {
+            client_time,
+            server_time,
+        } = receive_message(&mut ws).await?
+        {
+            break (client_time, server_time);
+        }
+    };
+    let round_trip = sent.elapsed();
+
+    assert_eq!(client_time, 1984);
+    assert!(server_time > 0);
+    assert!(round_trip < Duration::from_secs(2));
+    ws.close(None).await?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn heat_map_is_closed_without_an_admin_token() -> Result<()> {
+    let addr: SocketAddr = "127.0.0.1:8086".parse()?;
+    let config = flight_sim::ServerConfig {
+        ip: addr.ip(),
+        port: addr.port(),
+        admin_token: None,
+        ..Default::default()
+    };
+    tokio::spawn(flight_sim::run_server(config));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET /heatmap HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    timeout(Duration::from_secs(2), stream.read_to_string(&mut response)).await??;
+
+    assert!(
+        response.starts_with("HTTP/1.1 401"),
+        "unexpected response: {}",
+        response
+    );
+    Ok(())
+}
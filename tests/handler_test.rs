@@ -0,0 +1,2375 @@
+use flight_sim::metrics::Metrics;
+use flight_sim::{
+    advance_hate, available_actions, begin_interrogation, handle_client_message, handle_disconnect,
+    idle_action, lock_clients, lock_state, purge_disconnected, ApparentHealth, ArrestConsequence,
+    CatStatus, Character, ClientMessage, Clients, EndConditions, ErrorCode, GameState, IdleAction,
+    NotificationLevel, ServerConfig, ServerMessage, Simulation, TextLanguage, EVENT_LOG_CAPACITY,
+};
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, Receiver};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+// A game with one character and a client channel to capture what the server sends it
+fn setup() -> (GameState, Clients, Uuid, Receiver<Message>) {
+    let player_id = Uuid::new_v4();
+    let mut state = GameState::new();
+    state.players.insert(
+        player_id,
+        Character::new(player_id, "Winston".to_string(), "Pilot".to_string()),
+    );
+    let (sender, receiver) = mpsc::channel(ServerConfig::default().client_buffer);
+    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
+    clients.lock().unwrap().insert(player_id, sender);
+    (state, clients, player_id, receiver)
+}
+
+// A connected client that has not created a character yet
+fn connect(clients: &Clients) -> (Uuid, Receiver<Message>) {
+    let player_id = Uuid::new_v4();
+    let (sender, receiver) = mpsc::channel(ServerConfig::default().client_buffer);
+    clients.lock().unwrap().insert(player_id, sender);
+    (player_id, receiver)
+}
+
+// Add another connected character at `location`
+fn join(
+    state: &mut GameState,
+    clients: &Clients,
+    name: &str,
+    location: &str,
+) -> (Uuid, Receiver<Message>) {
+    let (player_id, receiver) = connect(clients);
+    let mut character = Character::new(player_id, name.to_string(), "Pilot".to_string());
+    character.location = location.to_string();
+    state.players.insert(player_id, character);
+    (player_id, receiver)
+}
+
+fn send(state: &mut GameState, clients: &Clients, player_id: Uuid, msg: ClientMessage) {
+    handle_client_message(player_id, msg, state, clients, &ServerConfig::default());
+}
+
+// Everything the server sent to the client so far
+fn drain(receiver: &mut Receiver<Message>) -> Vec<ServerMessage> {
+    let mut messages = Vec::new();
+    while let Ok(Message::Text(text)) = receiver.try_recv() {
+        // Unwrap a tick's batch as clients do
+        match serde_json::from_str(&text).unwrap() {
+            ServerMessage::BatchUpdate { messages: batch } => messages.extend(batch),
+            message => messages.push(message),
+        }
+    }
+    messages
+}
+
+#[test]
+fn occupation_sets_starting_location_stats_and_inventory() {
+    let (mut state, clients, _, _receiver) = setup();
+    let create = |name: &str, occupation: &str| ClientMessage::RequestCharacterCreation {
+        name: name.to_string(),
+        occupation: occupation.to_string(),
+    };
+    let (spy, _spy_receiver) = connect(&clients);
+    let (prole, _prole_receiver) = connect(&clients);
+    let (stranger, _stranger_receiver) = connect(&clients);
+
+    send(
+        &mut state,
+        &clients,
+        spy,
+        create("Parsons", "Junior Spy Instructor"),
+    );
+    send(&mut state, &clients, prole, create("Tom", "Prole"));
+    send(&mut state, &clients, stranger, create("Ampleforth", "Poet"));
+
+    let spy = &state.players[&spy];
+    assert_eq!(spy.location, "Ministry of Truth");
+    assert_eq!(spy.loyalty, 65);
+    let prole = &state.players[&prole];
+    assert_eq!(prole.location, "Prole District");
+    assert_eq!(prole.inventory, vec!["Victory Gin".to_string()]);
+    let stranger = &state.players[&stranger];
+    assert_eq!(stranger.location, "Victory Mansions");
+    assert_eq!(stranger.loyalty, 50);
+}
+
+#[test]
+fn messages_before_character_creation_get_an_error() {
+    let (mut state, clients, _, _receiver) = setup();
+    let (player_id, mut receiver) = connect(&clients);
+
+    let move_to = ClientMessage::MoveRequest {
+        target_location: "Canteen".to_string(),
+        commit: true,
+    };
+    send(&mut state, &clients, player_id, move_to);
+    send(&mut state, &clients, player_id, ClientMessage::LookAround);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { code: ErrorCode::NoCharacter, message: first }, ServerMessage::Error { code: ErrorCode::NoCharacter, message: second }]
+            if first == "Create a character first" && second == first
+    ));
+
+    let create = ClientMessage::RequestCharacterCreation {
+        name: "Syme".to_string(),
+        occupation: "Records Department Worker".to_string(),
+    };
+    send(&mut state, &clients, player_id, create);
+    assert!(state.players.contains_key(&player_id));
+}
+
+#[test]
+fn small_talk_builds_trust_with_present_npc() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    // Parsons lives in Victory Mansions, the starting location
+    let interact = ClientMessage::InteractRequest {
+        npc_name: "Parsons".to_string(),
+        interaction_type: 0,
+    };
+    send(&mut state, &clients, player_id, interact);
+    drain(&mut receiver);
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::QueryRelationships,
+    );
+
+    let report = drain(&mut receiver).into_iter().find_map(|msg| match msg {
+        ServerMessage::RelationshipReport { relationships } => Some(relationships),
+        _ => None,
+    });
+    assert_eq!(report.unwrap()["Parsons"], 5);
+}
+
+#[test]
+fn interacting_with_absent_npc_is_an_error() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let interact = ClientMessage::InteractRequest {
+        npc_name: "Julia".to_string(),
+        interaction_type: 0,
+    };
+    send(&mut state, &clients, player_id, interact);
+
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+    assert!(state.players[&player_id].relationships.is_empty());
+}
+
+#[test]
+fn bandages_dress_the_worst_injury() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    {
+        let winston = state.players.get_mut(&player_id).unwrap();
+        winston.injure("Mid-air collision", 10);
+        winston.injure("Hard landing", 40);
+        winston.inventory.push("Bandages".to_string());
+    }
+
+    send(&mut state, &clients, player_id, ClientMessage::RestRequest);
+    let winston = &state.players[&player_id];
+    assert_eq!(winston.health, 50 + 4);
+    let injuries = drain(&mut receiver)
+        .into_iter()
+        .find_map(|msg| match msg {
+            ServerMessage::StatUpdate { injuries, .. } => Some(injuries),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(injuries.len(), 2);
+
+    let use_bandages = ClientMessage::UseItem {
+        item: "Bandages".to_string(),
+    };
+    send(&mut state, &clients, player_id, use_bandages);
+    let winston = &state.players[&player_id];
+    assert_eq!(winston.injuries.len(), 1);
+    assert_eq!(winston.injuries[0].cause, "Mid-air collision");
+    assert_eq!(winston.max_health(), 95);
+    assert_eq!(winston.health, 59);
+    assert!(winston.inventory.is_empty());
+}
+
+#[test]
+fn victory_gin_heals_raises_suspicion_and_is_consumed() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    {
+        let winston = state.players.get_mut(&player_id).unwrap();
+        winston.health = 50;
+        winston.inventory.push("Victory Gin".to_string());
+    }
+
+    let use_gin = ClientMessage::UseItem {
+        item: "Victory Gin".to_string(),
+    };
+    send(&mut state, &clients, player_id, use_gin);
+
+    let winston = &state.players[&player_id];
+    assert_eq!(winston.health, 55);
+    assert_eq!(winston.suspicion, 3);
+    assert!(winston.inventory.is_empty());
+    assert!(matches!(
+        drain(&mut receiver).first(),
+        Some(ServerMessage::NarrativeUpdate(_))
+    ));
+}
+
+#[test]
+fn using_or_dropping_unowned_item_is_an_error() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let use_gin = ClientMessage::UseItem {
+        item: "Victory Gin".to_string(),
+    };
+    send(&mut state, &clients, player_id, use_gin);
+    let drop_blade = ClientMessage::DropItem {
+        item: "Razor Blade".to_string(),
+    };
+    send(&mut state, &clients, player_id, drop_blade);
+
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }, ServerMessage::Error { .. }]
+    ));
+}
+
+#[test]
+fn razor_blade_buys_trust() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    state
+        .players
+        .get_mut(&player_id)
+        .unwrap()
+        .inventory
+        .push("Razor Blade".to_string());
+
+    let offer = ClientMessage::InteractRequest {
+        npc_name: "Parsons".to_string(),
+        interaction_type: 2,
+    };
+    send(&mut state, &clients, player_id, offer);
+
+    let winston = &state.players[&player_id];
+    assert_eq!(winston.relationships["Parsons"], 15);
+    assert!(winston.inventory.is_empty());
+}
+
+#[test]
+fn hiding_the_diary_until_next_entry() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let write = |entry: &str| ClientMessage::JournalWriteRequest {
+        entry: entry.to_string(),
+    };
+    let hide = ClientMessage::HideForbiddenText {
+        text_id: "journal".to_string(),
+        hiding_place: "under the floorboard".to_string(),
+    };
+
+    send(&mut state, &clients, player_id, write("April 4th, 1984"));
+    send(&mut state, &clients, player_id, hide);
+    assert_eq!(
+        state.players[&player_id].journal_hiding_place.as_deref(),
+        Some("under the floorboard")
+    );
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        write("Down with Big Brother"),
+    );
+    assert_eq!(state.players[&player_id].journal_hiding_place, None);
+}
+
+#[test]
+fn hidden_texts_are_retrieved_where_they_were_hidden() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let text_id = "free_market".to_string();
+    let retrieve = || ClientMessage::RetrieveHiddenText {
+        text_id: "free_market".to_string(),
+    };
+    state
+        .players
+        .get_mut(&player_id)
+        .unwrap()
+        .inventory
+        .push(text_id.clone());
+
+    let hide = ClientMessage::HideForbiddenText {
+        text_id: text_id.clone(),
+        hiding_place: "behind the telescreen".to_string(),
+    };
+    send(&mut state, &clients, player_id, hide);
+    assert!(!state.players[&player_id].inventory.contains(&text_id));
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::ListHiddenTexts,
+    );
+    let listed = drain(&mut receiver).into_iter().find_map(|msg| match msg {
+        ServerMessage::HiddenTextList { texts } => Some(texts),
+        _ => None,
+    });
+    let hidden = &listed.unwrap()[&text_id];
+    assert_eq!(hidden.location, "Victory Mansions");
+    assert_eq!(hidden.hiding_place, "behind the telescreen");
+
+    // Only where it was hidden
+    state.players.get_mut(&player_id).unwrap().location = "Canteen".to_string();
+    send(&mut state, &clients, player_id, retrieve());
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+
+    state.players.get_mut(&player_id).unwrap().location = "Victory Mansions".to_string();
+    send(&mut state, &clients, player_id, retrieve());
+    let character = &state.players[&player_id];
+    assert!(character.inventory.contains(&text_id));
+    assert!(character.hidden_texts.is_empty());
+}
+
+#[test]
+fn newspeak_filter_penalizes_oldspeak_when_enabled() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let mut config = ServerConfig::default();
+    config.newspeak.enabled = true;
+
+    let entry = ClientMessage::JournalWriteRequest {
+        entry: "Freedom and justice for all.".to_string(),
+    };
+    handle_client_message(player_id, entry, &mut state, &clients, &config);
+
+    // 5 for writing plus 2 per flagged word
+    assert_eq!(state.players[&player_id].thoughtcrime, 9);
+    match drain(&mut receiver).first() {
+        Some(ServerMessage::NarrativeUpdate(text)) => {
+            assert!(text.contains("Oldspeak detected (2): freedom, justice."))
+        }
+        other => panic!("expected a narrative, got {:?}", other),
+    }
+}
+
+#[test]
+fn studying_forbidden_knowledge_feeds_rebellion() {
+    let (mut state, clients, player_id, _receiver) = setup();
+
+    let study = ClientMessage::MemorizeForbiddenKnowledge {
+        topic: "Voluntary Exchange".to_string(),
+        time_invested: 10,
+    };
+    send(&mut state, &clients, player_id, study);
+
+    let winston = &state.players[&player_id];
+    assert_eq!(winston.anarcho_knowledge["Voluntary Exchange"], 30);
+    assert_eq!(winston.thoughtcrime, 10);
+    assert_eq!(winston.rebellion_score, 6);
+}
+
+#[test]
+fn exchange_requires_owning_the_offer() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let trade = ClientMessage::VoluntaryExchange {
+        target_npc: "Parsons".to_string(),
+        offer: "Chocolate".to_string(),
+        request: "Razor Blade".to_string(),
+    };
+    send(&mut state, &clients, player_id, trade);
+
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+    assert_eq!(state.players[&player_id].voluntary_actions, 0);
+}
+
+#[test]
+fn only_inner_party_may_enter_the_ministry_of_love() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+    let enter = || ClientMessage::MoveRequest {
+        target_location: "Ministry of Love".to_string(),
+        commit: true,
+    };
+
+    send(&mut state, &clients, player_id, enter());
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+
+    state.players.get_mut(&player_id).unwrap().occupation =
+        flight_sim::INNER_PARTY_OCCUPATION.to_string();
+    send(&mut state, &clients, player_id, enter());
+    assert_eq!(state.players[&player_id].location, "Ministry of Love");
+}
+
+#[test]
+fn dangerous_move_waits_for_commitment() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let cross = |commit| ClientMessage::MoveRequest {
+        target_location: "Victory Square".to_string(),
+        commit,
+    };
+
+    send(&mut state, &clients, player_id, cross(false));
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Notification {
+            level: NotificationLevel::Warning,
+            ..
+        }]
+    ));
+    assert_eq!(state.players[&player_id].location, "Victory Mansions");
+
+    send(&mut state, &clients, player_id, cross(true));
+    assert_eq!(state.players[&player_id].location, "Victory Square");
+}
+
+#[test]
+fn travel_plans_a_route_or_reports_none() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let travel = ClientMessage::TravelTo {
+        destination: "Nowhere".to_string(),
+    };
+    send(&mut state, &clients, player_id, travel);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+    assert!(state.players[&player_id].travel_route.is_empty());
+
+    let travel = ClientMessage::TravelTo {
+        destination: "Prole District".to_string(),
+    };
+    send(&mut state, &clients, player_id, travel);
+    let character = &state.players[&player_id];
+    assert_eq!(character.location, "Victory Mansions");
+    assert_eq!(
+        character.travel_route,
+        vec!["Victory Square".to_string(), "Prole District".to_string()]
+    );
+}
+
+#[test]
+fn o_brien_hands_over_the_book_then_betrays() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+    let choose = |option_id| ClientMessage::DialogueChoice {
+        npc_name: "O'Brien".to_string(),
+        option_id,
+    };
+
+    let start = ClientMessage::StartDialogue {
+        npc_name: "O'Brien".to_string(),
+    };
+    send(&mut state, &clients, player_id, start);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::DialoguePrompt { options, .. }] if options.len() == 2
+    ));
+
+    // Accept the invitation, swear loyalty to the Brotherhood, take the book
+    for option_id in [0, 0, 0] {
+        send(&mut state, &clients, player_id, choose(option_id));
+    }
+    let character = &state.players[&player_id];
+    assert!(character.inventory.contains(&"The Book".to_string()));
+    assert_eq!(character.relationships["O'Brien"], 30);
+    assert!(character.suspicion < 100);
+
+    send(&mut state, &clients, player_id, choose(0));
+    let character = &state.players[&player_id];
+    assert_eq!(character.suspicion, 100);
+    assert!(character.dialogue_nodes.is_empty());
+}
+
+#[test]
+fn old_trader_opens_up_to_those_who_understand() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.location = "Prole District".to_string();
+    character.inventory.push("Razor Blade".to_string());
+    let start = || ClientMessage::StartDialogue {
+        npc_name: "Old Trader".to_string(),
+    };
+    let buy_coffee = || ClientMessage::VoluntaryExchange {
+        target_npc: "Old Trader".to_string(),
+        offer: "Razor Blade".to_string(),
+        request: "Real Coffee".to_string(),
+    };
+
+    send(&mut state, &clients, player_id, start());
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::DialoguePrompt { options, .. }] if options.len() == 1
+    ));
+    send(&mut state, &clients, player_id, buy_coffee());
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::VoluntaryExchangeResult { success: false, .. }]
+    ));
+    assert!(state.players[&player_id]
+        .inventory
+        .contains(&"Razor Blade".to_string()));
+
+    state
+        .players
+        .get_mut(&player_id)
+        .unwrap()
+        .economic_freedom_score = 40;
+    send(&mut state, &clients, player_id, start());
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::NarrativeUpdate(_), ServerMessage::DialoguePrompt { options, .. }] if options.len() == 3
+    ));
+    for option_id in [0, 0] {
+        let choose = ClientMessage::DialogueChoice {
+            npc_name: "Old Trader".to_string(),
+            option_id,
+        };
+        send(&mut state, &clients, player_id, choose);
+    }
+    assert!(state.players[&player_id]
+        .inventory
+        .contains(&"Real Coffee".to_string()));
+}
+
+#[test]
+fn npc_without_dialogue_has_nothing_to_say() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let start = ClientMessage::StartDialogue {
+        npc_name: "Parsons".to_string(),
+    };
+    send(&mut state, &clients, player_id, start);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+}
+
+#[test]
+fn interrogation_blocks_everything_but_answers() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.suspicion = 100;
+    character.thoughtcrime = 60;
+    character.rebellion_score = 40;
+    assert!(matches!(
+        begin_interrogation(character),
+        ServerMessage::DialoguePrompt { .. }
+    ));
+    assert_eq!(character.location, "Ministry of Love");
+
+    send(&mut state, &clients, player_id, ClientMessage::WorkRequest);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+
+    // Give in at every question
+    for _ in 0..3 {
+        let answer = ClientMessage::DialogueChoice {
+            npc_name: "O'Brien".to_string(),
+            option_id: 1,
+        };
+        send(&mut state, &clients, player_id, answer);
+    }
+    let character = &state.players[&player_id];
+    assert!(character.detention.is_none());
+    assert_eq!(character.suspicion, 0);
+    assert_eq!(character.rebellion_score, 0);
+    assert_eq!(character.loyalty, 100);
+    assert_eq!(character.location, "Victory Mansions");
+}
+
+#[test]
+fn defiance_under_interrogation_breaks_the_character() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.thoughtcrime = 60;
+    begin_interrogation(character);
+
+    // Hold out at every question
+    for _ in 0..3 {
+        let answer = ClientMessage::DialogueChoice {
+            npc_name: "O'Brien".to_string(),
+            option_id: 0,
+        };
+        send(&mut state, &clients, player_id, answer);
+    }
+    assert!(matches!(
+        state.players[&player_id].detention,
+        Some(flight_sim::ThoughtcrimeConsequence::Arrest { .. })
+    ));
+}
+
+#[test]
+fn reporting_a_suspect_shifts_suspicion_onto_them() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (suspect_id, mut suspect) = join(&mut state, &clients, "Julia", "Ministry of Truth");
+    state.players.get_mut(&suspect_id).unwrap().suspicion = 50;
+    let reporter = state.players.get_mut(&player_id).unwrap();
+    reporter.loyalty = 100;
+    reporter.suspicion = 30;
+
+    let report = ClientMessage::ReportPlayer {
+        target_name: "Julia".to_string(),
+        accusation: "keeping a diary".to_string(),
+    };
+    send(&mut state, &clients, player_id, report);
+    assert_eq!(state.players[&suspect_id].suspicion, 70);
+    assert_eq!(state.players[&player_id].suspicion, 20);
+    assert_eq!(state.players[&player_id].thoughtcrime, 0);
+    assert!(drain(&mut suspect)
+        .iter()
+        .any(|msg| matches!(msg, ServerMessage::TeleScreenWarning { .. })));
+
+    let nobody = ClientMessage::ReportPlayer {
+        target_name: "Goldstein".to_string(),
+        accusation: "treason".to_string(),
+    };
+    drain(&mut receiver);
+    send(&mut state, &clients, player_id, nobody);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+}
+
+#[test]
+fn false_reports_weigh_on_the_reporter() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    join(&mut state, &clients, "Parsons", "Victory Mansions");
+
+    let report = ClientMessage::ReportPlayer {
+        target_name: "Parsons".to_string(),
+        accusation: "thoughtcrime".to_string(),
+    };
+    send(&mut state, &clients, player_id, report);
+    assert_eq!(state.players[&player_id].thoughtcrime, 5);
+}
+
+#[test]
+fn local_narratives_stay_in_the_room() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let (_, mut neighbour) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let (_, mut prole) = join(&mut state, &clients, "Tom", "Prole District");
+    let is_narrative = |msg: &ServerMessage| matches!(msg, ServerMessage::NarrativeUpdate(_));
+
+    let interact = ClientMessage::InteractRequest {
+        npc_name: "Parsons".to_string(),
+        interaction_type: 0,
+    };
+    send(&mut state, &clients, player_id, interact);
+
+    assert!(drain(&mut neighbour).iter().any(is_narrative));
+    // The conversation changes only the speaker's own stats, so nothing reaches elsewhere
+    assert!(drain(&mut prole).is_empty());
+}
+
+#[test]
+fn event_log_catches_up_on_news_told_here_or_everywhere() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (tom, mut prole) = join(&mut state, &clients, "Tom", "Prole District");
+    let (newcomer, mut newcomer_receiver) = connect(&clients);
+    state.record_event(None, "Chocolate ration increased to 20 grams");
+    let interact = ClientMessage::InteractRequest {
+        npc_name: "Parsons".to_string(),
+        interaction_type: 0,
+    };
+    send(&mut state, &clients, player_id, interact);
+    let request = || ClientMessage::RequestEventLog { limit: 10 };
+    let texts = |messages: Vec<ServerMessage>| match &messages[..] {
+        [ServerMessage::EventLog { events }] => events
+            .iter()
+            .map(|event| event.text.clone())
+            .collect::<Vec<_>>(),
+        other => panic!("expected an event log, got {:?}", other),
+    };
+    drain(&mut receiver);
+    drain(&mut prole);
+    drain(&mut newcomer_receiver);
+
+    send(&mut state, &clients, player_id, request());
+    let here = texts(drain(&mut receiver));
+    assert_eq!(here.len(), 2);
+    assert!(here[1].contains("Parsons"));
+
+    // Elsewhere, and before creating a character, only the news told everywhere is sent
+    send(&mut state, &clients, tom, request());
+    assert_eq!(texts(drain(&mut prole)), vec![here[0].clone()]);
+    send(&mut state, &clients, newcomer, request());
+    assert_eq!(texts(drain(&mut newcomer_receiver)), vec![here[0].clone()]);
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::RequestEventLog { limit: 1 },
+    );
+    assert_eq!(texts(drain(&mut receiver)), vec![here[1].clone()]);
+
+    for i in 0..EVENT_LOG_CAPACITY {
+        state.record_event(None, format!("Event {}", i));
+    }
+    assert_eq!(state.event_log.len(), EVENT_LOG_CAPACITY);
+}
+
+#[test]
+fn arriving_describes_the_place_to_the_newcomer_alone() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+    let (_, mut tom) = join(&mut state, &clients, "Tom", "Canteen");
+    let description = state.world_state.locations["Canteen"].description.clone();
+    let move_to = ClientMessage::MoveRequest {
+        target_location: "Canteen".to_string(),
+        commit: true,
+    };
+
+    send(&mut state, &clients, player_id, move_to);
+
+    let narratives = |messages: Vec<ServerMessage>| {
+        messages
+            .into_iter()
+            .filter_map(|msg| match msg {
+                ServerMessage::NarrativeUpdate(text) => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let seen = narratives(drain(&mut receiver));
+    assert!(seen.iter().any(|text| text.starts_with(&description)
+        && text.contains("Syme")
+        && text.contains("Tom")));
+    assert!(!narratives(drain(&mut tom))
+        .iter()
+        .any(|text| text.contains(&description)));
+}
+
+#[test]
+fn looking_around_lists_what_is_here() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    join(&mut state, &clients, "Julia", "Victory Mansions");
+    join(&mut state, &clients, "Tom", "Prole District");
+    // Someone whose connection dropped is no longer really here
+    let (gone, _) = join(&mut state, &clients, "Ampleforth", "Victory Mansions");
+    state.players.get_mut(&gone).unwrap().disconnected_at = Some(Instant::now());
+
+    send(&mut state, &clients, player_id, ClientMessage::LookAround);
+    let report = drain(&mut receiver).into_iter().find_map(|msg| match msg {
+        ServerMessage::LocationReport {
+            npcs_present,
+            other_players_present,
+            texts_hint,
+            ..
+        } => Some((npcs_present, other_players_present, texts_hint)),
+        _ => None,
+    });
+    let (npcs, players, hint) = report.unwrap();
+    assert_eq!(npcs, vec!["Parsons".to_string()]);
+    assert_eq!(players, vec!["Julia".to_string()]);
+    assert!(hint.is_none());
+
+    // Texts are only hinted at, never listed
+    state.players.get_mut(&player_id).unwrap().location = "Prole District".to_string();
+    send(&mut state, &clients, player_id, ClientMessage::LookAround);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::LocationReport { texts_hint: Some(hint), .. }] if !hint.contains("free_market")
+    ));
+}
+
+#[test]
+fn poisoned_locks_are_recovered() {
+    let (state, clients, player_id, _receiver) = setup();
+    let state = Arc::new(Mutex::new(state));
+
+    // A handler panicking while holding both locks poisons them
+    let (poisoned_state, poisoned_clients) = (state.clone(), clients.clone());
+    let _ = std::thread::spawn(move || {
+        let _state = poisoned_state.lock().unwrap();
+        let _clients = poisoned_clients.lock().unwrap();
+        panic!("handler bug");
+    })
+    .join();
+    assert!(state.is_poisoned());
+
+    assert!(lock_state(&state).players.contains_key(&player_id));
+    assert!(lock_clients(&clients).contains_key(&player_id));
+}
+
+#[test]
+fn racing_disconnects_announce_the_player_leaving_once() {
+    for _ in 0..50 {
+        let (mut state, clients, player_id, _receiver) = setup();
+        let (_, mut other) = join(&mut state, &clients, "Julia", "Victory Mansions");
+        let state = Arc::new(Mutex::new(state));
+        let metrics = Arc::new(Metrics::new());
+
+        // The forwarding task's send failure and the receive loop's close arrive together
+        let barrier = Arc::new(std::sync::Barrier::new(2));
+        let paths: Vec<_> = (0..2)
+            .map(|_| {
+                let (state, clients) = (state.clone(), clients.clone());
+                let (metrics, barrier) = (metrics.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    handle_disconnect(player_id, &clients, &state, &metrics, Duration::ZERO, false)
+                })
+            })
+            .collect();
+        let cleaned_up: Vec<bool> = paths.into_iter().map(|p| p.join().unwrap()).collect();
+
+        assert_eq!(cleaned_up.iter().filter(|&&done| done).count(), 1);
+        let left = drain(&mut other)
+            .into_iter()
+            .filter(|msg| matches!(msg, ServerMessage::PlayerLeft { player_id: id } if *id == player_id))
+            .count();
+        assert_eq!(left, 1);
+        assert!(!lock_state(&state).players.contains_key(&player_id));
+        assert!(metrics
+            .render_prometheus(0)
+            .contains("flight_sim_disconnects_total 1\n"));
+    }
+}
+
+#[test]
+fn client_that_falls_behind_is_dropped() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    // Nobody reads from this client's channel
+    let (slow_id, mut slow) = join(&mut state, &clients, "Parsons", "Victory Mansions");
+    for _ in 0..ServerConfig::default().client_buffer {
+        send(
+            &mut state,
+            &clients,
+            slow_id,
+            ClientMessage::QueryRelationships,
+        );
+    }
+    assert!(lock_clients(&clients).contains_key(&slow_id));
+
+    // One more message than the buffer holds disconnects it, without touching the others
+    send(
+        &mut state,
+        &clients,
+        slow_id,
+        ClientMessage::QueryRelationships,
+    );
+    assert!(!lock_clients(&clients).contains_key(&slow_id));
+    assert!(lock_clients(&clients).contains_key(&player_id));
+    assert_eq!(
+        drain(&mut slow).len(),
+        ServerConfig::default().client_buffer
+    );
+}
+
+#[test]
+fn moving_sends_a_delta_instead_of_the_whole_state() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (_, mut elsewhere) = join(&mut state, &clients, "Tom", "Canteen");
+
+    let move_to = ClientMessage::MoveRequest {
+        target_location: "Ministry of Truth".to_string(),
+        commit: true,
+    };
+    send(&mut state, &clients, player_id, move_to);
+
+    let seen = drain(&mut elsewhere);
+    assert!(matches!(
+        seen.as_slice(),
+        [ServerMessage::PlayerMoved { player_id: moved, from, to }]
+            if *moved == player_id && from == "Victory Mansions" && to == "Ministry of Truth"
+    ));
+    assert!(!drain(&mut receiver)
+        .iter()
+        .any(|msg| matches!(msg, ServerMessage::GameStateUpdate(_))));
+}
+
+#[test]
+fn stat_changes_are_sent_only_to_the_player() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (_, mut other) = join(&mut state, &clients, "Julia", "Victory Mansions");
+
+    send(&mut state, &clients, player_id, ClientMessage::WorkRequest);
+    send(&mut state, &clients, player_id, ClientMessage::RestRequest);
+
+    let loyalty = state.players[&player_id].loyalty;
+    let updates: Vec<_> = drain(&mut receiver)
+        .into_iter()
+        .filter_map(|msg| match msg {
+            ServerMessage::StatUpdate { loyalty, .. } => Some(loyalty),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(updates, vec![loyalty, loyalty]);
+    assert!(drain(&mut other).is_empty());
+}
+
+#[test]
+fn fly_input_is_clamped_and_must_be_finite() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let fly = |pitch| ClientMessage::FlyInput {
+        pitch,
+        roll: 0.0,
+        yaw: 0.0,
+        throttle_change: 0.0,
+        seq: 0,
+    };
+
+    send(&mut state, &clients, player_id, fly(f32::NAN));
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+    assert_eq!(
+        state.players[&player_id].orientation,
+        nalgebra::UnitQuaternion::identity()
+    );
+
+    // A huge input turns no faster than full deflection
+    send(&mut state, &clients, player_id, fly(1.0e9));
+    let huge = state.players[&player_id].orientation;
+    state.players.get_mut(&player_id).unwrap().orientation = nalgebra::UnitQuaternion::identity();
+    send(&mut state, &clients, player_id, fly(1.0));
+    assert!(huge.angle_to(&state.players[&player_id].orientation) < 1.0e-6);
+}
+
+#[test]
+fn only_the_trained_can_open_the_throttle() {
+    let (mut state, clients, _, _receiver) = setup();
+    let (clerk, mut receiver) = join(&mut state, &clients, "Syme", "Victory Mansions");
+    state.players.get_mut(&clerk).unwrap().occupation = "Records Department Worker".to_string();
+    let throttle_up = |seq| ClientMessage::FlyInput {
+        pitch: 1.0,
+        roll: 0.0,
+        yaw: 0.0,
+        throttle_change: 1.0,
+        seq,
+    };
+
+    // The stick moves, the throttle doesn't, and the player is told why once
+    send(&mut state, &clients, clerk, throttle_up(1));
+    send(&mut state, &clients, clerk, throttle_up(2));
+    assert_eq!(state.players[&clerk].throttle, 0.0);
+    assert_ne!(
+        state.players[&clerk].orientation,
+        UnitQuaternion::identity()
+    );
+    let refusals = drain(&mut receiver)
+        .into_iter()
+        .filter(|msg| matches!(msg, ServerMessage::Notification { .. }))
+        .count();
+    assert_eq!(refusals, 1);
+
+    state.players.get_mut(&clerk).unwrap().tasks_completed =
+        ServerConfig::default().flight_min_tasks;
+    send(&mut state, &clients, clerk, throttle_up(3));
+    assert!(state.players[&clerk].throttle > 0.0);
+}
+
+#[test]
+fn stale_fly_input_is_dropped_and_the_last_seq_kept() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let fly = |seq| ClientMessage::FlyInput {
+        pitch: 0.0,
+        roll: 0.0,
+        yaw: 0.0,
+        throttle_change: 1.0,
+        seq,
+    };
+
+    send(&mut state, &clients, player_id, fly(5));
+    let throttle = state.players[&player_id].throttle;
+    send(&mut state, &clients, player_id, fly(3));
+
+    let character = &state.players[&player_id];
+    assert_eq!(character.last_input_seq, 5);
+    assert_eq!(character.throttle, throttle);
+}
+
+#[test]
+fn same_seed_gives_same_outcomes() {
+    // Pitch ideas to Parsons until the dice have decided a few times either way
+    let play = |seed| {
+        let (mut state, clients, player_id, _receiver) = setup();
+        state.rng = flight_sim::WorldRng::from_seed(seed);
+        for _ in 0..20 {
+            let share = ClientMessage::ShareForbiddenKnowledge {
+                target_npc: "Parsons".to_string(),
+                knowledge_topic: "free_market".to_string(),
+                approach: flight_sim::SharingApproach::Questioning,
+            };
+            send(&mut state, &clients, player_id, share);
+        }
+        let character = state.players.remove(&player_id).unwrap();
+        (
+            character.relationships,
+            character.suspicion,
+            character.rebellion_score,
+        )
+    };
+
+    assert_eq!(play(1984), play(1984));
+}
+
+#[test]
+fn admin_commands_need_the_token() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let config = ServerConfig {
+        admin_token: Some("big-brother".to_string()),
+        ..Default::default()
+    };
+    let teleport = |token: &str| ClientMessage::AdminCommand {
+        token: token.to_string(),
+        command: flight_sim::AdminCommand::Teleport {
+            player: "Winston".to_string(),
+            location: "Prole District".to_string(),
+        },
+    };
+
+    handle_client_message(player_id, teleport("guess"), &mut state, &clients, &config);
+    assert!(matches!(
+        drain(&mut receiver).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+    assert_eq!(state.players[&player_id].location, "Victory Mansions");
+
+    handle_client_message(
+        player_id,
+        teleport("big-brother"),
+        &mut state,
+        &clients,
+        &config,
+    );
+    assert_eq!(state.players[&player_id].location, "Prole District");
+
+    let switch = ClientMessage::AdminCommand {
+        token: "big-brother".to_string(),
+        command: flight_sim::AdminCommand::ForceEnemySwitch,
+    };
+    handle_client_message(player_id, switch, &mut state, &clients, &config);
+    assert_eq!(state.world_state.current_enemy, "Eastasia");
+}
+
+#[test]
+fn caring_for_an_injured_cat_uses_up_food() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    send(&mut state, &clients, player_id, ClientMessage::CareForCat);
+    assert!(matches!(
+        drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.inventory.push("Scraps".to_string());
+    let cat = character.cat_companion.as_mut().unwrap();
+    cat.status = CatStatus::Injured;
+    cat.health = 40;
+    send(&mut state, &clients, player_id, ClientMessage::CareForCat);
+
+    let character = &state.players[&player_id];
+    let cat = character.cat_companion.as_ref().unwrap();
+    assert_eq!(cat.status, CatStatus::Following);
+    assert_eq!(cat.health, 70);
+    assert!(character.inventory.is_empty());
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::NarrativeUpdate(_), ServerMessage::StatUpdate { inventory, .. }]
+            if inventory.is_empty()
+    ));
+}
+
+#[test]
+fn lost_cat_comes_back_only_from_nearby() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    let cat = character.cat_companion.as_mut().unwrap();
+    cat.status = CatStatus::Lost;
+    cat.location = "Canteen".to_string();
+
+    send(&mut state, &clients, player_id, ClientMessage::CallCat);
+    assert_eq!(
+        state.players[&player_id]
+            .cat_companion
+            .as_ref()
+            .unwrap()
+            .status,
+        CatStatus::Lost
+    );
+
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+    send(&mut state, &clients, player_id, ClientMessage::CallCat);
+    let cat = state.players[&player_id].cat_companion.as_ref().unwrap();
+    assert_eq!(cat.status, CatStatus::Following);
+    assert_eq!(cat.location, "Ministry of Truth");
+    assert_eq!(drain(&mut receiver).len(), 2);
+}
+
+#[test]
+fn request_self_sends_only_the_players_own_character() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (_, mut other_receiver) = join(&mut state, &clients, "Julia", "Victory Mansions");
+
+    send(&mut state, &clients, player_id, ClientMessage::RequestSelf);
+
+    match &drain(&mut receiver)[..] {
+        [ServerMessage::SelfState(character)] => {
+            assert_eq!(character.player_id, player_id);
+            assert_eq!(character.name, "Winston");
+        }
+        other => panic!("expected SelfState, got {:?}", other),
+    }
+    assert!(drain(&mut other_receiver).is_empty());
+
+    let (stranger, mut stranger_receiver) = connect(&clients);
+    send(&mut state, &clients, stranger, ClientMessage::RequestSelf);
+    assert!(matches!(
+        drain(&mut stranger_receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+}
+
+#[test]
+fn creator_gets_character_created_before_the_state() {
+    let (mut state, clients, _, mut receiver) = setup();
+    let (player_id, mut new_receiver) = connect(&clients);
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::RequestCharacterCreation {
+            name: "Julia".to_string(),
+            occupation: "Fiction Department Writer".to_string(),
+        },
+    );
+
+    match &drain(&mut new_receiver)[..] {
+        [ServerMessage::CharacterCreated { character }, ServerMessage::GameStateUpdate(_)] => {
+            assert_eq!(character.player_id, player_id);
+            assert_eq!(character.name, "Julia");
+        }
+        other => panic!("expected CharacterCreated then the state, got {:?}", other),
+    }
+    assert!(matches!(
+        drain(&mut receiver)[..],
+        [ServerMessage::PlayerJoined { .. }]
+    ));
+}
+
+#[test]
+fn orientation_stays_unit_length_through_many_large_rotations() {
+    let (mut state, clients, player_id, _receiver) = setup();
+
+    for i in 0..10_000 {
+        let sign = if i % 3 == 0 { -1.0 } else { 1.0 };
+        let input = ClientMessage::FlyInput {
+            pitch: 1.0,
+            roll: sign,
+            yaw: 1.0,
+            throttle_change: 0.0,
+            seq: 0,
+        };
+        send(&mut state, &clients, player_id, input);
+    }
+
+    let orientation = state.players[&player_id].orientation;
+    assert!((orientation.quaternion().norm() - 1.0).abs() < 1.0e-5);
+}
+
+#[test]
+fn leaderboard_ranks_characters_without_private_stats() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (julia, _julia_receiver) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    state.players.get_mut(&julia).unwrap().rebellion_score = 40;
+    state.players.get_mut(&player_id).unwrap().suspicion = 90;
+    state.day = 3;
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::RequestLeaderboard,
+    );
+
+    let Some(ServerMessage::Leaderboard { entries }) = drain(&mut receiver).pop() else {
+        panic!("expected a leaderboard");
+    };
+    let names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+    assert_eq!(names, vec!["Julia", "Winston"]);
+    assert_eq!(entries[0].days_survived, 3);
+    assert!(entries[0].score > entries[1].score);
+
+    // Spectators may look too, and the list never grows past its cap
+    for i in 0..20 {
+        join(&mut state, &clients, &format!("Citizen {}", i), "Canteen");
+    }
+    let (spectator, mut spectator_receiver) = connect(&clients);
+    send(
+        &mut state,
+        &clients,
+        spectator,
+        ClientMessage::RequestLeaderboard,
+    );
+    let Some(ServerMessage::Leaderboard { entries }) = drain(&mut spectator_receiver).pop() else {
+        panic!("expected a leaderboard");
+    };
+    assert_eq!(entries.len(), 10);
+}
+
+#[test]
+fn confiding_in_obrien_draws_him_in() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+
+    for _ in 0..3 {
+        let share = ClientMessage::ShareForbiddenKnowledge {
+            target_npc: "O'Brien".to_string(),
+            knowledge_topic: "Voluntary Exchange".to_string(),
+            approach: flight_sim::SharingApproach::Subtle,
+        };
+        send(&mut state, &clients, player_id, share);
+    }
+
+    assert_eq!(
+        state.players[&player_id].entrapment,
+        flight_sim::Entrapment::Watching { confidences: 3 }
+    );
+}
+
+#[test]
+fn renaming_is_checked_and_announced_to_everyone() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (_, mut other_receiver) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let rename = |name: &str| ClientMessage::UpdateProfile {
+        name: Some(name.to_string()),
+        occupation: None,
+    };
+
+    send(&mut state, &clients, player_id, rename("Julia"));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+    send(&mut state, &clients, player_id, rename("   "));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+    assert_eq!(state.players[&player_id].name, "Winston");
+
+    send(&mut state, &clients, player_id, rename(" Smith "));
+    assert_eq!(state.players[&player_id].name, "Smith");
+    // The owner alone also gets the whole character back
+    match &drain(&mut receiver)[..] {
+        [ServerMessage::ProfileUpdated { profile, .. }, ServerMessage::SelfState(character)] => {
+            assert_eq!(profile.name, "Smith");
+            assert_eq!(character.name, "Smith");
+        }
+        other => panic!("expected ProfileUpdated and SelfState, got {:?}", other),
+    }
+    match &drain(&mut other_receiver)[..] {
+        [ServerMessage::ProfileUpdated {
+            player_id: id,
+            profile,
+        }] => {
+            assert_eq!(*id, player_id);
+            assert_eq!(profile.name, "Smith");
+        }
+        other => panic!("expected ProfileUpdated, got {:?}", other),
+    }
+}
+
+#[test]
+fn occupation_changes_need_the_server_setting() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let change_to = |occupation: &str| ClientMessage::UpdateProfile {
+        name: None,
+        occupation: Some(occupation.to_string()),
+    };
+
+    send(&mut state, &clients, player_id, change_to("Prole"));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+    assert_eq!(state.players[&player_id].occupation, "Pilot");
+
+    let config = ServerConfig {
+        allow_occupation_change: true,
+        ..ServerConfig::default()
+    };
+    handle_client_message(
+        player_id,
+        change_to("Thought Police"),
+        &mut state,
+        &clients,
+        &config,
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+
+    handle_client_message(player_id, change_to("Prole"), &mut state, &clients, &config);
+    assert_eq!(state.players[&player_id].occupation, "Prole");
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [
+            ServerMessage::ProfileUpdated { .. },
+            ServerMessage::SelfState(_)
+        ]
+    ));
+}
+
+#[test]
+fn czech_texts_mean_little_until_the_old_trader_teaches_czech() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.location = "Prole District".to_string();
+    character.economic_freedom_score = 40;
+    character.inventory.push("ankap_principles".to_string());
+    let read = || ClientMessage::ReadForbiddenText {
+        text_id: "ankap_principles".to_string(),
+    };
+
+    send(&mut state, &clients, player_id, read());
+    let struggled = match &drain(&mut receiver)[..] {
+        [ServerMessage::ForbiddenTextContent {
+            understanding_increase,
+            ..
+        }, ServerMessage::NarrativeUpdate(_), ServerMessage::StatUpdate { .. }] => {
+            *understanding_increase
+        }
+        other => panic!("expected a struggle with the Czech, got {:?}", other),
+    };
+
+    // Three lessons, one conversation each
+    for _ in 0..3 {
+        send(
+            &mut state,
+            &clients,
+            player_id,
+            ClientMessage::StartDialogue {
+                npc_name: "Old Trader".to_string(),
+            },
+        );
+        for option_id in [1, 0] {
+            let choose = ClientMessage::DialogueChoice {
+                npc_name: "Old Trader".to_string(),
+                option_id,
+            };
+            send(&mut state, &clients, player_id, choose);
+        }
+    }
+    assert!(state.players[&player_id]
+        .languages
+        .contains(&TextLanguage::Czech));
+    drain(&mut receiver);
+
+    send(&mut state, &clients, player_id, read());
+    match &drain(&mut receiver)[..] {
+        [ServerMessage::ForbiddenTextContent {
+            understanding_increase,
+            ..
+        }, ServerMessage::StatUpdate { .. }] => assert!(*understanding_increase > struggled),
+        other => panic!("expected the text to be understood, got {:?}", other),
+    }
+    assert!(state.players[&player_id].anarcho_knowledge["Principles of Non-Aggression"] > 0);
+}
+
+#[test]
+fn reading_needs_the_text_in_hand() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+
+    let read = ClientMessage::ReadForbiddenText {
+        text_id: "freedom_eng".to_string(),
+    };
+    send(&mut state, &clients, player_id, read);
+
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+}
+
+#[test]
+fn texts_pass_only_between_players_in_the_same_place() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let here = state.players[&player_id].location.clone();
+    state.world_state.locations.get_mut(&here).unwrap().safety = 5;
+    let (julia, mut julia_receiver) = join(&mut state, &clients, "Julia", &here);
+    join(&mut state, &clients, "Parsons", "Ministry of Truth");
+    let give = |target: &str| ClientMessage::GiveText {
+        target_name: target.to_string(),
+        text_id: "free_market".to_string(),
+    };
+
+    send(&mut state, &clients, player_id, give("Julia"));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::MissingItem,
+            ..
+        }]
+    ));
+
+    state
+        .players
+        .get_mut(&player_id)
+        .unwrap()
+        .inventory
+        .push("free_market".to_string());
+    send(&mut state, &clients, player_id, give("Parsons"));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::UnknownTarget,
+            ..
+        }]
+    ));
+
+    // Nobody watches a handoff somewhere perfectly safe
+    send(&mut state, &clients, player_id, give("Julia"));
+    assert!(!state.players[&player_id]
+        .inventory
+        .contains(&"free_market".to_string()));
+    assert!(state.players[&julia]
+        .inventory
+        .contains(&"free_market".to_string()));
+    assert_eq!(
+        state.players[&julia].suspicion,
+        state.players[&player_id].suspicion
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [
+            ServerMessage::NarrativeUpdate(_),
+            ServerMessage::StatUpdate { .. }
+        ]
+    ));
+    assert!(matches!(
+        &drain(&mut julia_receiver)[..],
+        [
+            ServerMessage::NarrativeUpdate(_),
+            ServerMessage::StatUpdate { .. }
+        ]
+    ));
+}
+
+#[test]
+fn examining_a_player_shows_only_what_can_be_seen() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let here = state.players[&player_id].location.clone();
+    let (julia, _julia_receiver) = join(&mut state, &clients, "Julia", &here);
+    join(&mut state, &clients, "Parsons", "Ministry of Truth");
+    let character = state.players.get_mut(&julia).unwrap();
+    character.health = 30;
+    character.suspicion = 80;
+    character.inventory = vec![
+        "Razor Blade".to_string(),
+        "free_market".to_string(),
+        "The Book".to_string(),
+    ];
+    let examine = |target: &str| ClientMessage::ExaminePlayer {
+        target_name: target.to_string(),
+    };
+
+    send(&mut state, &clients, player_id, examine("Julia"));
+    let messages = drain(&mut receiver);
+    let [ServerMessage::PlayerProfile { profile }] = &messages[..] else {
+        panic!("expected a profile, got {:?}", messages);
+    };
+    assert_eq!(profile.name, "Julia");
+    assert_eq!(profile.location, here);
+    assert_eq!(profile.visible_inventory, vec!["Razor Blade".to_string()]);
+    assert_eq!(profile.apparent_health, ApparentHealth::Injured);
+    let json = serde_json::to_string(&messages[0]).unwrap();
+    assert!(!json.contains("suspicion"));
+
+    send(&mut state, &clients, player_id, examine("Parsons"));
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::UnknownTarget,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn the_hate_rewards_those_who_attend_and_marks_those_who_do_not() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (julia, _julia_receiver) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.location = "Victory Square".to_string();
+    character.suspicion = 20;
+    let config = ServerConfig {
+        admin_token: Some("big-brother".to_string()),
+        ..ServerConfig::default()
+    };
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::ParticipateInHate,
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+
+    let hate = ClientMessage::AdminCommand {
+        token: "big-brother".to_string(),
+        command: flight_sim::AdminCommand::TwoMinutesHate,
+    };
+    handle_client_message(player_id, hate, &mut state, &clients, &config);
+    assert!(state.world_state.hate_in_progress());
+    drain(&mut receiver);
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::ParticipateInHate,
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [
+            ServerMessage::NarrativeUpdate(_),
+            ServerMessage::StatUpdate { .. }
+        ]
+    ));
+    let winston = &state.players[&player_id];
+    assert_eq!((winston.loyalty, winston.suspicion), (60, 10));
+    assert_eq!(winston.last_loyal_act_day, Some(state.day));
+
+    // Once is enough, and Julia's flat has no crowd
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::ParticipateInHate,
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error { .. }]
+    ));
+    send(
+        &mut state,
+        &clients,
+        julia,
+        ClientMessage::ParticipateInHate,
+    );
+    assert!(!state.players[&julia].attended_hate);
+
+    assert!(advance_hate(&mut state, 60.0).is_empty());
+    assert_eq!(advance_hate(&mut state, 60.0), vec![julia]);
+    assert!(!state.world_state.hate_in_progress());
+    assert_eq!(state.players[&julia].suspicion, 15);
+    assert_eq!(state.players[&player_id].suspicion, 10);
+}
+
+#[test]
+fn character_survives_a_disconnect_shorter_than_the_grace_period() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let (_, mut other) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let state = Arc::new(Mutex::new(state));
+    let metrics = Metrics::new();
+    let grace = Duration::from_secs(60);
+
+    assert!(handle_disconnect(
+        player_id, &clients, &state, &metrics, grace, true
+    ));
+    assert!(!handle_disconnect(
+        player_id, &clients, &state, &metrics, grace, true
+    ));
+    assert!(!lock_clients(&clients).contains_key(&player_id));
+    assert!(lock_state(&state).players[&player_id]
+        .disconnected_at
+        .is_some());
+
+    // Still inside the grace period: nobody is told anything
+    let mut guard = lock_state(&state);
+    assert!(purge_disconnected(&mut guard, &clients, grace, true).is_empty());
+    assert!(guard.players.contains_key(&player_id));
+    assert!(drain(&mut other).is_empty());
+
+    // Once it runs out the character is archived and the others hear about it
+    assert_eq!(
+        purge_disconnected(&mut guard, &clients, Duration::ZERO, true),
+        vec![player_id]
+    );
+    assert!(!guard.players.contains_key(&player_id));
+    assert!(guard.saved_characters.contains_key("Winston"));
+    assert!(matches!(
+        &drain(&mut other)[..],
+        [ServerMessage::PlayerLeft { player_id: id }] if *id == player_id
+    ));
+}
+
+#[test]
+fn idle_players_are_warned_then_disconnected_unless_busy() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let timeout = Duration::from_secs(300);
+    let idle_since = |minutes: u64| Instant::now() - Duration::from_secs(minutes * 60);
+
+    assert_eq!(idle_action(&state.players[&player_id], timeout), None);
+    assert_eq!(
+        idle_action(&state.players[&player_id], Duration::ZERO),
+        None
+    );
+
+    state.players.get_mut(&player_id).unwrap().last_input_at = idle_since(4);
+    assert_eq!(
+        idle_action(&state.players[&player_id], timeout),
+        Some(IdleAction::Warn)
+    );
+    state.players.get_mut(&player_id).unwrap().idle_warned = true;
+    assert_eq!(idle_action(&state.players[&player_id], timeout), None);
+
+    state.players.get_mut(&player_id).unwrap().last_input_at = idle_since(6);
+    assert_eq!(
+        idle_action(&state.players[&player_id], timeout),
+        Some(IdleAction::Disconnect)
+    );
+
+    // Flying or travelling counts as busy
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.position.y += 500.0;
+    assert_eq!(idle_action(character, timeout), None);
+    character.position.y -= 500.0;
+    character.travel_route = vec!["Ministry of Truth".to_string()];
+    assert_eq!(idle_action(character, timeout), None);
+    character.travel_route.clear();
+
+    // Any message shows the player is back
+    send(&mut state, &clients, player_id, ClientMessage::RequestSelf);
+    assert!(!state.players[&player_id].idle_warned);
+    assert_eq!(idle_action(&state.players[&player_id], timeout), None);
+}
+
+#[test]
+fn logging_out_keeps_the_character_only_when_asked() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let (julia, mut julia_receiver) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let (_, mut other) = join(&mut state, &clients, "Parsons", "Victory Mansions");
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::Disconnect { save: true },
+    );
+    assert!(!state.players.contains_key(&player_id));
+    assert!(state.saved_characters.contains_key("Winston"));
+    assert!(matches!(
+        receiver.try_recv(),
+        Ok(Message::Close(Some(frame))) if frame.code == CloseCode::Normal
+    ));
+
+    send(
+        &mut state,
+        &clients,
+        julia,
+        ClientMessage::Disconnect { save: false },
+    );
+    assert!(!state.players.contains_key(&julia));
+    assert!(!state.saved_characters.contains_key("Julia"));
+    // Winston leaving reaches her before her own connection closes
+    assert!(matches!(julia_receiver.try_recv(), Ok(Message::Text(_))));
+    assert!(matches!(julia_receiver.try_recv(), Ok(Message::Close(_))));
+    assert!(matches!(
+        &drain(&mut other)[..],
+        [
+            ServerMessage::PlayerLeft { .. },
+            ServerMessage::PlayerLeft { .. }
+        ]
+    ));
+}
+
+#[test]
+fn errors_carry_a_code_for_the_kind_of_problem() {
+    let cases = [
+        (
+            ClientMessage::MoveRequest {
+                target_location: "Ministry of Love".to_string(),
+                commit: true,
+            },
+            ErrorCode::InvalidMove,
+        ),
+        (
+            ClientMessage::FlyInput {
+                pitch: f32::NAN,
+                roll: 0.0,
+                yaw: 0.0,
+                throttle_change: 0.0,
+                seq: 1,
+            },
+            ErrorCode::InvalidMessage,
+        ),
+        (
+            ClientMessage::StartDialogue {
+                npc_name: "Goldstein".to_string(),
+            },
+            ErrorCode::UnknownTarget,
+        ),
+        (
+            ClientMessage::UseItem {
+                item: "Victory Gin".to_string(),
+            },
+            ErrorCode::MissingItem,
+        ),
+        (ClientMessage::CallCat, ErrorCode::InvalidAction),
+        (
+            ClientMessage::AdminCommand {
+                token: "guess".to_string(),
+                command: flight_sim::AdminCommand::ForceEnemySwitch,
+            },
+            ErrorCode::NotAuthenticated,
+        ),
+        (
+            ClientMessage::RequestCharacterCreation {
+                name: "Winston".to_string(),
+                occupation: "Pilot".to_string(),
+            },
+            ErrorCode::CharacterExists,
+        ),
+        (
+            ClientMessage::UpdateProfile {
+                name: None,
+                occupation: Some("Prole".to_string()),
+            },
+            ErrorCode::NotAllowed,
+        ),
+    ];
+    for (msg, expected) in cases {
+        let (mut state, clients, player_id, mut receiver) = setup();
+        let description = format!("{:?}", msg);
+        send(&mut state, &clients, player_id, msg);
+        match &drain(&mut receiver)[..] {
+            [ServerMessage::Error { code, .. }] => assert_eq!(*code, expected, "{}", description),
+            other => panic!("expected an error for {}, got {:?}", description, other),
+        }
+    }
+
+    let (mut state, clients, player_id, mut receiver) = setup();
+    begin_interrogation(state.players.get_mut(&player_id).unwrap());
+    send(&mut state, &clients, player_id, ClientMessage::WorkRequest);
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::Detained,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn the_map_is_only_topology_and_is_served_before_joining() {
+    let (mut state, clients, _, _receiver) = setup();
+    let (newcomer, mut receiver) = connect(&clients);
+
+    send(&mut state, &clients, newcomer, ClientMessage::RequestMap);
+    let first = match receiver.try_recv().unwrap() {
+        Message::Text(text) => text,
+        other => panic!("expected text, got {:?}", other),
+    };
+    assert!(!first.contains("description"));
+    let ServerMessage::WorldMap { locations } = serde_json::from_str(&first).unwrap() else {
+        panic!("expected a map, got {}", first);
+    };
+    assert_eq!(locations.len(), state.world_state.locations.len());
+    assert!(locations.windows(2).all(|pair| pair[0].name < pair[1].name));
+    let mansions = locations
+        .iter()
+        .find(|location| location.name == "Victory Mansions")
+        .unwrap();
+    assert_eq!(
+        mansions.connections,
+        state.world_state.locations["Victory Mansions"].connections
+    );
+
+    // The payload is built once and reused
+    send(&mut state, &clients, newcomer, ClientMessage::RequestMap);
+    assert_eq!(receiver.try_recv().unwrap(), Message::Text(first));
+}
+
+// Run one game loop tick with the given end conditions
+fn tick_with(state: &mut GameState, clients: &Clients, end_conditions: EndConditions) {
+    let config = ServerConfig {
+        end_conditions,
+        ..ServerConfig::default()
+    };
+    Simulation::new(&config).tick(state, clients);
+}
+
+#[test]
+fn a_lowered_arrest_threshold_arrests_earlier() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().suspicion = 85;
+
+    tick_with(&mut state, &clients, EndConditions::default());
+    assert!(state.players[&player_id].detention.is_none());
+
+    let hardcore = EndConditions {
+        arrest_suspicion: 80,
+        ..EndConditions::default()
+    };
+    tick_with(&mut state, &clients, hardcore);
+    assert!(state.players[&player_id].detention.is_some());
+}
+
+#[test]
+fn arrest_is_announced_as_danger() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().suspicion = 100;
+
+    tick_with(&mut state, &clients, EndConditions::default());
+    assert!(drain(&mut receiver).iter().any(|msg| matches!(
+        msg,
+        ServerMessage::Notification {
+            level: NotificationLevel::Danger,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn arrests_can_vaporize_at_once() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().suspicion = 100;
+
+    let removal = EndConditions {
+        arrest: ArrestConsequence::Removal,
+        ..EndConditions::default()
+    };
+    tick_with(&mut state, &clients, removal);
+    assert!(!state.players.contains_key(&player_id));
+}
+
+#[test]
+fn without_permadeath_the_dying_come_round_at_home() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.health = 0;
+    character.location = "Prole District".to_string();
+
+    let story = EndConditions {
+        permadeath: false,
+        ..EndConditions::default()
+    };
+    tick_with(&mut state, &clients, story);
+    let character = &state.players[&player_id];
+    assert_eq!(character.location, "Victory Mansions");
+    assert!(character.health > 0);
+
+    state.players.get_mut(&player_id).unwrap().health = 0;
+    tick_with(&mut state, &clients, EndConditions::default());
+    assert!(!state.players.contains_key(&player_id));
+}
+
+#[test]
+fn only_records_workers_at_the_ministry_alter_records() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let alter = || ClientMessage::AlterRecord {
+        record_id: "times-1983-12-19".to_string(),
+        new_content: "Chocolate ration raised to twenty grammes.".to_string(),
+    };
+
+    send(&mut state, &clients, player_id, alter());
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::NotAllowed,
+            ..
+        }]
+    ));
+
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.occupation = "Records Department Worker".to_string();
+    send(&mut state, &clients, player_id, alter());
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::InvalidAction,
+            ..
+        }]
+    ));
+    assert!(state.world_state.records.is_empty());
+
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.location = "Ministry of Truth".to_string();
+    let (loyalty, thoughtcrime) = (character.loyalty, character.thoughtcrime);
+    send(&mut state, &clients, player_id, alter());
+    let character = &state.players[&player_id];
+    assert_eq!(character.tasks_completed, 1);
+    assert!(character.loyalty > loyalty);
+    assert!(character.thoughtcrime > thoughtcrime);
+    assert_eq!(
+        state.world_state.records["times-1983-12-19"],
+        "Chocolate ration raised to twenty grammes."
+    );
+}
+
+#[test]
+fn clients_cannot_set_their_own_position() {
+    let cheat = r#"{"FlyInput":{"pitch":0.0,"roll":0.0,"yaw":0.0,"throttle_change":0.0,"position":[0.0,900.0,0.0]}}"#;
+    assert!(serde_json::from_str::<ClientMessage>(cheat).is_err());
+
+    let honest = r#"{"FlyInput":{"pitch":0.0,"roll":0.0,"yaw":0.0,"throttle_change":0.0}}"#;
+    assert!(serde_json::from_str::<ClientMessage>(honest).is_ok());
+}
+
+#[test]
+fn available_actions_follow_place_occupation_and_company() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let config = ServerConfig::default();
+    let targets = |state: &GameState, message: &str| {
+        available_actions(state, player_id, &config)
+            .into_iter()
+            .find(|action| action.message == message)
+            .map(|action| action.targets)
+    };
+
+    // Alone at home with empty pockets
+    assert_eq!(
+        targets(&state, "MoveRequest"),
+        Some(
+            state.world_state.locations["Victory Mansions"]
+                .connections
+                .clone()
+        )
+    );
+    assert_eq!(targets(&state, "AlterRecord"), None);
+    assert_eq!(targets(&state, "VoluntaryExchange"), None);
+    assert_eq!(targets(&state, "ExaminePlayer"), None);
+
+    // A records worker at the Ministry, with goods to trade and company
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.occupation = "Records Department Worker".to_string();
+    character.location = "Prole District".to_string();
+    character.inventory.push("Razor Blade".to_string());
+    join(&mut state, &clients, "Julia", "Prole District");
+    assert_eq!(
+        targets(&state, "VoluntaryExchange"),
+        Some(vec!["Old Trader".to_string()])
+    );
+    assert_eq!(
+        targets(&state, "UseItem"),
+        Some(vec!["Razor Blade".to_string()])
+    );
+    assert_eq!(
+        targets(&state, "ExaminePlayer"),
+        Some(vec!["Julia".to_string()])
+    );
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+    assert_eq!(targets(&state, "AlterRecord"), Some(Vec::new()));
+
+    // Only the interrogator can be answered in detention
+    begin_interrogation(state.players.get_mut(&player_id).unwrap());
+    assert_eq!(
+        available_actions(&state, player_id, &config)
+            .into_iter()
+            .map(|action| action.message)
+            .collect::<Vec<_>>(),
+        vec!["DialogueChoice".to_string()]
+    );
+
+    send(&mut state, &clients, player_id, ClientMessage::QueryActions);
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::AvailableActions { actions }] if actions.len() == 1
+    ));
+}
+
+#[test]
+fn oversized_journal_entries_are_refused() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let config = ServerConfig::default();
+    let entry = "Down with Big Brother. ".repeat(config.max_text_len);
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::JournalWriteRequest { entry },
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::InvalidMessage,
+            ..
+        }]
+    ));
+    assert!(state.players[&player_id].journal_entries.is_empty());
+}
+
+#[test]
+fn trusted_npcs_teach_one_lesson_a_day() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let learn = |npc_name: &str, subject: &str| ClientMessage::LearnFromNpc {
+        npc_name: npc_name.to_string(),
+        subject: subject.to_string(),
+    };
+    state.players.get_mut(&player_id).unwrap().location = "Prole District".to_string();
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        learn("Old Trader", "Czech"),
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::NarrativeUpdate(_), ServerMessage::StatUpdate { skills, .. }]
+            if skills["Czech"] > 0
+    ));
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        learn("Old Trader", "Czech"),
+    );
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::InvalidAction,
+            ..
+        }]
+    ));
+
+    // Syme doesn't trust a stranger enough, and asking draws attention
+    state.day += 1;
+    state.players.get_mut(&player_id).unwrap().location = "Canteen".to_string();
+    send(&mut state, &clients, player_id, learn("Syme", "Newspeak"));
+    let character = &state.players[&player_id];
+    assert!(!character.skills.contains_key("Newspeak"));
+    assert!(character.suspicion > 0);
+}
+
+#[test]
+fn denouncers_win_the_partys_trust_and_lose_the_rebels() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    join(&mut state, &clients, "Tom", "Victory Mansions");
+
+    let report = ClientMessage::ReportPlayer {
+        target_name: "Tom".to_string(),
+        accusation: "humming an old song".to_string(),
+    };
+    send(&mut state, &clients, player_id, report);
+    let relationships = &state.players[&player_id].relationships;
+    let trust = ServerConfig::default().trust_dynamics;
+    assert_eq!(relationships["Syme"], trust.report_party);
+    assert_eq!(relationships["Old Trader"], trust.report_rebel);
+}
+
+#[test]
+fn sharing_forbidden_knowledge_sways_both_sides() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().location = "Ministry of Truth".to_string();
+
+    let share = ClientMessage::ShareForbiddenKnowledge {
+        target_npc: "Julia".to_string(),
+        knowledge_topic: "Voluntary Exchange".to_string(),
+        approach: flight_sim::SharingApproach::Subtle,
+    };
+    send(&mut state, &clients, player_id, share);
+    let relationships = &state.players[&player_id].relationships;
+    let trust = ServerConfig::default().trust_dynamics;
+    assert_eq!(relationships["Old Trader"], trust.share_rebel);
+    assert_eq!(relationships["Parsons"], trust.share_party);
+}
+
+#[test]
+fn switching_enemies_shifts_npc_trust_by_side() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let config = ServerConfig {
+        admin_token: Some("big-brother".to_string()),
+        ..Default::default()
+    };
+    let (syme, julia) = (
+        state.world_state.npcs["Syme"].trust,
+        state.world_state.npcs["Julia"].trust,
+    );
+
+    let switch = ClientMessage::AdminCommand {
+        token: "big-brother".to_string(),
+        command: flight_sim::AdminCommand::ForceEnemySwitch,
+    };
+    handle_client_message(player_id, switch, &mut state, &clients, &config);
+    let npcs = &state.world_state.npcs;
+    assert_eq!(
+        npcs["Syme"].trust,
+        syme + config.trust_dynamics.enemy_switch_party
+    );
+    assert_eq!(
+        npcs["Julia"].trust,
+        julia + config.trust_dynamics.enemy_switch_rebel
+    );
+}
+
+#[test]
+fn reflecting_restores_morale_at_the_cost_of_thoughtcrime() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    state.players.get_mut(&player_id).unwrap().morale = 20;
+
+    send(&mut state, &clients, player_id, ClientMessage::Reflect);
+    let character = &state.players[&player_id];
+    assert!(character.morale > 20);
+    assert!(character.thoughtcrime > 0);
+    assert!(drain(&mut receiver).iter().any(|msg| matches!(
+        msg,
+        ServerMessage::StatUpdate { morale, .. } if *morale == character.morale
+    )));
+
+    state.players.get_mut(&player_id).unwrap().morale = 100;
+    send(&mut state, &clients, player_id, ClientMessage::Reflect);
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Error {
+            code: ErrorCode::InvalidAction,
+            ..
+        }]
+    ));
+}
+
+#[test]
+fn ration_cuts_lower_everyones_morale() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let config = ServerConfig {
+        admin_token: Some("big-brother".to_string()),
+        ..Default::default()
+    };
+    let morale = state.players[&player_id].morale;
+    let ration = |amount| ClientMessage::AdminCommand {
+        token: "big-brother".to_string(),
+        command: flight_sim::AdminCommand::SetRation { amount },
+    };
+
+    let raised = state.world_state.chocolate_ration + 5;
+    handle_client_message(player_id, ration(raised), &mut state, &clients, &config);
+    assert_eq!(state.players[&player_id].morale, morale);
+
+    handle_client_message(player_id, ration(10), &mut state, &clients, &config);
+    assert!(state.players[&player_id].morale < morale);
+}
+
+#[test]
+fn a_tick_sends_each_client_one_batch() {
+    let (mut state, clients, _, mut receiver) = setup();
+    // The Hate ends this tick without Winston, which costs a warning and a stat update
+    state.world_state.two_minutes_hate_today = true;
+    state.world_state.hate_seconds_left = 0.001;
+
+    Simulation::new(&ServerConfig::default()).tick(&mut state, &clients);
+    let mut frames = Vec::new();
+    while let Ok(Message::Text(text)) = receiver.try_recv() {
+        frames.push(serde_json::from_str::<ServerMessage>(&text).unwrap());
+    }
+    match &frames[..] {
+        [ServerMessage::BatchUpdate { messages }] => {
+            assert!(messages
+                .iter()
+                .any(|msg| matches!(msg, ServerMessage::Notification { .. })));
+            assert!(messages
+                .iter()
+                .any(|msg| matches!(msg, ServerMessage::StatUpdate { .. })));
+        }
+        other => panic!("Expected a single batch, got {:?}", other),
+    }
+}
+
+#[test]
+fn a_crash_in_the_tick_updates_the_pilots_stats() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    let character = state.players.get_mut(&player_id).unwrap();
+    character.location = "Prole District".to_string();
+    character.position = Point3::new(0.0, 0.1, 0.0);
+    character.velocity = Vector3::new(0.0, -10.0, 0.0);
+    let health = character.health;
+
+    Simulation::new(&ServerConfig::default()).tick(&mut state, &clients);
+
+    let character = &state.players[&player_id];
+    assert!(character.health < health);
+    assert!(drain(&mut receiver).iter().any(|msg| matches!(
+        msg,
+        ServerMessage::StatUpdate { health, injuries, .. }
+            if *health == character.health && !injuries.is_empty()
+    )));
+}
+
+#[test]
+fn moving_aircraft_reach_everyone_as_their_own_pose() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let (_, mut julia) = join(&mut state, &clients, "Julia", "Victory Mansions");
+    let mut simulation = Simulation::new(&ServerConfig::default());
+    // Let the first flight broadcast fall due
+    std::thread::sleep(Duration::from_millis(150));
+
+    simulation.tick(&mut state, &clients);
+
+    let seen = drain(&mut julia);
+    let winston = &state.players[&player_id];
+    assert!(seen.iter().any(|msg| matches!(
+        msg,
+        ServerMessage::PlayerState { player_id: moved, position, .. }
+            if *moved == player_id && *position == winston.position
+    )));
+}
+
+#[test]
+fn flight_assist_is_on_until_turned_off() {
+    let (mut state, clients, player_id, mut receiver) = setup();
+    assert!(state.players[&player_id].flight_assist);
+
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::SetFlightAssist { enabled: false },
+    );
+    assert!(!state.players[&player_id].flight_assist);
+    assert!(matches!(
+        &drain(&mut receiver)[..],
+        [ServerMessage::Notification {
+            level: NotificationLevel::Info,
+            ..
+        }]
+    ));
+
+    // Moving the stick marks it held until the next physics tick
+    send(
+        &mut state,
+        &clients,
+        player_id,
+        ClientMessage::FlyInput {
+            pitch: 0.0,
+            roll: 0.5,
+            yaw: 0.0,
+            throttle_change: 0.0,
+            seq: 1,
+        },
+    );
+    assert!(state.players[&player_id].stick_held);
+}
+
+#[test]
+fn other_players_never_see_private_stats() {
+    let (mut state, clients, player_id, _receiver) = setup();
+    let winston = state.players.get_mut(&player_id).unwrap();
+    winston.suspicion = 37;
+    winston.thoughtcrime = 41;
+    winston.inventory.push("Victory Gin".to_string());
+    let (julia, mut julia_receiver) = connect(&clients);
+
+    send(
+        &mut state,
+        &clients,
+        julia,
+        ClientMessage::RequestCharacterCreation {
+            name: "Julia".to_string(),
+            occupation: "Fiction Department Writer".to_string(),
+        },
+    );
+    let write = ClientMessage::JournalWriteRequest {
+        entry: "DOWN WITH BIG BROTHER".to_string(),
+    };
+    send(&mut state, &clients, player_id, write);
+    let hide = ClientMessage::HideForbiddenText {
+        text_id: "journal".to_string(),
+        hiding_place: "under the floorboard".to_string(),
+    };
+    send(&mut state, &clients, player_id, hide);
+    let drop = ClientMessage::DropItem {
+        item: "Victory Gin".to_string(),
+    };
+    send(&mut state, &clients, player_id, drop);
+    Simulation::new(&ServerConfig::default()).tick(&mut state, &clients);
+
+    let seen = drain(&mut julia_receiver);
+    assert!(seen.iter().any(|msg| matches!(
+        msg,
+        ServerMessage::GameStateUpdate(public) if public.players.contains_key(&player_id)
+    )));
+    for msg in seen {
+        // Julia's own stats are hers to see
+        if matches!(
+            msg,
+            ServerMessage::CharacterCreated { .. }
+                | ServerMessage::StatUpdate { .. }
+                | ServerMessage::SelfState(_)
+        ) {
+            continue;
+        }
+        let json = serde_json::to_string(&msg).unwrap();
+        for private in [
+            "\"suspicion\"",
+            "\"thoughtcrime\"",
+            "BIG BROTHER",
+            "floorboard",
+        ] {
+            assert!(!json.contains(private), "{} leaked in {}", private, json);
+        }
+    }
+}
+
+#[test]
+fn low_morale_makes_searches_come_up_empty() {
+    // Seed 5 rolls 34: under the Prole District's chance of 40, but not once low morale
+    // takes 30 off it
+    let search = |morale: u8| {
+        let (mut state, clients, player_id, mut receiver) = setup();
+        state.rng = flight_sim::WorldRng::from_seed(5);
+        let character = state.players.get_mut(&player_id).unwrap();
+        character.location = "Prole District".to_string();
+        character.morale = morale;
+        send(
+            &mut state,
+            &clients,
+            player_id,
+            ClientMessage::SearchRequest,
+        );
+        let found = drain(&mut receiver).into_iter().find_map(|msg| match msg {
+            ServerMessage::ForbiddenTextFound { texts } => Some(texts),
+            _ => None,
+        });
+        (state, player_id, found)
+    };
+
+    // The only text hidden there moves from the district into the inventory
+    let (state, player_id, found) = search(100);
+    assert_eq!(found, Some(vec!["free_market".to_string()]));
+    assert!(state.players[&player_id]
+        .inventory
+        .contains(&"free_market".to_string()));
+    assert!(state.world_state.text_locations["Prole District"].is_empty());
+
+    let (state, player_id, found) = search(0);
+    assert_eq!(found, None);
+    assert!(!state.players[&player_id]
+        .inventory
+        .contains(&"free_market".to_string()));
+    assert_eq!(
+        state.world_state.text_locations["Prole District"],
+        vec!["free_market".to_string()]
+    );
+}
@@ -0,0 +1,34 @@
+use flight_sim::metrics::Metrics;
+use std::time::Duration;
+
+#[test]
+fn prometheus_output_reports_counters() {
+    let metrics = Metrics::new();
+    metrics.record_message();
+    metrics.record_message();
+    metrics.record_disconnect();
+
+    let output = metrics.render_prometheus(3);
+
+    assert!(output.contains("flight_sim_connected_clients 3\n"));
+    assert!(output.contains("flight_sim_messages_processed_total 2\n"));
+    assert!(output.contains("flight_sim_disconnects_total 1\n"));
+}
+
+#[test]
+fn tick_histogram_buckets_are_cumulative() {
+    let metrics = Metrics::new();
+    metrics.record_tick(Duration::from_micros(500));
+    metrics.record_tick(Duration::from_millis(20));
+    metrics.record_tick(Duration::from_secs(1));
+
+    let output = metrics.render_prometheus(0);
+
+    assert!(output.contains("flight_sim_tick_duration_seconds_bucket{le=\"0.001\"} 1\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_bucket{le=\"0.01\"} 1\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_bucket{le=\"0.025\"} 2\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_bucket{le=\"0.25\"} 2\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_sum 1.0205\n"));
+    assert!(output.contains("flight_sim_tick_duration_seconds_count 3\n"));
+}
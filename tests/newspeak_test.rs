@@ -0,0 +1,20 @@
+use flight_sim::newspeak::{flag_oldspeak, NewspeakConfig};
+
+#[test]
+fn flags_whole_words_case_insensitively_in_order() {
+    let banned = NewspeakConfig::default().banned_words;
+
+    let flagged = flag_oldspeak(
+        "Freedom is the freedom to say that two plus two make four. Not a BAD thought, badger.",
+        &banned,
+    );
+
+    assert_eq!(flagged, vec!["freedom", "freedom", "bad"]);
+}
+
+#[test]
+fn clean_newspeak_is_not_flagged() {
+    let banned = NewspeakConfig::default().banned_words;
+
+    assert!(flag_oldspeak("Ungood thoughts are doubleplusungood.", &banned).is_empty());
+}
@@ -0,0 +1,132 @@
+use flight_sim::{Character, GameState};
+use nalgebra::{Point3, UnitQuaternion, Vector3};
+use serde_json::json;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("flight_sim_{}_{}", std::process::id(), name))
+}
+
+#[test]
+fn json_save_round_trips() {
+    let path = temp_path("round_trip.json");
+    let mut state = GameState::new();
+    state.day = 42;
+    state.world_state.chocolate_ration = 20;
+
+    state.save_to_path(&path).unwrap();
+    let loaded = GameState::load_from_path(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.day, 42);
+    assert_eq!(loaded.world_state.chocolate_ration, 20);
+    assert_eq!(
+        loaded.world_state.locations.len(),
+        state.world_state.locations.len()
+    );
+}
+
+#[test]
+fn corrupt_save_is_an_error() {
+    let path = temp_path("corrupt.json");
+    std::fs::write(&path, "{ not json").unwrap();
+
+    let result = GameState::load_from_path(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn binary_snapshot_round_trips_losslessly() {
+    let path = temp_path("round_trip.bin");
+    let mut state = GameState::new();
+    state.day = 7;
+    state.day_elapsed = 123.5;
+    for i in 0..3 {
+        let id = Uuid::new_v4();
+        let mut character = Character::new(id, format!("Citizen {}", i), "Pilot".to_string());
+        character
+            .journal_entries
+            .push("Down with Big Brother".to_string());
+        character.velocity.x = 0.1 * i as f32;
+        state.players.insert(id, character);
+    }
+
+    state.save_to_path(&path).unwrap();
+    let loaded = GameState::load_from_path(&path).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    // Not JSON on disk, and everything saved comes back exactly
+    assert!(serde_json::from_slice::<serde_json::Value>(&bytes).is_err());
+    assert_eq!(
+        serde_json::to_value(&loaded).unwrap(),
+        serde_json::to_value(&state).unwrap()
+    );
+}
+
+#[test]
+fn corrupt_binary_snapshot_is_an_error() {
+    let path = temp_path("corrupt.bin");
+    std::fs::write(&path, [0xff; 16]).unwrap();
+
+    let result = GameState::load_from_path(&path);
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn equal_states_serialize_identically() {
+    let ids: Vec<Uuid> = (1..=8).map(Uuid::from_u128).collect();
+    let build = |order: &mut dyn Iterator<Item = &Uuid>| {
+        let mut state = GameState::new();
+        for id in order {
+            let mut character = Character::new(*id, id.to_string(), "Pilot".to_string());
+            character.relationships.insert("O'Brien".to_string(), 5);
+            character
+                .relationships
+                .insert("Mr. Charrington".to_string(), -5);
+            state.players.insert(*id, character);
+        }
+        serde_json::to_string(&state).unwrap()
+    };
+
+    assert_eq!(build(&mut ids.iter()), build(&mut ids.iter().rev()));
+}
+
+#[test]
+fn flight_state_serializes_as_named_coordinates() {
+    let mut character = Character::new(Uuid::nil(), "Winston".to_string(), "Pilot".to_string());
+    character.position = Point3::new(1.0, 2.0, 3.0);
+    character.velocity = Vector3::new(-1.0, 0.5, 0.0);
+    character.orientation = UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3);
+    let q = character.orientation;
+
+    let json = serde_json::to_value(&character).unwrap();
+    assert_eq!(json["position"], json!({"x": 1.0, "y": 2.0, "z": 3.0}));
+    assert_eq!(json["velocity"], json!({"x": -1.0, "y": 0.5, "z": 0.0}));
+    assert_eq!(
+        json["orientation"],
+        json!({"w": q.w, "x": q.i, "y": q.j, "z": q.k})
+    );
+
+    let loaded: Character = serde_json::from_value(json).unwrap();
+    assert_eq!(loaded.position, character.position);
+    assert_eq!(loaded.velocity, character.velocity);
+    assert_eq!(loaded.orientation, character.orientation);
+}
+
+#[test]
+fn saves_with_coordinate_arrays_still_load() {
+    let character = Character::new(Uuid::nil(), "Winston".to_string(), "Pilot".to_string());
+    let mut json = serde_json::to_value(&character).unwrap();
+    json["position"] = json!([1.0, 2.0, 3.0]);
+    json["orientation"] = json!([0.0, 0.0, 0.0, 1.0]);
+
+    let loaded: Character = serde_json::from_value(json).unwrap();
+    assert_eq!(loaded.position, Point3::new(1.0, 2.0, 3.0));
+    assert_eq!(loaded.orientation, UnitQuaternion::identity());
+}
@@ -0,0 +1,542 @@
+use flight_sim::physics::{
+    self, Aircraft, DragModel, FlightState, PhysicsConfig, Weather, LIGHT_AIRCRAFT,
+};
+use flight_sim::{
+    apply_flight_to_map, integrate_flight, step_character_physics, terrain_height, Character,
+    SentPose, ServerMessage, WorldState,
+};
+use nalgebra::{Point3, Quaternion, UnitQuaternion, Vector3};
+use uuid::Uuid;
+
+const DT: f32 = 1.0 / 30.0;
+
+fn airborne_character() -> Character {
+    let mut character = Character::new(Uuid::nil(), "Winston".to_string(), "Pilot".to_string());
+    character.position = Point3::new(0.0, 500.0, 0.0);
+    character
+}
+
+#[test]
+fn zero_wind_matches_still_air_model() {
+    // From rest there is no airflow, so only thrust and gravity act on the first step
+    let mut character = airborne_character();
+    character.throttle = 1.0;
+
+    let sample = integrate_flight(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert_eq!(sample.airspeed, 0.0);
+    assert_eq!(sample.angle_of_attack, 0.0);
+    assert!((character.velocity - Vector3::new(0.0, -9.81 * DT, 20.0 * DT)).norm() < 1.0e-6);
+}
+
+#[test]
+fn zero_wind_is_deterministic() {
+    let mut first = airborne_character();
+    let mut second = airborne_character();
+    first.throttle = 0.8;
+    second.throttle = 0.8;
+
+    for _ in 0..300 {
+        integrate_flight(&mut first, Vector3::zeros(), DT, &PhysicsConfig::default());
+        integrate_flight(&mut second, Vector3::zeros(), DT, &PhysicsConfig::default());
+    }
+
+    assert_eq!(first.position, second.position);
+    assert_eq!(first.velocity, second.velocity);
+}
+
+#[test]
+fn headwind_creates_airspeed_without_ground_speed() {
+    let mut character = airborne_character();
+
+    let sample = integrate_flight(
+        &mut character,
+        Vector3::new(0.0, 0.0, -10.0),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert!((sample.airspeed - 10.0).abs() < 1.0e-4);
+    // Drag from the headwind pushes the aircraft backwards
+    assert!(character.velocity.z < 0.0);
+}
+
+#[test]
+fn aircraft_2d_nose_points_along_x() {
+    let aircraft = Aircraft::new();
+    let state = aircraft.flight_state();
+
+    let forward = state.orientation * Vector3::z_axis();
+    let up = state.orientation * Vector3::y_axis();
+    assert!((forward.into_inner() - Vector3::x()).norm() < 1.0e-6);
+    assert!((up.into_inner() - Vector3::y()).norm() < 1.0e-6);
+}
+
+#[test]
+fn aircraft_2d_update_delegates_to_shared_core() {
+    let mut aircraft = Aircraft::new();
+    aircraft.throttle_level = 0.5;
+    aircraft.theta = 0.05;
+    let mut expected = aircraft.flight_state();
+
+    aircraft.update(DT);
+    physics::integrate(
+        &LIGHT_AIRCRAFT,
+        &mut expected,
+        Vector3::zeros(),
+        physics::G,
+        DT,
+    );
+
+    assert_eq!(aircraft.x, expected.position.x);
+    assert_eq!(aircraft.y, expected.position.y);
+    assert_eq!(aircraft.vx, expected.velocity.x);
+    assert_eq!(aircraft.vy, expected.velocity.y);
+}
+
+// Top speed of the server airframe at full throttle with gravity switched off
+fn terminal_speed(drag: DragModel) -> f32 {
+    let airframe = PhysicsConfig {
+        drag,
+        ..PhysicsConfig::default()
+    }
+    .airframe();
+    let mut state = FlightState {
+        position: Point3::origin(),
+        velocity: Vector3::zeros(),
+        orientation: UnitQuaternion::identity(),
+        throttle: 1.0,
+    };
+    for _ in 0..20_000 {
+        physics::integrate(&airframe, &mut state, Vector3::zeros(), 0.0, DT);
+    }
+    state.velocity.norm()
+}
+
+#[test]
+fn drag_model_sets_terminal_velocity() {
+    let thrust = PhysicsConfig::default().thrust_scale;
+
+    // Thrust balances coeff * v for linear drag and coeff * v^2 for quadratic drag
+    let linear = terminal_speed(DragModel::Linear { coeff: 0.5 });
+    assert!((linear - thrust / 0.5).abs() < 0.1, "{}", linear);
+    let quadratic = terminal_speed(DragModel::Quadratic { coeff: 0.05 });
+    assert!(
+        (quadratic - (thrust / 0.05).sqrt()).abs() < 0.1,
+        "{}",
+        quadratic
+    );
+
+    // The same coefficient holds a fast aircraft back far harder when drag is quadratic
+    assert!(terminal_speed(DragModel::Quadratic { coeff: 0.5 }) < linear / 5.0);
+    assert_eq!(
+        PhysicsConfig::default().drag,
+        DragModel::Quadratic { coeff: 0.0125 }
+    );
+}
+
+#[test]
+fn storms_buffet_the_2d_aircraft() {
+    let mut calm = Aircraft::with_seed(1984);
+    calm.throttle_level = 0.5;
+    let mut stormy = Aircraft::with_seed(1984);
+    stormy.throttle_level = 0.5;
+    stormy.weather = Weather::Fog;
+    for _ in 0..30 {
+        calm.update(DT);
+        stormy.update(DT);
+    }
+    assert_eq!((calm.vx, calm.vy), (stormy.vx, stormy.vy));
+
+    stormy.weather = Weather::Storm;
+    for _ in 0..30 {
+        calm.update(DT);
+        stormy.update(DT);
+    }
+    assert_ne!((calm.vx, calm.vy), (stormy.vx, stormy.vy));
+}
+
+#[test]
+fn storm_gusts_repeat_for_the_same_seed() {
+    let fly = |seed| {
+        let mut aircraft = Aircraft::with_seed(seed);
+        aircraft.throttle_level = 0.5;
+        aircraft.weather = Weather::Storm;
+        for _ in 0..30 {
+            aircraft.update(DT);
+        }
+        (aircraft.vx, aircraft.vy)
+    };
+    assert_eq!(fly(1984), fly(1984));
+    assert_ne!(fly(1984), fly(1948));
+}
+
+#[test]
+fn weather_cycles_and_fog_hides_the_most() {
+    let mut weather = Weather::default();
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        seen.push(weather);
+        weather = weather.next();
+    }
+    assert_eq!(weather, Weather::Clear);
+    assert_eq!(
+        seen.iter()
+            .min_by(|a, b| a.visibility().total_cmp(&b.visibility())),
+        Some(&Weather::Fog)
+    );
+}
+
+#[test]
+fn aircraft_2d_warnings() {
+    let mut aircraft = Aircraft::new();
+    assert!(!aircraft.stall_warning());
+    assert!(!aircraft.ground_proximity_warning());
+
+    // Nose 30 degrees above a level flight path
+    aircraft.theta = 30.0_f32.to_radians();
+    assert!(aircraft.stall_warning());
+
+    // Low and sinking fast
+    aircraft.theta = 0.0;
+    aircraft.y = 20.0;
+    aircraft.vy = -15.0;
+    assert!(aircraft.ground_proximity_warning());
+}
+
+#[test]
+fn analog_pitch_scales_pitch_rate() {
+    let mut full = Aircraft::new();
+    full.input.pitch_up = true;
+    let mut half = Aircraft::new();
+    half.input.pitch_axis = 0.5;
+
+    full.update(DT);
+    half.update(DT);
+
+    assert!(full.theta > 0.0);
+    assert!((half.theta - full.theta / 2.0).abs() < 1.0e-6);
+}
+
+#[test]
+fn step_full_throttle_from_rest_accelerates_forward() {
+    let mut character = airborne_character();
+    character.throttle = 1.0;
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert!(!step.on_ground);
+    assert!(character.velocity.z > 0.0);
+    assert!(character.position.z > 0.0);
+}
+
+#[test]
+fn step_zero_throttle_falls_under_gravity() {
+    let mut character = airborne_character();
+
+    step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert!((character.velocity.y + 9.81 * DT).abs() < 1.0e-6);
+    assert!(character.position.y < 500.0);
+    assert_eq!(character.velocity.z, 0.0);
+}
+
+#[test]
+fn step_ground_clamp_zeroes_downward_velocity() {
+    let mut character = airborne_character();
+    character.location = "Prole District".to_string();
+    character.position = Point3::new(0.0, 0.01, 0.0);
+    character.velocity = Vector3::new(0.0, -1.0, 2.0);
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert!(step.on_ground);
+    assert_eq!(step.hard_landing, None);
+    assert_eq!(character.position.y, 0.0);
+    assert_eq!(character.velocity.y, 0.0);
+    // Ground friction slows the roll
+    assert!(character.velocity.z < 2.0);
+}
+
+#[test]
+fn step_hard_landing_damages_aircraft() {
+    let mut character = airborne_character();
+    character.location = "Prole District".to_string();
+    character.position = Point3::new(0.0, 0.1, 0.0);
+    character.velocity = Vector3::new(0.0, -10.0, 0.0);
+    let health = character.health;
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    let landing = step.hard_landing.expect("expected a hard landing");
+    assert!(landing.impact_speed > 10.0);
+    assert_eq!(character.health, health - landing.damage);
+}
+
+#[test]
+fn step_skips_non_finite_state() {
+    let mut character = airborne_character();
+    character.velocity = Vector3::new(f32::NAN, 0.0, 0.0);
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert_eq!(character.position, Point3::new(0.0, 500.0, 0.0));
+    assert!(!step.stall_began);
+}
+
+#[test]
+fn touching_down_moves_the_character_to_the_nearest_location() {
+    let world = WorldState::initialize();
+    let mut character = airborne_character();
+    // Sinking gently onto the Prole District after taking off from Victory Mansions
+    character.position = Point3::new(-290.0, terrain_height("Victory Mansions") + 0.01, 490.0);
+    character.velocity = Vector3::new(0.0, -1.0, 0.0);
+    let previous = character.position;
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    let messages = apply_flight_to_map(&mut character, &world, &previous, &step);
+
+    assert!(step.touchdown);
+    assert_eq!(character.location, "Prole District");
+    assert_eq!(character.position.y, terrain_height("Prole District"));
+    assert!(matches!(messages[..], [ServerMessage::NarrativeUpdate(_)]));
+}
+
+#[test]
+fn overflying_the_ministry_of_love_raises_suspicion_once() {
+    let world = WorldState::initialize();
+    let mut character = airborne_character();
+    let outside = Point3::new(500.0, 500.0, -300.0);
+    character.position = Point3::new(800.0, 500.0, -300.0);
+    let suspicion = character.suspicion;
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    let messages = apply_flight_to_map(&mut character, &world, &outside, &step);
+    assert!(matches!(
+        messages[..],
+        [ServerMessage::TeleScreenWarning { .. }]
+    ));
+    assert!(character.suspicion > suspicion);
+
+    // Staying inside doesn't count as entering again
+    let previous = character.position;
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert!(apply_flight_to_map(&mut character, &world, &previous, &step).is_empty());
+    assert_eq!(character.location, "Victory Mansions");
+}
+
+#[test]
+fn physics_config_changes_flight_feel() {
+    let weightless = PhysicsConfig {
+        gravity: 0.0,
+        ..PhysicsConfig::default()
+    };
+    let mut floating = airborne_character();
+    integrate_flight(&mut floating, Vector3::zeros(), DT, &weightless);
+    assert_eq!(floating.position.y, 500.0);
+
+    let powerful = PhysicsConfig {
+        thrust_scale: 40.0,
+        ..PhysicsConfig::default()
+    };
+    let mut fast = airborne_character();
+    fast.throttle = 1.0;
+    integrate_flight(&mut fast, Vector3::zeros(), DT, &powerful);
+    assert!((fast.velocity.z - 40.0 * DT).abs() < 1.0e-6);
+
+    // Ground friction is the share of horizontal speed kept on touching the ground
+    let slippery = PhysicsConfig {
+        ground_friction: 1.0,
+        ..PhysicsConfig::default()
+    };
+    let mut sliding = airborne_character();
+    sliding.position.y = 15.0;
+    sliding.velocity = Vector3::new(0.0, -1.0, 5.0);
+    let mut braking = sliding.clone();
+    step_character_physics(&mut sliding, Vector3::zeros(), DT, &slippery);
+    step_character_physics(
+        &mut braking,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert!((braking.velocity.z - sliding.velocity.z * 0.9).abs() < 1.0e-6);
+}
+
+#[test]
+fn corrupt_orientation_is_reset_to_level_flight() {
+    let mut character = airborne_character();
+    character.orientation = UnitQuaternion::new_unchecked(Quaternion::new(f32::NAN, 0.0, 0.0, 0.0));
+
+    step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert_eq!(character.orientation, UnitQuaternion::identity());
+    // The step still ran, so gravity pulled the aircraft down
+    assert!(character.position.y < 500.0);
+}
+
+#[test]
+fn idle_aircraft_are_not_resent() {
+    let mut character = airborne_character();
+    let sent = SentPose::of(&character);
+    assert!(!sent.differs_from(&character));
+
+    // Sub-centimetre jitter isn't worth a message
+    character.position.y += 0.001;
+    assert!(!sent.differs_from(&character));
+
+    character.position.y += 0.5;
+    assert!(sent.differs_from(&character));
+
+    // An acknowledged input is sent even if it didn't move the aircraft
+    let mut character = airborne_character();
+    character.last_input_seq += 1;
+    assert!(sent.differs_from(&character));
+
+    let mut character = airborne_character();
+    character.orientation = UnitQuaternion::from_euler_angles(0.0, 0.1, 0.0);
+    assert!(sent.differs_from(&character));
+}
+
+#[test]
+fn implausible_speed_is_clamped_and_flagged() {
+    let mut character = airborne_character();
+    character.velocity = Vector3::new(5000.0, 0.0, 0.0);
+    let start = character.position;
+
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+
+    assert!(step.implausible_jump);
+    assert!((character.position - start).norm() <= 120.0 * DT + 1e-3);
+    assert!(character.velocity.norm() <= 120.0 + 1e-3);
+
+    // Ordinary flight is left alone
+    let step = step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert!(!step.implausible_jump);
+}
+
+// How far the wings dip from level: the height of the right wingtip's unit vector
+fn bank(character: &Character) -> f32 {
+    (character.orientation * Vector3::x()).y.abs()
+}
+
+fn banked_character(flight_assist: bool) -> Character {
+    let mut character = airborne_character();
+    character.orientation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), 0.8);
+    character.flight_assist = flight_assist;
+    character
+}
+
+#[test]
+fn flight_assist_levels_the_wings_without_input() {
+    let mut assisted = banked_character(true);
+    let mut unassisted = banked_character(false);
+    let initial_bank = bank(&assisted);
+
+    let mut previous = initial_bank;
+    for _ in 0..300 {
+        step_character_physics(
+            &mut assisted,
+            Vector3::zeros(),
+            DT,
+            &PhysicsConfig::default(),
+        );
+        step_character_physics(
+            &mut unassisted,
+            Vector3::zeros(),
+            DT,
+            &PhysicsConfig::default(),
+        );
+        assert!(bank(&assisted) <= previous + 1.0e-6);
+        previous = bank(&assisted);
+    }
+
+    assert!(bank(&assisted) < 0.05 * initial_bank);
+    // The nose stays where it was pointed
+    assert!((assisted.orientation * Vector3::z() - Vector3::z()).norm() < 1.0e-3);
+    assert!((bank(&unassisted) - initial_bank).abs() < 1.0e-4);
+}
+
+#[test]
+fn flight_assist_gives_way_to_the_stick() {
+    let mut character = banked_character(true);
+    let initial_bank = bank(&character);
+
+    character.stick_held = true;
+    step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert!((bank(&character) - initial_bank).abs() < 1.0e-6);
+    assert!(!character.stick_held);
+
+    // Once the stick is let go the assist takes over again
+    step_character_physics(
+        &mut character,
+        Vector3::zeros(),
+        DT,
+        &PhysicsConfig::default(),
+    );
+    assert!(bank(&character) < initial_bank);
+}
@@ -0,0 +1,84 @@
+use flight_sim::replay::{self, Recorder};
+use flight_sim::{
+    handle_client_message, ClientMessage, Clients, GameState, ServerConfig, Simulation, WorldRng,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+#[test]
+fn replaying_a_recording_reproduces_the_session() {
+    let path = std::env::temp_dir().join(format!("flight_sim_{}_replay.jsonl", std::process::id()));
+    let config = ServerConfig {
+        seed: Some(1984),
+        flight_min_tasks: 0,
+        ..ServerConfig::default()
+    };
+
+    // A live session: two players act between ticks of the game loop
+    let mut live = GameState::new();
+    live.rng = WorldRng::from_seed(1984);
+    live.recorder = Some(Recorder::create(&path).unwrap());
+    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
+    let players = [Uuid::new_v4(), Uuid::new_v4()];
+    let mut outboxes = Vec::new();
+    for id in players {
+        let (sender, receiver) = mpsc::channel(4096);
+        clients.lock().unwrap().insert(id, sender);
+        outboxes.push(receiver);
+    }
+    let mut simulation = Simulation::new(&config);
+    let script = [
+        (
+            players[0],
+            ClientMessage::RequestCharacterCreation {
+                name: "Winston".to_string(),
+                occupation: "Records Department Worker".to_string(),
+            },
+        ),
+        (
+            players[1],
+            ClientMessage::RequestCharacterCreation {
+                name: "Julia".to_string(),
+                occupation: "Fiction Department Writer".to_string(),
+            },
+        ),
+        (
+            players[0],
+            ClientMessage::FlyInput {
+                pitch: 0.3,
+                roll: 0.1,
+                yaw: 0.0,
+                throttle_change: 1.0,
+                seq: 1,
+            },
+        ),
+        (
+            players[1],
+            ClientMessage::MoveRequest {
+                target_location: "Ministry of Truth".to_string(),
+                commit: true,
+            },
+        ),
+        (players[0], ClientMessage::SearchRequest),
+    ];
+    for (id, message) in script {
+        for _ in 0..20 {
+            simulation.tick(&mut live, &clients);
+        }
+        handle_client_message(id, message, &mut live, &clients, &config);
+    }
+    live.recorder = None;
+
+    let entries = replay::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[4].tick, 100);
+
+    let replayed = replay::replay(&entries, &config);
+    assert_eq!(
+        serde_json::to_string(&replayed).unwrap(),
+        serde_json::to_string(&live).unwrap()
+    );
+}
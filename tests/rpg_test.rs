@@ -0,0 +1,382 @@
+use flight_sim::{
+    adjust_relationship, advance_clock, advance_entrapment, check_promotion,
+    diary_discovery_chance, heal_injuries, hour_of_day, morale_penalty, npc_present_at,
+    rest_healing, retrieval_risk_chance, settled_morale, surveillance_heat_map,
+    suspicion_decay_chance, telescreen_suspicion_chance, tick_cat, travel_risk_chance, CatStatus,
+    Character, Entrapment, GameState, ServerMessage, ThoughtcrimeConsequence, WorldState,
+    PILOTING_SKILL, PILOTING_SKILL_TO_FLY, STARTING_MORALE,
+};
+use uuid::Uuid;
+
+fn character() -> Character {
+    Character::new(Uuid::nil(), "Winston".to_string(), "Pilot".to_string())
+}
+
+#[test]
+fn relationship_starts_neutral_and_accumulates() {
+    let mut winston = character();
+
+    assert_eq!(adjust_relationship(&mut winston, "Julia", 5), 5);
+    assert_eq!(adjust_relationship(&mut winston, "Julia", -15), -10);
+    assert_eq!(winston.relationships["Julia"], -10);
+}
+
+#[test]
+fn relationship_clamps_without_wrapping() {
+    let mut winston = character();
+
+    adjust_relationship(&mut winston, "Julia", 90);
+    assert_eq!(adjust_relationship(&mut winston, "Julia", 100), 100);
+
+    adjust_relationship(&mut winston, "Charrington", -90);
+    assert_eq!(adjust_relationship(&mut winston, "Charrington", -128), -100);
+}
+
+#[test]
+fn diary_discovery_scales_with_suspicion() {
+    let mut winston = character();
+    winston.suspicion = 80;
+    assert_eq!(diary_discovery_chance(&winston, 1.0), 0.0);
+
+    winston
+        .journal_entries
+        .push("Down with Big Brother".to_string());
+    let high = diary_discovery_chance(&winston, 1.0);
+    winston.suspicion = 40;
+    let low = diary_discovery_chance(&winston, 1.0);
+    assert!(high > low && low > 0.0);
+
+    winston.suspicion = 10;
+    assert_eq!(diary_discovery_chance(&winston, 1.0), 0.0);
+}
+
+#[test]
+fn hidden_diary_is_harder_to_find() {
+    let mut winston = character();
+    winston.suspicion = 80;
+    winston
+        .journal_entries
+        .push("Down with Big Brother".to_string());
+    let exposed = diary_discovery_chance(&winston, 1.0);
+
+    winston.journal_hiding_place = Some("behind the telescreen alcove".to_string());
+
+    assert!(diary_discovery_chance(&winston, 1.0) < exposed);
+}
+
+#[test]
+fn promotion_needs_sustained_loyalty_and_tasks() {
+    let mut winston = character();
+    winston.loyalty = 90;
+    winston.tasks_completed = 10;
+
+    assert!(!check_promotion(&mut winston, 1));
+    assert!(!check_promotion(&mut winston, 3));
+    // A lapse in loyalty restarts the clock
+    winston.loyalty = 50;
+    assert!(!check_promotion(&mut winston, 4));
+    winston.loyalty = 90;
+    assert!(!check_promotion(&mut winston, 5));
+
+    assert!(check_promotion(&mut winston, 8));
+    assert!(winston.is_inner_party());
+    assert!(!check_promotion(&mut winston, 9));
+}
+
+#[test]
+fn promotion_needs_completed_tasks() {
+    let mut winston = character();
+    winston.loyalty = 90;
+    winston.tasks_completed = 9;
+
+    check_promotion(&mut winston, 1);
+    assert!(!check_promotion(&mut winston, 10));
+}
+
+#[test]
+fn route_takes_the_fewest_hops() {
+    let world = WorldState::initialize();
+
+    let route = world.find_route("Victory Mansions", "Prole District");
+    assert_eq!(
+        route,
+        Some(vec![
+            "Victory Square".to_string(),
+            "Prole District".to_string()
+        ])
+    );
+    assert_eq!(
+        world.find_route("Victory Mansions", "Victory Mansions"),
+        Some(Vec::new())
+    );
+}
+
+#[test]
+fn no_route_out_of_the_ministry_of_love() {
+    let world = WorldState::initialize();
+
+    assert_eq!(
+        world.find_route("Ministry of Love", "Victory Mansions"),
+        None
+    );
+    assert_eq!(world.find_route("Victory Mansions", "Nowhere"), None);
+}
+
+#[test]
+fn unsafe_places_are_riskier_to_reach() {
+    assert_eq!(travel_risk_chance(5, false), 0.0);
+    assert!(travel_risk_chance(1, false) > travel_risk_chance(4, false));
+    assert!(travel_risk_chance(0, true) <= 1.0);
+}
+
+#[test]
+fn night_brings_patrols_to_unsafe_districts_only() {
+    assert!(travel_risk_chance(1, true) > travel_risk_chance(1, false));
+    assert_eq!(travel_risk_chance(4, true), travel_risk_chance(4, false));
+}
+
+#[test]
+fn retrieving_texts_is_riskier_under_closer_watch() {
+    assert_eq!(retrieval_risk_chance(5), 0.0);
+    assert!(retrieval_risk_chance(0) > retrieval_risk_chance(3));
+}
+
+#[test]
+fn clock_pulses_once_per_hour_and_rolls_over_the_day() {
+    let mut state = GameState::new();
+    let hour = 600.0 / 24.0;
+
+    assert!(advance_clock(&mut state, hour / 2.0).is_none());
+    assert!(matches!(
+        advance_clock(&mut state, hour / 2.0),
+        Some(ServerMessage::TimePulse {
+            day: 1,
+            hour: 7,
+            ..
+        })
+    ));
+    assert!(matches!(
+        advance_clock(&mut state, hour * 23.0),
+        Some(ServerMessage::TimePulse {
+            day: 2,
+            hour: 6,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn clock_runs_from_dawn_through_the_night() {
+    let day = 600.0;
+    assert_eq!(hour_of_day(0.0), 6);
+    assert_eq!(hour_of_day(day / 2.0), 18);
+    assert_eq!(hour_of_day(day * 0.75), 0);
+
+    let mut world = WorldState::initialize();
+    assert!(!world.is_night());
+    world.hour = 23;
+    assert!(world.is_night());
+    world.hour = 3;
+    assert!(world.is_night());
+}
+
+#[test]
+fn telescreens_watch_less_at_night() {
+    let mut winston = character();
+    assert_eq!(telescreen_suspicion_chance(&winston, false, 1.0), 0.0);
+
+    winston.thoughtcrime = 50;
+    let day = telescreen_suspicion_chance(&winston, false, 1.0);
+    let night = telescreen_suspicion_chance(&winston, true, 1.0);
+    assert!(day > 0.0);
+    assert!(night < day);
+}
+
+#[test]
+fn cat_trails_its_owner_but_not_into_danger() {
+    let world = WorldState::initialize();
+    let mut rng = rand::thread_rng();
+    let mut winston = character();
+
+    winston.location = "Canteen".to_string();
+    assert_eq!(tick_cat(&mut winston, &world, 1.0, &mut rng), None);
+    assert_eq!(winston.cat_companion.as_ref().unwrap().location, "Canteen");
+
+    winston.location = "Ministry of Truth".to_string();
+    assert!(tick_cat(&mut winston, &world, 1.0, &mut rng).is_some());
+    let cat = winston.cat_companion.as_ref().unwrap();
+    assert_eq!(cat.status, CatStatus::Lost);
+    assert_eq!(cat.location, "Canteen");
+}
+
+#[test]
+fn injured_cat_left_uncared_for_fails_the_quest() {
+    let world = WorldState::initialize();
+    let mut rng = rand::thread_rng();
+    let mut winston = character();
+    let cat = winston.cat_companion.as_mut().unwrap();
+    cat.status = CatStatus::Injured;
+    cat.health = 3;
+
+    // A long enough tick makes every decay roll succeed
+    assert_eq!(tick_cat(&mut winston, &world, 10.0, &mut rng), None);
+    assert_eq!(tick_cat(&mut winston, &world, 10.0, &mut rng), None);
+    assert!(tick_cat(&mut winston, &world, 10.0, &mut rng).is_some());
+    assert!(winston.cat_companion.is_none());
+    assert!(winston.kocourka_quest_failed);
+    assert!(!winston.kocourka_quest_active);
+}
+
+#[test]
+fn suspicion_fades_only_for_those_lying_low() {
+    let world = WorldState::initialize();
+    let mut winston = character();
+    winston.suspicion = 40;
+    assert_eq!(suspicion_decay_chance(&winston, &world, 3, 1.0), 0.0);
+
+    winston.last_loyal_act_day = Some(2);
+    let chance = suspicion_decay_chance(&winston, &world, 3, 1.0);
+    assert!(chance > 0.0);
+    // Slower than the telescreens at their most attentive
+    winston.thoughtcrime = 100;
+    assert!(chance < telescreen_suspicion_chance(&winston, false, 1.0));
+
+    // Loyalty goes stale, danger and detention keep the Party's attention
+    assert_eq!(suspicion_decay_chance(&winston, &world, 5, 1.0), 0.0);
+    winston.location = "Victory Square".to_string();
+    assert_eq!(suspicion_decay_chance(&winston, &world, 3, 1.0), 0.0);
+    winston.location = "Victory Mansions".to_string();
+    winston.detention = Some(ThoughtcrimeConsequence::Surveillance { duration_days: 3 });
+    assert_eq!(suspicion_decay_chance(&winston, &world, 3, 1.0), 0.0);
+}
+
+#[test]
+fn obrien_betrays_confiding_characters_a_day_later() {
+    let mut winston = character();
+    winston.entrapment = Entrapment::Watching { confidences: 2 };
+    assert_eq!(advance_entrapment(&mut winston, 4), None);
+    assert_eq!(winston.entrapment, Entrapment::Watching { confidences: 2 });
+
+    winston.entrapment = Entrapment::Watching { confidences: 3 };
+    assert_eq!(advance_entrapment(&mut winston, 4), None);
+    assert_eq!(winston.entrapment, Entrapment::Scheduled { day: 5 });
+    assert_eq!(advance_entrapment(&mut winston, 4), None);
+
+    let narrative = advance_entrapment(&mut winston, 5).unwrap();
+    assert!(narrative.contains("no darkness"));
+    assert_eq!(winston.suspicion, 95);
+    assert_eq!(winston.entrapment, Entrapment::Betrayed);
+    assert_eq!(advance_entrapment(&mut winston, 9), None);
+}
+
+#[test]
+fn obrien_notices_rebels_who_never_confided() {
+    let mut julia = character();
+    julia.rebellion_score = 50;
+
+    advance_entrapment(&mut julia, 1);
+    assert_eq!(julia.entrapment, Entrapment::Scheduled { day: 2 });
+}
+
+#[test]
+fn heat_map_averages_suspicion_per_location() {
+    let mut state = GameState::new();
+    for (suspicion, location) in [(10, "Canteen"), (30, "Canteen"), (80, "Prole District")] {
+        let mut citizen =
+            Character::new(Uuid::new_v4(), "Citizen".to_string(), "Pilot".to_string());
+        citizen.suspicion = suspicion;
+        citizen.thoughtcrime = suspicion / 2;
+        citizen.location = location.to_string();
+        state.players.insert(citizen.player_id, citizen);
+    }
+
+    let heat_map = surveillance_heat_map(&state);
+
+    assert_eq!(heat_map["Canteen"].players, 2);
+    assert_eq!(heat_map["Canteen"].average_suspicion, 20.0);
+    assert_eq!(heat_map["Canteen"].average_thoughtcrime, 10.0);
+    assert_eq!(heat_map["Prole District"].average_suspicion, 80.0);
+    // Empty locations are listed too
+    assert_eq!(heat_map["Victory Square"].players, 0);
+    assert_eq!(heat_map["Victory Square"].average_suspicion, 0.0);
+}
+
+#[test]
+fn npcs_are_present_only_where_they_stand() {
+    let mut world = WorldState::initialize();
+    let (name, npc) = world.npcs.iter().next().unwrap();
+    let (name, here) = (name.clone(), npc.location.clone());
+
+    assert!(npc_present_at(&world, &name, &here));
+    assert!(!npc_present_at(&world, &name, "Golden Country"));
+    assert!(!npc_present_at(&world, "Emmanuel Goldstein", &here));
+
+    // An NPC who wanders off is no longer there to talk to
+    world.npcs.get_mut(&name).unwrap().location = "Golden Country".to_string();
+    assert!(!npc_present_at(&world, &name, &here));
+}
+
+#[test]
+fn injuries_hold_health_back_until_they_heal() {
+    let mut winston = character();
+    winston.injure("Hard landing", 20);
+    winston.injure("Mid-air collision", 10);
+
+    assert_eq!(winston.health, 70);
+    assert_eq!(winston.max_health(), 85);
+    assert!(rest_healing(&winston) < rest_healing(&character()));
+    winston.heal(50);
+    assert_eq!(winston.health, 85);
+
+    // The lighter injury heals after a day, the worse one takes another
+    assert_eq!(
+        heal_injuries(&mut winston).as_deref(),
+        Some("The injuries from your mid-air collision have finally healed.")
+    );
+    assert_eq!(winston.max_health(), 95);
+    assert!(heal_injuries(&mut winston).is_some());
+    assert!(winston.injuries.is_empty());
+    assert_eq!(winston.max_health(), 100);
+}
+
+#[test]
+fn injuries_never_push_maximum_health_to_nothing() {
+    let mut winston = character();
+    for _ in 0..10 {
+        winston.injure("Mid-air collision", 15);
+    }
+    assert_eq!(winston.health, 0);
+    assert!(winston.max_health() > 0);
+}
+
+#[test]
+fn learning_to_pilot_opens_the_throttle() {
+    let mut clerk = Character::new(Uuid::nil(), "Winston".to_string(), "Clerk".to_string());
+    assert!(!clerk.can_fly(5));
+
+    clerk
+        .skills
+        .insert(PILOTING_SKILL.to_string(), PILOTING_SKILL_TO_FLY);
+    assert!(clerk.can_fly(5));
+}
+
+#[test]
+fn the_cats_wellbeing_decides_where_morale_settles() {
+    let mut winston = character();
+    assert!(settled_morale(&winston) > STARTING_MORALE);
+
+    winston.cat_companion.as_mut().unwrap().status = CatStatus::Lost;
+    assert!(settled_morale(&winston) < STARTING_MORALE);
+
+    winston.cat_companion = None;
+    assert_eq!(settled_morale(&winston), STARTING_MORALE);
+}
+
+#[test]
+fn only_low_morale_spoils_the_odds() {
+    let mut winston = character();
+    assert_eq!(morale_penalty(&winston), 0);
+
+    winston.morale = 0;
+    assert!(morale_penalty(&winston) > 0);
+}
@@ -0,0 +1,53 @@
+use flight_sim::spatial::SpatialIndex;
+use nalgebra::Point3;
+use uuid::Uuid;
+
+// An index with one id per point
+fn index(points: &[Point3<f32>]) -> (SpatialIndex, Vec<Uuid>) {
+    let mut index = SpatialIndex::new(2.0);
+    let ids: Vec<Uuid> = points.iter().map(|_| Uuid::new_v4()).collect();
+    for (id, point) in ids.iter().zip(points) {
+        index.insert(*id, *point);
+    }
+    (index, ids)
+}
+
+#[test]
+fn finds_points_within_the_radius_across_cells() {
+    let (index, ids) = index(&[
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.5, 0.0, 0.0),  // Same cell
+        Point3::new(-0.5, 0.5, 0.0), // Neighbouring cell
+        Point3::new(2.5, 0.0, 0.0),  // Too far
+        Point3::new(100.0, 50.0, -30.0),
+    ]);
+
+    let mut found = index.query_radius(&Point3::origin(), 2.0);
+    found.sort();
+    let mut expected = vec![ids[0], ids[1], ids[2]];
+    expected.sort();
+    assert_eq!(found, expected);
+
+    assert_eq!(
+        index.query_radius(&Point3::new(100.0, 50.0, -29.0), 1.0),
+        vec![ids[4]]
+    );
+    assert!(index
+        .query_radius(&Point3::new(50.0, 0.0, 0.0), 5.0)
+        .is_empty());
+}
+
+#[test]
+fn huge_radius_finds_everything_and_bad_input_nothing() {
+    let (mut index, ids) = index(&[Point3::new(0.0, 0.0, 0.0), Point3::new(-1.0e4, 0.0, 1.0e4)]);
+    index.insert(Uuid::new_v4(), Point3::new(f32::NAN, 0.0, 0.0));
+
+    assert_eq!(
+        index.query_radius(&Point3::origin(), 1.0e6).len(),
+        ids.len()
+    );
+    assert!(index.query_radius(&Point3::origin(), f32::NAN).is_empty());
+
+    index.clear();
+    assert!(index.query_radius(&Point3::origin(), 1.0e6).is_empty());
+}
@@ -0,0 +1,33 @@
+use flight_sim::TickClock;
+use std::time::Duration;
+
+const TICK: Duration = Duration::from_millis(10);
+
+#[test]
+fn ticks_run_as_real_time_accumulates() {
+    let mut clock = TickClock::new(TICK, 5);
+
+    assert_eq!(clock.advance(Duration::from_millis(4)), 0);
+    assert_eq!(clock.until_next_tick(), Duration::from_millis(6));
+    assert_eq!(clock.advance(Duration::from_millis(6)), 1);
+    // A late loop catches up, keeping the leftover for next time
+    assert_eq!(clock.advance(Duration::from_millis(35)), 3);
+    assert_eq!(clock.until_next_tick(), Duration::from_millis(5));
+}
+
+#[test]
+fn catch_up_is_capped_and_the_rest_dropped() {
+    let mut clock = TickClock::new(TICK, 5);
+
+    assert_eq!(clock.advance(Duration::from_millis(1003)), 6);
+    assert_eq!(clock.until_next_tick(), Duration::from_millis(7));
+}
+
+#[test]
+fn without_catch_up_every_loop_is_one_tick() {
+    let mut clock = TickClock::new(TICK, 0);
+
+    assert_eq!(clock.advance(Duration::from_millis(1)), 1);
+    assert_eq!(clock.advance(Duration::from_millis(50)), 1);
+    assert_eq!(clock.until_next_tick(), TICK);
+}
@@ -4,12 +4,25 @@ console.log("1984 RPG Client Script Loaded with Three.js support");
 
 // --- Configuration ---
 const RECONNECT_DELAY = 3000; // Milliseconds
+const PROTOCOL_VERSION = 7; // Must match the server's PROTOCOL_VERSION
+const EVENT_LOG_CATCH_UP = 20; // Recent events asked for on joining
+const PROTOCOL_MISMATCH_CLOSE_CODE = 4000; // Server closed us for speaking another protocol version
+const PING_INTERVAL = 5000; // Milliseconds between latency measurements
+// Ask the server to deflate large messages when the browser can inflate them
+const COMPRESSION_SUPPORTED = typeof DecompressionStream !== 'undefined';
 
 // --- State Variables ---
 let socket = null;
+let inboundQueue = Promise.resolve(); // Keeps messages in order while binary frames inflate
 let myPlayerId = null;
-let currentGameState = null;
-let playerInput = { pitch: 0, roll: 0, yaw: 0, throttle_change: 0 }; // Added input state
+let loggedOut = false; // Set when the player logs out, so the socket closing isn't retried
+let currentGameState = null; // What everyone can see: the world and each player's public profile
+let myCharacter = null; // Our own character, stats and diary included; sent only to us
+let worldMap = null; // Location name -> { connections, safety }; fixed for the session
+let playerInput = { pitch: 0, roll: 0, yaw: 0, throttle_change: 0, seq: 0 }; // Added input state
+let lastAckedInputSeq = 0; // Last FlyInput seq the server has applied
+let flightAssist = true; // Whether the server levels the wings while the stick is let go
+let latencyMs = null; // Round trip of the last answered Ping
 const keysPressed = {}; // Track currently pressed keys
 
 // --- Three.js Variables ---
@@ -38,10 +51,16 @@ const playerListPanel = document.getElementById('player-list');
 const statName = document.getElementById('stat-name');
 const statOccupation = document.getElementById('stat-occupation');
 const statHealth = document.getElementById('stat-health');
+const statMorale = document.getElementById('stat-morale');
 const statLoyalty = document.getElementById('stat-loyalty');
 const statSuspicion = document.getElementById('stat-suspicion');
 const statThoughtcrime = document.getElementById('stat-thoughtcrime');
 const statRebellion = document.getElementById('stat-rebellion');
+const statInjuries = document.getElementById('stat-injuries');
+const statSkills = document.getElementById('stat-skills');
+const clockDate = document.getElementById('clock-date');
+const clockHour = document.getElementById('clock-hour');
+const clockDay = document.getElementById('clock-day');
 
 const locationName = document.getElementById('location-name');
 const locationDescription = document.getElementById('location-description');
@@ -60,7 +79,17 @@ const presentPlayersList = document.getElementById('present-players-list');
 // Action Buttons
 const actionSearchButton = document.getElementById('action-search');
 const actionWorkButton = document.getElementById('action-work');
+const actionAlterRecordButton = document.getElementById('action-alter-record');
+const actionLearnButton = document.getElementById('action-learn');
 const actionRestButton = document.getElementById('action-rest');
+const actionReflectButton = document.getElementById('action-reflect');
+const actionFlightAssistButton = document.getElementById('action-flight-assist');
+const actionHateButton = document.getElementById('action-hate');
+const actionCareCatButton = document.getElementById('action-care-cat');
+const actionCallCatButton = document.getElementById('action-call-cat');
+const actionQueryButton = document.getElementById('action-query');
+const actionLeaderboardButton = document.getElementById('action-leaderboard');
+const actionLogoutButton = document.getElementById('action-logout');
 
 const threeJsContainer = document.getElementById('threejs-container'); // Get the container
 
@@ -161,26 +190,42 @@ function connectWebSocket() {
     // Use current host, assume server runs on same machine for default access
     const host = window.location.hostname || 'localhost';
     const port = window.location.port || '8080'; // Use the default port if not specified
-    const wsUrl = `${protocol}//${host}:${port}/ws`;
+    const query = COMPRESSION_SUPPORTED ? '?compress=deflate' : '';
+    const wsUrl = `${protocol}//${host}:${port}/ws${query}`;
 
     connectionStatus.textContent = `Connecting to ${wsUrl}...`;
     connectionStatus.style.color = '#ff0'; // Yellow
     console.log(`Attempting to connect to WebSocket: ${wsUrl}`);
 
     socket = new WebSocket(wsUrl);
+    socket.binaryType = 'arraybuffer';
 
     socket.onopen = () => {
         console.log("WebSocket connected");
         connectionStatus.textContent = 'Connected to Party Network';
         connectionStatus.style.color = '#0f0'; // Green
+        sendMessage({ Hello: { protocol_version: PROTOCOL_VERSION } });
+        sendPing();
     };
 
     socket.onclose = (event) => {
         console.log("WebSocket disconnected", event);
+        if (event.code === PROTOCOL_MISMATCH_CLOSE_CODE) {
+            // Reconnecting won't help until this page is updated
+            connectionStatus.textContent = event.reason;
+            connectionStatus.style.color = '#f00';
+            return;
+        }
+        if (loggedOut) {
+            connectionStatus.textContent = event.reason || 'Logged out';
+            hideElement(gameContentDiv);
+            return;
+        }
         connectionStatus.textContent = `Disconnected from Party Network (Code: ${event.code}). Reconnecting...`;
         connectionStatus.style.color = '#f00'; // Red
         myPlayerId = null;
         currentGameState = null;
+        myCharacter = null;
         playerIdDisplay.textContent = 'Your ID: N/A';
         hideElement(gameContentDiv);
         hideElement(characterCreationDiv);
@@ -196,17 +241,29 @@ function connectWebSocket() {
     };
 
     socket.onmessage = (event) => {
-        try {
-            const serverMessage = JSON.parse(event.data);
-            console.log("Received message:", serverMessage);
-            handleServerMessage(serverMessage);
-        } catch (e) {
-            console.error('Failed to parse server message:', event.data, e);
-            addLogEntry(`Error: Received unparseable message from server: ${event.data}`, 'error');
-        }
+        inboundQueue = inboundQueue.then(async () => {
+            const text = typeof event.data === 'string' ? event.data : await inflate(event.data);
+            try {
+                const serverMessage = JSON.parse(text);
+                console.log("Received message:", serverMessage);
+                handleServerMessage(serverMessage);
+            } catch (e) {
+                console.error('Failed to parse server message:', text, e);
+                addLogEntry(`Error: Received unparseable message from server: ${text}`, 'error');
+            }
+        }).catch((e) => {
+            console.error('Failed to inflate server message:', e);
+            addLogEntry('Error: Received a corrupt compressed message from server.', 'error');
+        });
     };
 }
 
+// Binary frames carry zlib-deflated JSON
+async function inflate(buffer) {
+    const stream = new Blob([buffer]).stream().pipeThrough(new DecompressionStream('deflate'));
+    return new Response(stream).text();
+}
+
 function sendMessage(clientMessage) {
     if (socket && socket.readyState === WebSocket.OPEN) {
         try {
@@ -229,9 +286,15 @@ function handleServerMessage(msg) {
         case 'Welcome':
             handleWelcome(msg.Welcome);
             break;
+        case 'CharacterCreated':
+            handleCharacterCreated(msg.CharacterCreated);
+            break;
         case 'PlayerJoined':
             handlePlayerJoined(msg.PlayerJoined);
             break;
+        case 'ProfileUpdated':
+            handleProfileUpdated(msg.ProfileUpdated);
+            break;
         case 'PlayerLeft':
             handlePlayerLeft(msg.PlayerLeft);
             break;
@@ -241,9 +304,76 @@ function handleServerMessage(msg) {
         case 'NarrativeUpdate':
             handleNarrativeUpdate(msg.NarrativeUpdate);
             break;
+        case 'Notification':
+            handleNotification(msg.Notification);
+            break;
         case 'Error':
             handleError(msg.Error);
             break;
+        case 'StallWarning':
+            handleStallWarning(msg.StallWarning);
+            break;
+        case 'KnowledgeShared':
+            handleKnowledgeShared(msg.KnowledgeShared);
+            break;
+        case 'ForbiddenTextFound':
+            handleForbiddenTextFound(msg.ForbiddenTextFound);
+            break;
+        case 'VoluntaryExchangeResult':
+            addLogEntry(msg.VoluntaryExchangeResult.result_message, msg.VoluntaryExchangeResult.success ? 'narrative' : 'warning');
+            break;
+        case 'TimePulse':
+            handleTimePulse(msg.TimePulse);
+            break;
+        case 'TeleScreenWarning':
+            addLogEntry(`TELESCREEN: ${msg.TeleScreenWarning.message}`, 'warning');
+            break;
+        case 'AvailableActions':
+            handleAvailableActions(msg.AvailableActions);
+            break;
+        case 'WorldMap':
+            handleWorldMap(msg.WorldMap);
+            break;
+        case 'Pong':
+            handlePong(msg.Pong);
+            break;
+        case 'BatchUpdate':
+            // One tick's messages, applied in the order they happened
+            msg.BatchUpdate.messages.forEach(handleServerMessage);
+            break;
+        case 'PlayerProfile':
+            handlePlayerProfile(msg.PlayerProfile.profile);
+            break;
+        case 'EventLog':
+            handleEventLog(msg.EventLog);
+            break;
+        case 'Leaderboard':
+            handleLeaderboard(msg.Leaderboard);
+            break;
+        case 'HiddenTextList':
+            handleHiddenTextList(msg.HiddenTextList);
+            break;
+        case 'RelationshipReport':
+            handleRelationshipReport(msg.RelationshipReport);
+            break;
+        case 'SelfState':
+            handleSelfState(msg.SelfState);
+            break;
+        case 'StatUpdate':
+            handleStatUpdate(msg.StatUpdate);
+            break;
+        case 'PlayerState':
+            handlePlayerState(msg.PlayerState);
+            break;
+        case 'PlayerMoved':
+            handlePlayerMoved(msg.PlayerMoved);
+            break;
+        case 'LocationReport':
+            handleLocationReport(msg.LocationReport);
+            break;
+        case 'DialoguePrompt':
+            handleDialoguePrompt(msg.DialoguePrompt);
+            break;
         default:
             console.warn("Received unknown message type:", msg);
             addLogEntry(`Warning: Received unknown message type from server.`, 'warning');
@@ -254,19 +384,22 @@ function handleWelcome(data) {
     myPlayerId = data.player_id;
     playerIdDisplay.textContent = `Your ID: ${myPlayerId}`;
     addLogEntry(`Connected to Party Network. Assigned ID: ${myPlayerId}`);
+    // Catch up on what happened before we arrived
+    sendMessage({ RequestEventLog: { limit: EVENT_LOG_CATCH_UP } });
+    if (!worldMap) {
+        sendMessage("RequestMap");
+    }
 
     // Update state immediately with the initial snapshot
     handleGameStateUpdate(data.initial_game_state);
 
     // Check if *this* player already has a character in the initial state
-    const myCharacter = data.initial_game_state.players[myPlayerId];
-
-    if (myCharacter) {
+    if (data.initial_game_state.players[myPlayerId]) {
         console.log("Rejoined game, character already exists.");
         hideElement(characterCreationDiv);
         showElement(gameContentDiv);
-        // Update UI based on existing character immediately
-        updateUI(data.initial_game_state);
+        // The state only has our public profile; the UI fills in once our own character arrives
+        sendMessage("RequestSelf");
     } else {
         console.log("New player, showing character creation.");
         // Need to create character
@@ -275,8 +408,16 @@ function handleWelcome(data) {
     }
 }
 
+function handleCharacterCreated(data) {
+    addLogEntry(`Welcome, ${data.character.name}. Big Brother is watching you.`);
+    myCharacter = data.character;
+    hideElement(characterCreationDiv);
+    showElement(gameContentDiv);
+    updateUI(currentGameState);
+}
+
 function handlePlayerJoined(data) {
-    addLogEntry(`Party Member ${data.character.name} (${data.player_id.substring(0, 6)}...) joined.`);
+    addLogEntry(`Party Member ${data.profile.name} (${data.player_id.substring(0, 6)}...) joined.`);
     // We get full state updates, so we don't *need* to add them manually here,
     // but we could update the player list specifically if needed.
     // The next GameStateUpdate will include them.
@@ -292,11 +433,9 @@ function handleGameStateUpdate(newGameState) {
     console.log("Updating game state:", newGameState);
     const previousGameState = currentGameState;
     currentGameState = newGameState;
-
-    // If game content isn't visible yet, but we now have a character, show it.
-    if (currentGameState.players[myPlayerId] && gameContentDiv.classList.contains('hidden')) {
-        hideElement(characterCreationDiv);
-        showElement(gameContentDiv);
+    // Updates leave the world out while it hasn't changed
+    if (!currentGameState.world_state) {
+        currentGameState.world_state = previousGameState.world_state;
     }
 
     // --- Update 3D Objects based on GameState --- 
@@ -304,35 +443,7 @@ function handleGameStateUpdate(newGameState) {
 
     // Add/Update players present in the new state
     for (const playerId in currentGameState.players) {
-        const playerData = currentGameState.players[playerId];
-
-        if (!players3D[playerId]) {
-            // Player doesn't exist yet, create a 3D object
-            console.log(`Creating 3D object for player ${playerId}`);
-            const geometry = new THREE.BoxGeometry(1, 1, 2); // Simple box for now
-            const material = new THREE.MeshStandardMaterial({
-                color: playerId === myPlayerId ? 0x00ff00 : 0xff0000 // Green for self, red for others
-            });
-            const playerMesh = new THREE.Mesh(geometry, material);
-            scene.add(playerMesh);
-            players3D[playerId] = { mesh: playerMesh, lastUpdate: Date.now() };
-        }
-
-        // Update position and orientation
-        const playerObj = players3D[playerId];
-        if (playerData.position && playerObj) {
-            playerObj.mesh.position.set(playerData.position.x, playerData.position.y, playerData.position.z);
-        }
-        if (playerData.orientation && playerObj) {
-            // Nalgebra Quaternions are likely [x, y, z, w], Three.js uses (x, y, z, w)
-            playerObj.mesh.quaternion.set(
-                playerData.orientation.coords.x,
-                playerData.orientation.coords.y,
-                playerData.orientation.coords.z,
-                playerData.orientation.coords.w
-            );
-        }
-        playerObj.lastUpdate = Date.now(); // Mark as updated
+        updatePlayerMesh(playerId, currentGameState.players[playerId]);
     }
 
     // Remove players that are no longer in the game state
@@ -346,27 +457,222 @@ function handleGameStateUpdate(newGameState) {
         }
     }
 
+    updateClock(currentGameState.day, currentGameState.world_state.current_date, currentGameState.world_state.hour);
+
     // Update the 2D UI elements (existing logic)
     updateUI(currentGameState);
 }
 
+// Create the player's 3D object if needed and put it where their profile says
+function updatePlayerMesh(playerId, playerData) {
+    if (!players3D[playerId]) {
+        // Player doesn't exist yet, create a 3D object
+        console.log(`Creating 3D object for player ${playerId}`);
+        const geometry = new THREE.BoxGeometry(1, 1, 2); // Simple box for now
+        const material = new THREE.MeshStandardMaterial({
+            color: playerId === myPlayerId ? 0x00ff00 : 0xff0000 // Green for self, red for others
+        });
+        const playerMesh = new THREE.Mesh(geometry, material);
+        scene.add(playerMesh);
+        players3D[playerId] = { mesh: playerMesh, lastUpdate: Date.now() };
+    }
+
+    // Update position and orientation
+    const playerObj = players3D[playerId];
+    if (playerData.position && playerObj) {
+        playerObj.mesh.position.set(playerData.position.x, playerData.position.y, playerData.position.z);
+    }
+    if (playerData.orientation && playerObj) {
+        // The server sends {w, x, y, z}; Three.js takes (x, y, z, w)
+        playerObj.mesh.quaternion.set(
+            playerData.orientation.x,
+            playerData.orientation.y,
+            playerData.orientation.z,
+            playerData.orientation.w
+        );
+    }
+    playerObj.lastUpdate = Date.now(); // Mark as updated
+}
+
+// One aircraft moved; only its pose comes with the message
+function handlePlayerState(data) {
+    if (data.player_id === myPlayerId) {
+        lastAckedInputSeq = data.last_input_seq;
+    }
+    const player = currentGameState?.players?.[data.player_id];
+    if (!player) return;
+    player.position = data.position;
+    player.orientation = data.orientation;
+    updatePlayerMesh(data.player_id, player);
+}
+
+// Tint the sky by the in-game hour: dark at night, grey by day
+// Show the in-game time and tint the sky to match
+function updateClock(day, currentDate, hour) {
+    clockDay.textContent = day ?? '??';
+    clockDate.textContent = currentDate ?? '??';
+    clockHour.textContent = hour === undefined ? '??' : `${String(hour).padStart(2, '0')}:00`;
+    updateSky(hour);
+}
+
+function handleTimePulse(pulse) {
+    if (currentGameState) {
+        currentGameState.day = pulse.day;
+        currentGameState.world_state.current_date = pulse.current_date;
+        currentGameState.world_state.hour = pulse.hour;
+    }
+    updateClock(pulse.day, pulse.current_date, pulse.hour);
+}
+
+function updateSky(hour) {
+    if (!scene || hour === undefined) return;
+    const daylight = Math.max(0, Math.sin(((hour - 6) / 24) * 2 * Math.PI));
+    const sky = new THREE.Color(0x0a0a1a).lerp(new THREE.Color(0x8a9099), daylight);
+    scene.background = sky;
+    scene.fog.color = sky;
+}
+
 function handleNarrativeUpdate(text) {
     addLogEntry(text, 'narrative');
 }
 
-function handleError(errorText) {
-    addLogEntry(`BIG BROTHER SAYS: ${errorText}`, 'error');
+function handleNotification(notification) {
+    // notification.level is "Info", "Success", "Warning" or "Danger"
+    addLogEntry(notification.text, notification.level.toLowerCase());
+}
+
+function handleError(error) {
+    // error.code says what went wrong, e.g. "NoCharacter"; error.message is for the player
+    addLogEntry(`BIG BROTHER SAYS: ${error.message}`, 'error');
     // Could also use alert() for critical errors
     // alert(`Server Error: ${errorText}`);
 }
 
+function handleStallWarning(data) {
+    const aoaDegrees = (data.angle_of_attack * 180 / Math.PI).toFixed(0);
+    addLogEntry(`STALL! Airspeed ${data.airspeed.toFixed(1)} m/s, angle of attack ${aoaDegrees}°. Lower the nose.`, 'warning');
+}
+
+function handleKnowledgeShared(data) {
+    addLogEntry(`${data.target_reaction} ${data.consequence}`, data.success ? 'narrative' : 'warning');
+}
+
+function handleForbiddenTextFound(data) {
+    addLogEntry(`Hidden away where no telescreen looks, you find: ${data.texts.join(', ')}.`, 'narrative');
+}
+
+function handlePlayerMoved(data) {
+    const player = currentGameState?.players?.[data.player_id];
+    if (!player) return;
+    player.location = data.to;
+    updateUI(currentGameState);
+}
+
+function handleLocationReport(data) {
+    const list = names => names.length > 0 ? names.join(', ') : 'nobody';
+    addLogEntry(data.description, 'narrative');
+    addLogEntry(`Exits: ${data.connections.join(', ') || 'none'}. NPCs: ${list(data.npcs_present)}. Others: ${list(data.other_players_present)}.`, 'narrative');
+    if (data.texts_hint) {
+        addLogEntry(data.texts_hint, 'narrative');
+    }
+}
+
+function handleDialoguePrompt(data) {
+    const entry = document.createElement('div');
+    entry.classList.add('log-dialogue');
+    const text = document.createElement('p');
+    text.textContent = `${data.npc_name}: ${data.text}`;
+    entry.appendChild(text);
+    data.options.forEach((optionText, optionId) => {
+        const button = document.createElement('button');
+        button.textContent = optionText;
+        button.onclick = () => {
+            // Each prompt can only be answered once
+            entry.querySelectorAll('button').forEach(b => b.disabled = true);
+            sendMessage({ DialogueChoice: { npc_name: data.npc_name, option_id: optionId } });
+        };
+        entry.appendChild(button);
+    });
+    logEntriesDiv.insertBefore(entry, logEntriesDiv.firstChild);
+}
+
+function handleRelationshipReport(data) {
+    const entries = Object.entries(data.relationships);
+    if (entries.length === 0) {
+        addLogEntry("Nobody has formed an opinion of you yet.", 'narrative');
+        return;
+    }
+    const summary = entries.map(([npc, trust]) => `${npc}: ${trust}`).join(', ');
+    addLogEntry(`Relationships - ${summary}`, 'narrative');
+}
+
+function handleHiddenTextList(data) {
+    const entries = Object.entries(data.texts);
+    if (entries.length === 0) {
+        addLogEntry("You have nothing hidden away.", 'narrative');
+        return;
+    }
+    const summary = entries
+        .map(([textId, hidden]) => `${textId}: ${hidden.hiding_place} at ${hidden.location}`)
+        .join(', ');
+    addLogEntry(`Hidden texts - ${summary}`, 'narrative');
+}
+
+function handleAvailableActions(data) {
+    const lines = data.actions.map(action => action.targets.length > 0
+        ? `${action.message}: ${action.targets.join(', ')}`
+        : action.message);
+    addLogEntry(`You could:\n${lines.join('\n')}`, 'narrative');
+}
+
+function handlePong(pong) {
+    latencyMs = Date.now() - pong.client_time;
+    connectionStatus.textContent = `Connected to Party Network (${latencyMs} ms)`;
+}
+
+function sendPing() {
+    if (socket?.readyState === WebSocket.OPEN) {
+        sendMessage({ Ping: { client_time: Date.now() } });
+    }
+}
+
+function handleWorldMap(data) {
+    worldMap = Object.fromEntries(data.locations.map(location => [location.name, location]));
+    console.log(`Map of ${data.locations.length} locations received`);
+}
+
+function handlePlayerProfile(profile) {
+    const carrying = profile.visible_inventory.length > 0
+        ? profile.visible_inventory.join(', ')
+        : 'nothing you can see';
+    addLogEntry(`${profile.name}, ${profile.occupation}, looks ${profile.apparent_health.toLowerCase()}. Carrying: ${carrying}.`, 'narrative');
+}
+
+function handleEventLog(data) {
+    data.events.forEach(event => {
+        const time = new Date(event.timestamp * 1000).toLocaleTimeString();
+        addLogEntry(`[Day ${event.day}, ${time}] ${event.text}`, 'narrative');
+    });
+}
+
+function handleLeaderboard(data) {
+    if (data.entries.length === 0) {
+        addLogEntry("Nobody is on the leaderboard yet.", 'narrative');
+        return;
+    }
+    const standings = data.entries
+        .map((entry, i) => `${i + 1}. ${entry.name} (${entry.score})`)
+        .join(', ');
+    addLogEntry(`Leaderboard - ${standings}`, 'narrative');
+}
+
 // --- UI Update Functions ---
 function updateUI(state) {
     if (!state || !myPlayerId) return; // Can't update if no state or ID
 
-    const myCharacter = state.players[myPlayerId];
+    const myProfile = state.players[myPlayerId];
 
-    if (!myCharacter) {
+    if (!myProfile) {
         // Player exists in state but this client doesn't have a character yet?
         // Or player was removed (died/arrested)
         console.log("My character data not found in game state. Hiding game content.");
@@ -375,6 +681,9 @@ function updateUI(state) {
         // Or show a "Game Over" screen.
         return;
     }
+    if (!myCharacter) return; // Still waiting for our own character
+    // Where we are is public, and the state update may be newer than our own copy
+    myCharacter.location = myProfile.location;
 
     // Update Character Stats Panel
     updateCharacterStatsUI(myCharacter);
@@ -392,14 +701,45 @@ function updateUI(state) {
     updatePlayerListUI(state.players, myCharacter.location);
 }
 
+function handleProfileUpdated(data) {
+    if (!currentGameState) return;
+    const previous = currentGameState.players[data.player_id];
+    if (previous && previous.name !== data.profile.name) {
+        addLogEntry(`Citizen ${previous.name} is now known as ${data.profile.name}.`);
+    }
+    currentGameState.players[data.player_id] = data.profile;
+    updateUI(currentGameState);
+}
+
+function handleSelfState(character) {
+    myCharacter = character;
+    updateUI(currentGameState);
+}
+
+function handleStatUpdate(stats) {
+    if (!myCharacter) return;
+    Object.assign(myCharacter, stats);
+    updateCharacterStatsUI(myCharacter);
+    updateInventoryUI(myCharacter);
+}
+
 function updateCharacterStatsUI(character) {
     statName.textContent = character.name || 'N/A';
     statOccupation.textContent = character.occupation || 'N/A';
     statHealth.textContent = character.health ?? '??';
+    statMorale.textContent = character.morale ?? '??';
     statLoyalty.textContent = character.loyalty ?? '??';
     statSuspicion.textContent = character.suspicion ?? '??';
     statThoughtcrime.textContent = character.thoughtcrime ?? '??';
     statRebellion.textContent = character.rebellion_score ?? '??';
+    const injuries = character.injuries ?? [];
+    statInjuries.textContent = injuries.length > 0
+        ? injuries.map(injury => `${injury.cause} (-${injury.severity})`).join(', ')
+        : 'none';
+    const skills = Object.entries(character.skills ?? {});
+    statSkills.textContent = skills.length > 0
+        ? skills.map(([subject, level]) => `${subject} ${level}`).join(', ')
+        : 'none';
 }
 
 function updateLocationUI(worldState, myCharacter) {
@@ -435,9 +775,10 @@ function updateLocationUI(worldState, myCharacter) {
         .forEach(([npcName, npcData]) => {
             const button = document.createElement('button');
             button.textContent = npcName;
-            // TODO: Implement more complex interaction system later
-            // For now, just a basic interaction request
-            button.onclick = () => sendInteractRequest(npcName, 1); // Using 1 as placeholder type
+            // NPCs with something to say open a conversation
+            button.onclick = Object.keys(npcData.dialogue ?? {}).length > 0
+                ? () => sendMessage({ StartDialogue: { npc_name: npcName } })
+                : () => sendInteractRequest(npcName, 1); // Using 1 as placeholder type
             npcOptionsDiv.appendChild(button);
         });
 }
@@ -471,7 +812,19 @@ function updatePlayerListUI(allPlayers, myLocation) {
         .forEach(([id, char]) => {
             playersFound = true;
             const li = document.createElement('li');
-            li.textContent = `${char.name} (${char.occupation}) - ${id.substring(0, 6)}...`;
+            li.textContent = `${char.name} (${char.occupation}) - ${id.substring(0, 6)}... `;
+            const reportButton = document.createElement('button');
+            reportButton.textContent = 'Report';
+            reportButton.onclick = () => reportPlayer(char.name);
+            li.appendChild(reportButton);
+            const examineButton = document.createElement('button');
+            examineButton.textContent = 'Examine';
+            examineButton.onclick = () => sendMessage({ ExaminePlayer: { target_name: char.name } });
+            li.appendChild(examineButton);
+            const giveButton = document.createElement('button');
+            giveButton.textContent = 'Give text';
+            giveButton.onclick = () => giveText(char.name);
+            li.appendChild(giveButton);
             presentPlayersList.appendChild(li);
         });
 
@@ -484,6 +837,18 @@ function updatePlayerListUI(allPlayers, myLocation) {
 
 
 // --- Action Sending Functions ---
+function reportPlayer(targetName) {
+    const accusation = prompt(`What do you accuse ${targetName} of?`, 'thoughtcrime');
+    if (!accusation) return;
+    sendMessage({ ReportPlayer: { target_name: targetName, accusation } });
+}
+
+function giveText(targetName) {
+    const textId = prompt(`Which text do you pass to ${targetName}?`);
+    if (!textId) return;
+    sendMessage({ GiveText: { target_name: targetName, text_id: textId.trim() } });
+}
+
 function sendCharacterCreation() {
     const name = charNameInput.value.trim();
     const occupation = charOccupationSelect.value;
@@ -495,7 +860,11 @@ function sendCharacterCreation() {
 }
 
 function sendMoveRequest(targetLocation) {
-    sendMessage({ MoveRequest: { target_location: targetLocation } });
+    // Dangerous destinations need the player to commit to the move
+    const safety = currentGameState?.world_state?.locations?.[targetLocation]?.safety ?? 5;
+    const commit = safety <= 1
+        && confirm(`${targetLocation} is dangerous. Go anyway?`);
+    sendMessage({ MoveRequest: { target_location: targetLocation, commit } });
 }
 
 function sendInteractRequest(npcName, interactionType) {
@@ -509,6 +878,7 @@ function sendJournalWrite() {
         return;
     }
     sendMessage({ JournalWriteRequest: { entry } });
+    sendMessage("RequestSelf"); // Fetch the diary back as the server kept it
     journalEntryInput.value = ''; // Clear input after sending
 }
 
@@ -520,10 +890,62 @@ function sendWorkRequest() {
     sendMessage({ WorkRequest: {} });
 }
 
+function sendAlterRecord() {
+    const recordId = prompt('Which record needs correcting?', 'times-1983-12-19');
+    if (!recordId) return;
+    const newContent = prompt(`What has ${recordId} always said?`);
+    if (!newContent) return;
+    sendMessage({ AlterRecord: { record_id: recordId.trim(), new_content: newContent } });
+}
+
+function sendLearnFromNpc() {
+    const npcName = prompt('Who will you ask to teach you?', 'Syme');
+    if (!npcName) return;
+    const subject = prompt(`What will you ask ${npcName} to teach you?`, 'Newspeak');
+    if (!subject) return;
+    sendMessage({ LearnFromNpc: { npc_name: npcName.trim(), subject: subject.trim() } });
+}
+
 function sendRestRequest() {
     sendMessage({ RestRequest: {} });
 }
 
+function sendReflect() {
+    sendMessage("Reflect");
+}
+
+function toggleFlightAssist() {
+    flightAssist = !flightAssist; // New characters start with the assist on
+    sendMessage({ SetFlightAssist: { enabled: flightAssist } });
+    actionFlightAssistButton.textContent = `Flight Assist: ${flightAssist ? 'On' : 'Off'}`;
+}
+
+function sendLogout() {
+    const save = confirm('Keep your character so you can return to it later?');
+    loggedOut = true;
+    sendMessage({ Disconnect: { save } });
+}
+
+function sendParticipateInHate() {
+    sendMessage("ParticipateInHate");
+}
+
+function sendCareForCat() {
+    sendMessage("CareForCat");
+}
+
+function sendCallCat() {
+    sendMessage("CallCat");
+}
+
+function sendQueryActions() {
+    sendMessage("QueryActions");
+}
+
+function sendLeaderboardRequest() {
+    sendMessage("RequestLeaderboard");
+}
+
 // --- Input Handling --- 
 function setupInputListeners() {
     window.addEventListener('keydown', (event) => {
@@ -555,6 +977,7 @@ function handleInput() {
 
     // Send FlyInput message if any input is active
     if (inputChanged) {
+        playerInput.seq += 1;
         sendMessage({ FlyInput: playerInput });
     }
 }
@@ -564,7 +987,17 @@ createCharButton.addEventListener('click', sendCharacterCreation);
 journalSubmitButton.addEventListener('click', sendJournalWrite);
 actionSearchButton.addEventListener('click', sendSearchRequest);
 actionWorkButton.addEventListener('click', sendWorkRequest);
+actionAlterRecordButton.addEventListener('click', sendAlterRecord);
+actionLearnButton.addEventListener('click', sendLearnFromNpc);
 actionRestButton.addEventListener('click', sendRestRequest);
+actionReflectButton.addEventListener('click', sendReflect);
+actionFlightAssistButton.addEventListener('click', toggleFlightAssist);
+actionHateButton.addEventListener('click', sendParticipateInHate);
+actionCareCatButton.addEventListener('click', sendCareForCat);
+actionCallCatButton.addEventListener('click', sendCallCat);
+actionQueryButton.addEventListener('click', sendQueryActions);
+actionLeaderboardButton.addEventListener('click', sendLeaderboardRequest);
+actionLogoutButton.addEventListener('click', sendLogout);
 
 // Add listener for Enter key on journal input
 journalEntryInput.addEventListener('keypress', function (e) {
@@ -581,13 +1014,24 @@ function init() {
     setupInputListeners(); // Setup keyboard listeners
     animate();          // Start the render loop
     connectWebSocket(); // Connect WebSocket
+    setInterval(sendPing, PING_INTERVAL); // Keep the latency readout fresh
 
     // Add event listeners for existing UI
     createCharButton?.addEventListener('click', sendCharacterCreation);
     journalSubmitButton?.addEventListener('click', sendJournalWrite);
     actionSearchButton?.addEventListener('click', sendSearchRequest);
     actionWorkButton?.addEventListener('click', sendWorkRequest);
+    actionAlterRecordButton?.addEventListener('click', sendAlterRecord);
+    actionLearnButton?.addEventListener('click', sendLearnFromNpc);
     actionRestButton?.addEventListener('click', sendRestRequest);
+    actionReflectButton?.addEventListener('click', sendReflect);
+    actionFlightAssistButton?.addEventListener('click', toggleFlightAssist);
+    actionHateButton?.addEventListener('click', sendParticipateInHate);
+    actionCareCatButton?.addEventListener('click', sendCareForCat);
+    actionCallCatButton?.addEventListener('click', sendCallCat);
+    actionQueryButton?.addEventListener('click', sendQueryActions);
+    actionLeaderboardButton?.addEventListener('click', sendLeaderboardRequest);
+    actionLogoutButton?.addEventListener('click', sendLogout);
 
     // Event delegation for dynamic buttons (move, interact)
     locationInfoPanel?.addEventListener('click', (event) => {